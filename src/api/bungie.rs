@@ -1,15 +1,31 @@
-use reqwest::header::{HeaderMap, HeaderValue};
+use std::{sync::{Arc, Mutex}, time::Duration};
+
+use dashmap::DashMap;
+use reqwest::{RequestBuilder, StatusCode};
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
+use serde_json::Value;
+use sqlx::PgPool;
+use thiserror::Error;
+use tokio::{sync::Semaphore, time::Instant};
+use tracing::warn;
+
+use crate::{api::manifest::{DestinyManifest, ManifestTables}, bot::commands::commands::BotResult};
 
-use crate::bot::{commands::commands::BotResult, state::def::BotError};
+const BUNGIE_ROOT: &str = "https://www.bungie.net";
+/// Víc souběžných requestů na Bungie nepouštíme, ať nespadneme do throttlu
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const MAX_ATTEMPTS: u32 = 3;
+/// Delší throttle nečekáme, join by visel – vrátíme `Throttled`
+const MAX_THROTTLE_WAIT: Duration = Duration::from_secs(10);
+const NAME_CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+const NAME_NOT_FOUND_TTL: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Serialize)]
-struct BungieName {
+struct BungieName<'a> {
     #[serde(rename = "displayName")]
-    name: String,
+    name: &'a str,
     #[serde(rename = "displayNameCode")]
-    code: String,
+    code: &'a str,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -20,76 +36,297 @@ pub struct MemberShip {
     pub type_m: i32,
 }
 
+/// Bungie účet: primární (cross-save) membership, na které jsou postavy, a všechny propojené
+#[derive(Debug, Clone)]
+pub struct BungieAccount {
+    pub primary: MemberShip,
+    pub linked: Vec<MemberShip>,
+}
+
+#[derive(Deserialize)]
+struct SearchResult {
+    #[serde(flatten)]
+    membership: MemberShip,
+    #[serde(rename = "crossSaveOverride", default)]
+    cross_save_override: i32,
+}
+
+#[derive(Deserialize)]
+struct LinkedProfiles {
+    #[serde(default)]
+    profiles: Vec<LinkedProfile>,
+}
+
+#[derive(Deserialize)]
+struct LinkedProfile {
+    #[serde(flatten)]
+    membership: MemberShip,
+    #[serde(rename = "isCrossSavePrimary", default)]
+    cross_save_primary: bool,
+    #[serde(rename = "dateLastPlayed", default)]
+    last_played: String,
+}
+
+/// Obálka, ve které Bungie vrací každou odpověď
 #[derive(Deserialize, Debug)]
-struct MembershipIdResponse {
-    Response: Vec<MemberShip>,
-    ErrorCode: i32,
-    ThrottleSeconds: i32,
-    ErrorStatus: String,
-    Message: String,
-    MessageData: serde_json::Value,
+#[serde(rename_all = "PascalCase")]
+struct Envelope {
+    #[serde(default)]
+    response: Value,
+    error_code: i32,
+    #[serde(default)]
+    throttle_seconds: i64,
+    #[serde(default)]
+    error_status: String,
+    #[serde(default)]
+    message: String,
+}
+
+#[derive(Debug, Error)]
+pub enum BungieError {
+    #[error("Bungie account not found")]
+    NotFound,
+    #[error("Invalid Bungie name: {0}")]
+    InvalidName(String),
+    #[error("Destiny profile is private")]
+    Private,
+    #[error("Bungie API is down for maintenance")]
+    Maintenance,
+    #[error("Bungie API throttled us for {0}s")]
+    Throttled(u64),
+    #[error("Bungie API error {code} ({status}): {message}")]
+    Api { code: i32, status: String, message: String },
+    #[error("Bungie API responded with HTTP {0}")]
+    Status(StatusCode),
+    #[error("Bungie request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Unexpected Bungie response: {0}")]
+    Json(#[from] serde_json::Error),
 }
 
-//https://www.bungie.net/Platform/Destiny2/ {MembershipType} /Account/ {MembershipId} /Character/0/Stats/?groups=&modes=4 and ['Response']['raid']['allTime']['activitiesCleared']['basic']['displayValue']
-pub async fn get_membershipid(bungie_name: &str, x_api_key: &str) -> BotResult<MemberShip> {
-    let bungie_name = bungie_name.to_string();
-    let (display_name, display_name_code) = bungie_name.split_once("#").unwrap();
-    
-    let bungie_name = BungieName {
-        name: display_name.to_string(),
-        code: display_name_code.to_string(),
-    };
-
-    let mut headers = HeaderMap::new();
-    headers.insert("X-API-Key", HeaderValue::from_str(x_api_key).unwrap());
-    //headers.insert("User-Agent", HeaderValue::from_str("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36").unwrap());
-
-    let res = reqwest::Client::new()
-        .post("https://www.bungie.net/Platform/Destiny2/SearchDestinyPlayerByBungieName/All/")
-        .headers(headers)
-        .json(&bungie_name)
-        .send()
-        .await?;
-
-    if res.status().is_success() {
-        let body = res.text().await.unwrap();
-        let body: MembershipIdResponse = from_str(&body).unwrap();
-
-        let mut users: Vec<MemberShip> = vec![];
-        for user in body.Response {
-            println!("membershipId: {}, membershipType: {}", user.id, user.type_m);
-            users.push(user);
+impl BungieError {
+    /// Chyba na straně Bungie (výpadek, throttle, síť), ne špatné jméno nebo soukromý profil
+    pub fn is_unavailable(&self) -> bool {
+        !matches!(self, BungieError::NotFound | BungieError::InvalidName(_) | BungieError::Private)
+    }
+
+    fn from_envelope(envelope: &Envelope) -> Self {
+        match envelope.error_status.as_str() {
+            "SystemDisabled" => BungieError::Maintenance,
+            "DestinyAccountNotFound" => BungieError::NotFound,
+            "DestinyPrivacyRestriction" => BungieError::Private,
+            status if status.contains("Throttle") || envelope.throttle_seconds > 0 => {
+                BungieError::Throttled(envelope.throttle_seconds.max(1) as u64)
+            }
+            status => BungieError::Api {
+                code: envelope.error_code,
+                status: status.to_string(),
+                message: envelope.message.clone(),
+            },
         }
-        if users.len() == 0 {
-            Ok(MemberShip {
-                id: String::new(),
-                type_m: -1,
-            })
-        } else {
-            Ok(users[0].clone())
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            BungieError::Http(_) => true,
+            BungieError::Status(status) => status.is_server_error(),
+            _ => false,
         }
-    } else {
-        println!("Request failed with status: {}", res.status());
-        Err(BotError::Custom("Poopoo".to_string()))
     }
 }
 
-pub struct BungieUser {
-    pub bungie_name: String,
-    pub membership_id: String,
-    pub membership_type: i32,
+struct CachedName {
+    account: Option<BungieAccount>,
+    fetched: Instant,
 }
 
-pub async fn is_real_bungiename(
-    x_api_key: &str,
-    bungie_name: &str,
-) -> Result<BungieUser, ()> {
-    match get_membershipid(bungie_name, x_api_key).await {
-        Ok(info) if info.type_m != -1 => Ok(BungieUser {
-            bungie_name: bungie_name.to_string(),
-            membership_id: info.id.to_string(),
-            membership_type: info.type_m,
-        }),
-        _ => Err(()),
-    }
-}
\ No newline at end of file
+impl CachedName {
+    fn is_fresh(&self) -> bool {
+        let ttl = if self.account.is_some() { NAME_CACHE_TTL } else { NAME_NOT_FOUND_TTL };
+        self.fetched.elapsed() < ttl
+    }
+}
+
+/// Sdílený klient pro Bungie API: jeden `reqwest::Client`, limit souběžných requestů,
+/// respektuje `ThrottleSeconds` a cachuje hledání podle Bungie jména.
+pub struct BungieClient {
+    http: reqwest::Client,
+    api_key: String,
+    permits: Semaphore,
+    throttled_until: Mutex<Option<Instant>>,
+    names: DashMap<String, CachedName>,
+    manifest: DestinyManifest,
+}
+
+impl BungieClient {
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: api_key.into(),
+            permits: Semaphore::new(MAX_CONCURRENT_REQUESTS),
+            throttled_until: Mutex::new(None),
+            names: DashMap::new(),
+            manifest: DestinyManifest::default(),
+        }
+    }
+
+    /// Tabulky z Destiny manifestu, verze se kontroluje nejvýš jednou za hodinu
+    pub async fn manifest(&self, pool: &PgPool) -> BotResult<Arc<ManifestTables>> {
+        self.manifest.tables(pool, self).await
+    }
+
+    /// GET na `/Platform{path}`, vrací obsah `Response`
+    pub async fn get(&self, path: &str) -> Result<Value, BungieError> {
+        let url = format!("{BUNGIE_ROOT}/Platform{path}");
+        self.request(|| self.http.get(&url)).await
+    }
+
+    pub async fn post<T: Serialize + ?Sized>(&self, path: &str, body: &T) -> Result<Value, BungieError> {
+        let url = format!("{BUNGIE_ROOT}/Platform{path}");
+        self.request(|| self.http.post(&url).json(body)).await
+    }
+
+    /// Statický obsah mimo Platform API (soubory manifestu)
+    pub async fn get_content(&self, path: &str) -> Result<String, BungieError> {
+        let url = format!("{BUNGIE_ROOT}{path}");
+        let _permit = self.permits.acquire().await.ok();
+        let res = self.http.get(&url).header("X-API-Key", &self.api_key).send().await?;
+        if !res.status().is_success() {
+            return Err(BungieError::Status(res.status()));
+        }
+        Ok(res.text().await?)
+    }
+
+    /// Primární membership podle `Name#1234`
+    pub async fn find_membership(&self, bungie_name: &str) -> Result<MemberShip, BungieError> {
+        self.find_account(bungie_name).await.map(|account| account.primary)
+    }
+
+    /// Účet podle `Name#1234` včetně cross-save propojení. Nenalezená jména se cachují kratší dobu.
+    pub async fn find_account(&self, bungie_name: &str) -> Result<BungieAccount, BungieError> {
+        let key = bungie_name.to_lowercase();
+        if let Some(cached) = self.names.get(&key).filter(|c| c.is_fresh()) {
+            return cached.account.clone().ok_or(BungieError::NotFound);
+        }
+
+        let (name, code) = bungie_name
+            .rsplit_once('#')
+            .ok_or_else(|| BungieError::InvalidName(bungie_name.to_string()))?;
+        let response = self
+            .post("/Destiny2/SearchDestinyPlayerByBungieName/All/", &BungieName { name, code })
+            .await?;
+        let results: Vec<SearchResult> = serde_json::from_value(response)?;
+
+        // Při cross-save je přepsaný typ ten, na kterém hráč hraje
+        let found = results
+            .iter()
+            .find(|r| r.cross_save_override == r.membership.type_m)
+            .or(results.first());
+        let account = match found {
+            None => None,
+            Some(found) => Some(match self.linked_account(&found.membership).await {
+                Ok(account) => account,
+                Err(err) if err.is_unavailable() => return Err(err),
+                Err(err) => {
+                    warn!("Linked profiles for {} failed, using search result: {}", bungie_name, err);
+                    BungieAccount {
+                        primary: found.membership.clone(),
+                        linked: results.iter().map(|r| r.membership.clone()).collect(),
+                    }
+                }
+            }),
+        };
+
+        self.names.insert(key, CachedName { account: account.clone(), fetched: Instant::now() });
+        account.ok_or(BungieError::NotFound)
+    }
+
+    /// Zahodí jméno z cache, třeba když ho hráč změnil
+    pub fn forget_name(&self, bungie_name: &str) {
+        self.names.remove(&bungie_name.to_lowercase());
+    }
+
+    /// Propojené profily, primární je ten s `isCrossSavePrimary`, jinak naposledy hraný
+    async fn linked_account(&self, membership: &MemberShip) -> Result<BungieAccount, BungieError> {
+        let response = self
+            .get(&format!("/Destiny2/{}/Profile/{}/LinkedProfiles/?getAllMemberships=true", membership.type_m, membership.id))
+            .await?;
+        let profiles = serde_json::from_value::<LinkedProfiles>(response)?.profiles;
+
+        let primary = profiles
+            .iter()
+            .find(|p| p.cross_save_primary)
+            .or_else(|| profiles.iter().max_by(|a, b| a.last_played.cmp(&b.last_played)))
+            .map_or_else(|| membership.clone(), |p| p.membership.clone());
+        let mut linked: Vec<MemberShip> = profiles.into_iter().map(|p| p.membership).collect();
+        if linked.is_empty() {
+            linked.push(membership.clone());
+        }
+
+        Ok(BungieAccount { primary, linked })
+    }
+
+    async fn request(&self, build: impl Fn() -> RequestBuilder) -> Result<Value, BungieError> {
+        let mut attempt = 1;
+        loop {
+            self.wait_for_throttle().await?;
+            let result = {
+                let _permit = self.permits.acquire().await.ok();
+                self.send(build()).await
+            };
+
+            match result {
+                Err(BungieError::Throttled(secs)) if attempt < MAX_ATTEMPTS => {
+                    warn!("Bungie API throttled for {secs}s (attempt {attempt})");
+                    self.throttle_for(Duration::from_secs(secs));
+                }
+                Err(err) if err.is_retryable() && attempt < MAX_ATTEMPTS => {
+                    warn!("Bungie request failed (attempt {attempt}): {err}");
+                    tokio::time::sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1))).await;
+                }
+                other => return other,
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Value, BungieError> {
+        let res = request.header("X-API-Key", &self.api_key).send().await?;
+        let status = res.status();
+        let body = res.text().await?;
+
+        // Údržba chodí jako 503 s normální obálkou, takže nejdřív zkusíme JSON
+        let envelope: Envelope = match serde_json::from_str(&body) {
+            Ok(envelope) => envelope,
+            Err(err) if status.is_success() => return Err(err.into()),
+            Err(_) => return Err(BungieError::Status(status)),
+        };
+
+        if envelope.error_code == 1 {
+            Ok(envelope.response)
+        } else {
+            Err(BungieError::from_envelope(&envelope))
+        }
+    }
+
+    fn throttle_for(&self, wait: Duration) {
+        let until = Instant::now() + wait;
+        if let Ok(mut throttled) = self.throttled_until.lock() {
+            if throttled.is_none_or(|current| current < until) {
+                *throttled = Some(until);
+            }
+        }
+    }
+
+    async fn wait_for_throttle(&self) -> Result<(), BungieError> {
+        let until = self.throttled_until.lock().ok().and_then(|t| *t);
+        if let Some(until) = until {
+            let wait = until.saturating_duration_since(Instant::now());
+            if wait > MAX_THROTTLE_WAIT {
+                return Err(BungieError::Throttled(wait.as_secs()));
+            }
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+}
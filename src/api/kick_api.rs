@@ -103,7 +103,30 @@ async fn get_broadcaster_user_id(channel_slug: &str) -> BotResult<u64> {
     Ok(id)
 }
 
+/// Kick nemá helix `streams`, živé vysílání je v `livestream` veřejného kanálu (null = offline)
+pub async fn is_kick_channel_live(channel_slug: &str) -> BotResult<bool> {
+    let key = normalize_channel_slug(channel_slug);
+    let value = fetch_public_channel(&key).await?;
+
+    if let Some(id) = value.get("user").and_then(|u| u.get("id")).and_then(|v| v.as_u64()) {
+        BROADCASTER_CACHE.insert(key, id);
+    }
+
+    Ok(value.get("livestream").is_some_and(|l| !l.is_null()))
+}
+
 async fn fetch_broadcaster_user_id_from_public_api(channel_slug: &str) -> BotResult<u64> {
+    let value = fetch_public_channel(channel_slug).await?;
+    let broadcaster_user_id = value
+        .get("user")
+        .and_then(|u| u.get("id"))
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| BotError::Custom("Kick response missing user.id for broadcaster".to_string()))?;
+
+    Ok(broadcaster_user_id)
+}
+
+async fn fetch_public_channel(channel_slug: &str) -> BotResult<Value> {
     let url = format!("https://kick.com/api/v2/channels/{channel_slug}");
     let client = reqwest::Client::new();
     let response = client
@@ -126,14 +149,7 @@ async fn fetch_broadcaster_user_id_from_public_api(channel_slug: &str) -> BotRes
         )));
     }
 
-    let value: Value = serde_json::from_str(&body)?;
-    let broadcaster_user_id = value
-        .get("user")
-        .and_then(|u| u.get("id"))
-        .and_then(|v| v.as_u64())
-        .ok_or_else(|| BotError::Custom("Kick response missing user.id for broadcaster".to_string()))?;
-
-    Ok(broadcaster_user_id)
+    Ok(serde_json::from_str(&body)?)
 }
 
 fn truncate_message(input: &str, max_len: usize) -> String {
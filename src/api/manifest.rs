@@ -0,0 +1,180 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::{sync::{Mutex, RwLock}, time::Instant};
+use tracing::{info, warn};
+
+use crate::{api::bungie::BungieClient, bot::{commands::commands::BotResult, db::manifest::{load_latest_manifest_component, load_manifest_component, save_manifest_component}, state::def::BotError}};
+
+const RECORD_DEFINITIONS: &str = "DestinyRecordDefinition";
+const OBJECTIVE_DEFINITIONS: &str = "DestinyObjectiveDefinition";
+const ACTIVITY_DEFINITIONS: &str = "DestinyActivityDefinition";
+/// Jak často se ptáme Bungie na verzi manifestu
+const VERSION_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Tabulky z manifestu zmenšené na to, co používáme: název triumfu -> hash, hash objektivu -> popis
+/// a hash aktivity -> název
+pub struct ManifestTables {
+    pub version: String,
+    records: HashMap<String, String>,
+    objectives: HashMap<String, String>,
+    activities: HashMap<String, String>,
+}
+
+impl ManifestTables {
+    /// Hash triumfu podle názvu (bez ohledu na velikost písmen)
+    pub fn record_hash(&self, name: &str) -> Option<&str> {
+        self.records.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    pub fn objective_name(&self, hash: u64) -> Option<&str> {
+        self.objectives.get(&hash.to_string()).map(String::as_str)
+    }
+
+    pub fn activity_name(&self, hash: u64) -> Option<&str> {
+        self.activities.get(&hash.to_string()).map(String::as_str)
+    }
+}
+
+#[derive(Default)]
+struct ManifestState {
+    tables: Option<Arc<ManifestTables>>,
+    checked_at: Option<Instant>,
+}
+
+/// Destiny manifest v paměti. Tabulky se stahují jen při změně verze a ukládají do Postgresu,
+/// takže redeploy nemusí nic stahovat znovu.
+#[derive(Default)]
+pub struct DestinyManifest {
+    state: RwLock<ManifestState>,
+    refreshing: Mutex<()>,
+}
+
+impl DestinyManifest {
+    pub async fn tables(&self, pool: &PgPool, bungie: &BungieClient) -> BotResult<Arc<ManifestTables>> {
+        if let Some(tables) = self.fresh_tables().await {
+            return Ok(tables);
+        }
+
+        // Jen jedna kontrola verze naráz, ostatní počkají na její výsledek
+        let _refreshing = self.refreshing.lock().await;
+        if let Some(tables) = self.fresh_tables().await {
+            return Ok(tables);
+        }
+
+        let current = self.state.read().await.tables.clone();
+        let tables = match refresh(pool, bungie, current.clone()).await {
+            Ok(tables) => tables,
+            Err(err) => {
+                warn!("Destiny manifest refresh failed: {}", err);
+                match current {
+                    Some(tables) => tables,
+                    None => load_stored(pool).await?.ok_or(err)?,
+                }
+            }
+        };
+
+        let mut state = self.state.write().await;
+        state.tables = Some(tables.clone());
+        state.checked_at = Some(Instant::now());
+        Ok(tables)
+    }
+
+    async fn fresh_tables(&self) -> Option<Arc<ManifestTables>> {
+        let state = self.state.read().await;
+        let fresh = state.checked_at.is_some_and(|at| at.elapsed() < VERSION_CHECK_INTERVAL);
+        state.tables.clone().filter(|_| fresh)
+    }
+}
+
+async fn refresh(pool: &PgPool, bungie: &BungieClient, current: Option<Arc<ManifestTables>>) -> BotResult<Arc<ManifestTables>> {
+    let manifest = bungie.get("/Destiny2/Manifest/").await?;
+    let version = manifest["version"]
+        .as_str()
+        .ok_or_else(|| BotError::Custom("Manifest is missing version".to_string()))?;
+
+    if let Some(current) = current.filter(|t| t.version == version) {
+        return Ok(current);
+    }
+
+    let records = load_component(pool, bungie, &manifest, version, RECORD_DEFINITIONS, index_records).await?;
+    let objectives = load_component(pool, bungie, &manifest, version, OBJECTIVE_DEFINITIONS, index_objectives).await?;
+    let activities = load_component(pool, bungie, &manifest, version, ACTIVITY_DEFINITIONS, index_activities).await?;
+    info!("Destiny manifest {} loaded", version);
+
+    Ok(Arc::new(ManifestTables { version: version.to_string(), records, objectives, activities }))
+}
+
+/// Tabulka z DB pro danou verzi, jinak stáhne definice, zmenší je a uloží
+async fn load_component(
+    pool: &PgPool,
+    bungie: &BungieClient,
+    manifest: &Value,
+    version: &str,
+    component: &str,
+    index: fn(HashMap<String, Value>) -> HashMap<String, String>,
+) -> BotResult<HashMap<String, String>> {
+    if let Some(content) = load_manifest_component(pool, component, version).await? {
+        return Ok(serde_json::from_str(&content)?);
+    }
+
+    let path = manifest["jsonWorldComponentContentPaths"]["en"][component]
+        .as_str()
+        .ok_or_else(|| BotError::Custom(format!("Manifest is missing {component}")))?;
+    info!("Downloading {} for manifest {}", component, version);
+    let definitions: HashMap<String, Value> = serde_json::from_str(&bungie.get_content(path).await?)?;
+
+    let table = index(definitions);
+    save_manifest_component(pool, component, version, &serde_json::to_string(&table)?).await?;
+    Ok(table)
+}
+
+/// Poslední uložený manifest, když Bungie při startu neodpovídá
+async fn load_stored(pool: &PgPool) -> BotResult<Option<Arc<ManifestTables>>> {
+    let (Some((version, records)), Some((_, objectives)), Some((_, activities))) = (
+        load_latest_manifest_component(pool, RECORD_DEFINITIONS).await?,
+        load_latest_manifest_component(pool, OBJECTIVE_DEFINITIONS).await?,
+        load_latest_manifest_component(pool, ACTIVITY_DEFINITIONS).await?,
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(Arc::new(ManifestTables {
+        version,
+        records: serde_json::from_str(&records)?,
+        objectives: serde_json::from_str(&objectives)?,
+        activities: serde_json::from_str(&activities)?,
+    })))
+}
+
+fn index_records(definitions: HashMap<String, Value>) -> HashMap<String, String> {
+    let mut records = HashMap::new();
+    for (hash, record) in definitions {
+        if let Some(name) = record["displayProperties"]["name"].as_str().filter(|n| !n.is_empty()) {
+            records.entry(name.to_lowercase()).or_insert(hash);
+        }
+    }
+    records
+}
+
+fn index_objectives(definitions: HashMap<String, Value>) -> HashMap<String, String> {
+    definitions
+        .into_iter()
+        .filter_map(|(hash, objective)| {
+            objective["progressDescription"].as_str().map(|desc| (hash, desc.to_string()))
+        })
+        .collect()
+}
+
+fn index_activities(definitions: HashMap<String, Value>) -> HashMap<String, String> {
+    definitions
+        .into_iter()
+        .filter_map(|(hash, activity)| {
+            activity["displayProperties"]["name"]
+                .as_str()
+                .filter(|name| !name.is_empty())
+                .map(|name| (hash, name.to_string()))
+        })
+        .collect()
+}
@@ -1,4 +1,5 @@
 pub mod bungie;
+pub mod manifest;
 pub mod api;
 pub mod twitch_api;
 pub mod kick_api;
@@ -13,24 +13,44 @@ struct ChatterResponse {
     data: Vec<Chatter>,
 }
 
-#[derive(Deserialize, Debug)]
-struct Chatter {
-    user_name: String,
+#[derive(Deserialize, Debug, Clone)]
+pub struct Chatter {
+    pub user_id: String,
+    pub user_name: String,
 }
 
-pub async fn fetch_lurkers(broadcaster_id: &str, token: &str, client_id: &str) -> Vec<String> {
-    let url = format!(
-        "https://api.twitch.tv/helix/chat/chatters?broadcaster_id={}&moderator_id=1091219021",
-        broadcaster_id
-    );
+/// Diváci v chatu (i lurkeři). Token musí patřit moderátorovi kanálu se scope `moderator:read:chatters`.
+pub async fn fetch_lurkers(broadcaster_id: &str, token: &str, client_id: &str) -> BotResult<Vec<Chatter>> {
+    let mut chatters = Vec::new();
+    let mut cursor: Option<String> = None;
 
-    let res = reqwest::Client::new()
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Client-Id", client_id)
-        .send()
-        .await.unwrap().json::<ChatterResponse>().await;
-    res.unwrap().data.into_iter().map(|c| c.user_name).collect()
+    loop {
+        let mut url = format!(
+            "https://api.twitch.tv/helix/chat/chatters?broadcaster_id={}&moderator_id=1091219021&first=1000",
+            broadcaster_id
+        );
+        if let Some(after) = &cursor {
+            url.push_str(&format!("&after={after}"));
+        }
+
+        let res: Value = reqwest::Client::new()
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Client-Id", client_id)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        cursor = res["pagination"]["cursor"].as_str().map(String::from);
+        let page: ChatterResponse = serde_json::from_value(res)?;
+        chatters.extend(page.data);
+
+        if cursor.is_none() {
+            return Ok(chatters);
+        }
+    }
 }
 
 pub async fn is_channel_live(channel_id: &str, token: &str, client_id: &str) -> Result<bool, reqwest::Error> {
@@ -135,3 +155,109 @@ pub async fn create_twitch_app_token(secrets: &BotSecrets) -> BotResult<TwitchAp
     })
 }
 
+
+/// Uživatelský token streamera po obnovení přes refresh token
+#[derive(Deserialize, Debug)]
+pub struct TwitchUserTokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+pub async fn refresh_twitch_user_token(secrets: &BotSecrets, refresh_token: &str) -> BotResult<TwitchUserTokenResponse> {
+    let body = format!(
+        "client_id={}&client_secret={}&grant_type=refresh_token&refresh_token={}",
+        secrets.bot_id,
+        secrets.client_secret,
+        urlencoding::encode(refresh_token),
+    );
+
+    let res = reqwest::Client::new()
+        .post("https://id.twitch.tv/oauth2/token")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body).send().await?;
+
+    if !res.status().is_success() {
+        return Err(BotError::Custom(format!("Failed to refresh Twitch user token: {}", res.status())));
+    }
+    Ok(res.json().await?)
+}
+
+/// Přihlásí EventSub websocket session k odběru uplatněných odměn za kanálové body
+pub async fn subscribe_reward_redemptions(secrets: &BotSecrets, token: &str, broadcaster_id: &str, session_id: &str) -> BotResult<()> {
+    let body = serde_json::json!({
+        "type": "channel.channel_points_custom_reward_redemption.add",
+        "version": "1",
+        "condition": { "broadcaster_user_id": broadcaster_id },
+        "transport": { "method": "websocket", "session_id": session_id },
+    });
+
+    let res = reqwest::Client::new()
+        .post("https://api.twitch.tv/helix/eventsub/subscriptions")
+        .header("Client-Id", &secrets.bot_id)
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let text = res.text().await.unwrap_or_default();
+        return Err(BotError::Custom(format!("EventSub subscription failed ({status}): {text}")));
+    }
+    Ok(())
+}
+
+/// Vytvoří odměnu za kanálové body. Jen odměny vytvořené naším client id jde potvrdit nebo vrátit.
+pub async fn create_custom_reward(secrets: &BotSecrets, token: &str, broadcaster_id: &str, title: &str, cost: i64, prompt: &str) -> BotResult<String> {
+    let body = serde_json::json!({
+        "title": title,
+        "cost": cost,
+        "prompt": prompt,
+        "is_user_input_required": false,
+    });
+
+    let res = reqwest::Client::new()
+        .post(format!("https://api.twitch.tv/helix/channel_points/custom_rewards?broadcaster_id={}", broadcaster_id))
+        .header("Client-Id", &secrets.bot_id)
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let status = res.status();
+        let text = res.text().await.unwrap_or_default();
+        return Err(BotError::Custom(format!("Creating reward failed ({status}): {text}")));
+    }
+
+    let parsed: Value = res.json().await?;
+    parsed["data"][0]["id"]
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| BotError::Custom("Invalid Twitch reward response".into()))
+}
+
+/// Potvrdí (`fulfilled`) nebo zruší a vrátí body za uplatnění odměny
+pub async fn update_redemption_status(secrets: &BotSecrets, token: &str, broadcaster_id: &str, reward_id: &str, redemption_id: &str, fulfilled: bool) -> BotResult<()> {
+    let url = format!(
+        "https://api.twitch.tv/helix/channel_points/custom_rewards/redemptions?broadcaster_id={}&reward_id={}&id={}",
+        broadcaster_id, reward_id, redemption_id
+    );
+    let status = if fulfilled { "FULFILLED" } else { "CANCELED" };
+
+    let res = reqwest::Client::new()
+        .patch(url)
+        .header("Client-Id", &secrets.bot_id)
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "status": status }))
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        let code = res.status();
+        let text = res.text().await.unwrap_or_default();
+        return Err(BotError::Custom(format!("Updating redemption failed ({code}): {text}")));
+    }
+    Ok(())
+}
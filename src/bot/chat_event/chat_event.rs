@@ -16,12 +16,30 @@ pub struct ChatEvent {
     //Twitch Data
     pub follower: Option<bool>,
     pub broadcaster_id: Option<String>,
+    //Sub / resub / gift, takové eventy se nezpracovávají jako příkazy
+    pub support: Option<SupportEvent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SupportKind { Sub, Gift }
+
+/// Sub nebo darované suby, `user` v eventu je předplatitel nebo dárce
+#[derive(Debug, Clone)]
+pub struct SupportEvent {
+    pub kind: SupportKind,
+    /// 1-3, Prime a Kick se počítají jako tier 1
+    pub tier: u8,
+    /// Počet darovaných subů, u subu 1
+    pub count: u32,
 }
 #[derive(Debug, Clone)]
 pub struct ChatUser {
     pub identity: UserIdentity,
     pub name: DisplayName,
-    pub permission: PermissionLevel
+    pub permission: PermissionLevel,
+    /// Sub badge zvlášť, sub s modem má `permission` Moderator
+    pub subscriber: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
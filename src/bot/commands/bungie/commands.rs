@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use sqlx::PgPool;
+
+use crate::{api::{api::{get_master_challenges, get_users_clears}, bungie::{BungieError, MemberShip}}, bot::{chat_event::chat_event::ChatEvent, commands::{CommandGroup, CommandRegistration, commands::{BotResult, CommandT, FnCommand}, queue::{commands::{mod_register_command, register_command}, logic::is_valid_bungie_name}, viewer::{ViewerScope, resolve_viewer}}, db::{ChannelId, bungie::load_membership}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::{AppState, BotError}}, cmd};
+
+pub static BUNGIE_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
+    Arc::new(CommandGroup {
+        name: "bungie".into(),
+        commands: vec![
+            cmd!(total_command(), "total"),
+            cmd!(master_challenges_command(), "cr"),
+            cmd!(register_command(), "register"),
+            cmd!(mod_register_command(), "mod_register"),
+        ]
+    })
+});
+
+/// Aktivity pro `!cr`, klíče z `get_master_challenges`
+const CHALLENGE_ACTIVITIES: &[&str] = &["vog", "vow", "ron", "se", "kf", "ce"];
+
+/// Výpadek Bungie a soukromý profil jdou do chatu, zbytek jen do logu
+fn bungie_failure(name: &str, err: BungieError) -> BotError {
+    match err {
+        BungieError::NotFound => BotError::Chat(Replies::bungie_name_not_found(name)),
+        BungieError::Private => BotError::Chat(Replies::bungie_private(name)),
+        err if err.is_unavailable() => {
+            tracing::warn!("Bungie request for {} failed: {}", name, err);
+            BotError::Chat(Replies::bungie_unavailable())
+        }
+        err => err.into(),
+    }
+}
+
+/// Čí Bungie účet: nic = odesílatel, `Name#1234` = přímo Bungie jméno, jinak @zmínka / login registrovaného diváka.
+/// Vrací (jméno do odpovědi, membership).
+async fn resolve_membership(pool: &PgPool, state: &AppState, event: &ChatEvent, target: &str) -> BotResult<(String, MemberShip)> {
+    let target = target.trim();
+    if target.is_empty() {
+        let Some(user) = &event.user else {
+            return Err(BotError::Chat(Replies::bungie_not_registered("You")));
+        };
+        return load_membership(pool, user.identity.platform, &user.identity.platform_user_id)
+            .await
+            .map(|m| (user.name.display.clone(), m))
+            .ok_or_else(|| BotError::Chat(Replies::bungie_not_registered(&user.name.display)));
+    }
+
+    if let Some(bungie_name) = is_valid_bungie_name(target) {
+        let membership = state.bungie.find_membership(&bungie_name).await.map_err(|e| bungie_failure(&bungie_name, e))?;
+        return Ok((bungie_name, membership));
+    }
+
+    let viewer = resolve_viewer(pool, state, event.platform, ViewerScope::Anywhere, target).await?;
+    load_membership(pool, viewer.user_id.platform(), viewer.user_id.platform_user_id())
+        .await
+        .map(|m| (viewer.display_name.clone(), m))
+        .ok_or_else(|| BotError::Chat(Replies::bungie_not_registered(&viewer.display_name)))
+}
+
+pub fn total_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let target = event.message.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+                let (name, membership) = resolve_membership(&pool, &state, &event, target).await?;
+
+                let clears = get_users_clears(&state.bungie, &membership).await.map_err(|e| bungie_failure(&name, e))?;
+                client.send_message(&caller, &Replies::bungie_total(&name, clears as i64)).await?;
+                Ok(())
+            })
+        },
+        "Show all raid clears of a Bungie name or registered viewer",
+        "!total [BungieName#1234 | @user]",
+        "total",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn master_challenges_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let mut args = event.message.splitn(3, ' ').skip(1);
+                let Some(activity) = args.next().map(str::to_lowercase).filter(|a| CHALLENGE_ACTIVITIES.contains(&a.as_str())) else {
+                    return Err(BotError::Chat(format!("Usage: !cr <{}> [BungieName#1234 | @user]", CHALLENGE_ACTIVITIES.join("|"))));
+                };
+                let (name, membership) = resolve_membership(&pool, &state, &event, args.next().unwrap_or("")).await?;
+
+                let challenges = match get_master_challenges(&pool, &state.bungie, &membership, activity.clone()).await {
+                    Ok(challenges) => challenges,
+                    Err(BotError::Bungie(err)) => return Err(bungie_failure(&name, err)),
+                    Err(err) => return Err(err),
+                };
+                client.send_message(&caller, &Replies::bungie_challenges(&name, &activity, &challenges)).await?;
+                Ok(())
+            })
+        },
+        "Show master raid challenge completions",
+        "!cr <activity> [BungieName#1234 | @user]",
+        "cr",
+        PermissionLevel::Everyone,
+    ))
+}
@@ -3,7 +3,7 @@ use futures::future::BoxFuture;
 use once_cell::sync::Lazy;
 use sqlx::PgPool;
 
-use crate::bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::{CommandGroup, CommandRegistration, CommandRegistry, moderation::commands::MODERATION_COMMANDS, queue::commands::QUEUE_COMMANDS}, db::ChannelId, handler::handler::UnifiedChatClient, permissions::permissions::PermissionLevel, state::def::{AppState, BotError}};
+use crate::bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::{CommandGroup, CommandRegistration, CommandRegistry, bungie::commands::BUNGIE_COMMANDS, moderation::commands::MODERATION_COMMANDS, points::commands::POINTS_COMMANDS, queue::commands::QUEUE_COMMANDS}, db::ChannelId, handler::handler::UnifiedChatClient, permissions::permissions::PermissionLevel, state::def::{AppState, BotError}};
 
 //pub type CommandHandler = Arc<dyn Fn(PrivmsgMessage, Arc<Mutex<TwitchClient>>, PgPool, Arc<AppState>) -> BoxFuture<'static, BotResult<()>> + Send + Sync>;
 
@@ -12,9 +12,9 @@ pub type BotResult<T> = Result<T, BotError>;
 pub static COMMAND_GROUPS: Lazy<HashMap<&'static str, Arc<CommandGroup>>> = Lazy::new(|| {
     let mut map = HashMap::new();
     map.insert("queue", QUEUE_COMMANDS.clone());
-    //map.insert("points", &*POINTS_COMMANDS);
+    map.insert("points", POINTS_COMMANDS.clone());
     map.insert("moderation", MODERATION_COMMANDS.clone());
-    //map.insert("bungie", &*BUNGIE_COMMANDS);
+    map.insert("bungie", BUNGIE_COMMANDS.clone());
     map
 });
 
@@ -5,6 +5,9 @@ use crate::bot::commands::commands::CommandT;
 pub mod commands;
 pub mod queue;
 pub mod moderation;
+pub mod points;
+pub mod bungie;
+pub mod viewer;
 
 #[derive(Clone)]
 pub struct CommandRegistration {
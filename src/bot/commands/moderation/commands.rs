@@ -2,7 +2,7 @@ use std::{str::FromStr, sync::Arc};
 
 use once_cell::sync::Lazy;
 
-use crate::{bot::{commands::{CommandGroup, CommandRegistration, commands::{CommandT, FnCommand}, moderation::connect_channel, queue::logic::QueueKey}, db::{ChannelId, config::save_channel_config}, dispatcher::dispatcher::refresh_channel_dispatcher, handler::handler::ChatClient, permissions::permissions::PermissionLevel, runtime::channel_lifecycle::reload_channel, state::def::BotError}, cmd};
+use crate::{bot::{commands::{CommandGroup, CommandRegistration, commands::{CommandT, FnCommand}, moderation::connect_channel, queue::logic::{QueueKey, resolve_queue_owner}, viewer::{Viewer, ViewerScope, resolve_viewer}}, db::{ChannelId, config::save_channel_config, queue::{ban_from_queue, unban_from_queue}}, dispatcher::dispatcher::refresh_channel_dispatcher, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, runtime::channel_lifecycle::reload_channel, state::def::BotError, web::sse::SseEvent}, cmd};
 pub static MODERATION_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
     Arc::new(CommandGroup {
         name: "moderation".into(),
@@ -10,7 +10,10 @@ pub static MODERATION_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
             cmd!(alias_command(), "alias"),
             cmd!(add_package_command(), "add_package"),
             cmd!(connect_command(), "connect"),
-            cmd!(config_command(), "config", "mod_config")
+            cmd!(config_command(), "config", "mod_config"),
+            cmd!(mod_ban_command(), "mod_ban"),
+            cmd!(mod_timeout_command(), "mod_timeout"),
+            cmd!(mod_unban_command(), "mod_unban")
         ]
     })
 });
@@ -257,16 +260,26 @@ pub fn config_command() -> Arc<dyn CommandT> {
 
                 lines.push(format!(
                     "Queue: {}",
-                    if cfg.open { "OPEN ✅" } else { "CLOSED 🚫" }
+                    if cfg.main.open { "OPEN ✅" } else { "CLOSED 🚫" }
                 ));
 
                 lines.push(format!(
                     "Mode: {}",
-                    if cfg.random_queue { "RAFFLE 🎲" } else { "QUEUE 📥" }
+                    if cfg.main.random_queue { "RAFFLE 🎲" } else { "QUEUE 📥" }
                 ));
 
-                lines.push(format!("Team size: {}", cfg.teamsize));
-                lines.push(format!("Max queue size: {}", cfg.size));
+                lines.push(format!("Team size: {}", cfg.main.teamsize));
+                lines.push(format!("Max queue size: {}", cfg.main.size));
+                for (name, q) in &cfg.queues {
+                    lines.push(format!(
+                        "Queue {}: {} | {} | team {} | max {}",
+                        name,
+                        if q.open { "OPEN ✅" } else { "CLOSED 🚫" },
+                        if q.random_queue { "RAFFLE 🎲" } else { "QUEUE 📥" },
+                        q.teamsize,
+                        q.size
+                    ));
+                }
                 lines.push(format!("Prefix: {}", cfg.prefix));
                 lines.push(format!("Runs today: {}", cfg.runs));
 
@@ -293,3 +306,128 @@ pub fn config_command() -> Arc<dyn CommandT> {
         PermissionLevel::Moderator,
     ))
 }
+
+/// Nejdelší timeout, delší zákaz je `!mod_ban`
+const MAX_BAN_DURATION: i64 = 365 * 86400;
+
+/// "600", "30m", "2h", "1d" -> sekundy, nejvýš `MAX_BAN_DURATION`
+fn parse_ban_duration(input: &str) -> Option<i64> {
+    let input = input.to_lowercase();
+    let (number, unit) = match input.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&input[..i], c),
+        _ => (input.as_str(), 's'),
+    };
+    let value: i64 = number.parse().ok().filter(|v| *v > 0)?;
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        _ => return None,
+    };
+    Some(value.checked_mul(multiplier)?.min(MAX_BAN_DURATION))
+}
+
+pub fn mod_ban_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let args: Vec<&str> = event.message.split_whitespace().collect();
+                if args.len() < 2 {
+                    return Err(BotError::Chat("Usage: !mod_ban <user> [reason]".to_string()));
+                }
+
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let Viewer { user_id, display_name, .. } = resolve_viewer(&pool, &state, event.platform, ViewerScope::Channel(&owner), args[1]).await?;
+                let reason = (args.len() > 2).then(|| args[2..].join(" "));
+                let banned_by = event.user.as_ref().map(|u| u.name.display.clone()).unwrap_or_default();
+
+                ban_from_queue(&pool, &owner, &user_id, &display_name, None, reason.as_deref(), &banned_by).await?;
+                let _ = state.sse_bus.send(SseEvent::BansUpdated { channel: owner });
+
+                client.send_message(&caller, &Replies::mod_banned(&display_name, reason.as_deref())).await?;
+                Ok(())
+            })
+        },
+        "Ban a person from the queue.",
+        "!mod_ban <user> [reason]",
+        "mod_ban",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn mod_timeout_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let args: Vec<&str> = event.message.split_whitespace().collect();
+                let duration = args.get(2).and_then(|d| parse_ban_duration(d));
+                let Some(duration) = duration else {
+                    return Err(BotError::Chat("Usage: !mod_timeout <user> <600 | 30m | 2h | 1d> [reason]".to_string()));
+                };
+
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let Viewer { user_id, display_name, .. } = resolve_viewer(&pool, &state, event.platform, ViewerScope::Channel(&owner), args[1]).await?;
+                let reason = (args.len() > 3).then(|| args[3..].join(" "));
+                let banned_by = event.user.as_ref().map(|u| u.name.display.clone()).unwrap_or_default();
+
+                ban_from_queue(&pool, &owner, &user_id, &display_name, Some(duration), reason.as_deref(), &banned_by).await?;
+                let _ = state.sse_bus.send(SseEvent::BansUpdated { channel: owner });
+
+                client.send_message(&caller, &Replies::mod_timed_out(&display_name, duration, reason.as_deref())).await?;
+                Ok(())
+            })
+        },
+        "Timeout someone from entering the queue.",
+        "!mod_timeout <user> <duration> [reason]",
+        "mod_timeout",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn mod_unban_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let args: Vec<&str> = event.message.split_whitespace().collect();
+                if args.len() < 2 {
+                    return Err(BotError::Chat("Usage: !mod_unban <user>".to_string()));
+                }
+
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let Viewer { user_id, display_name, .. } = resolve_viewer(&pool, &state, event.platform, ViewerScope::Channel(&owner), args[1]).await?;
+
+                let reply = if unban_from_queue(&pool, &owner, user_id.as_str()).await? {
+                    let _ = state.sse_bus.send(SseEvent::BansUpdated { channel: owner });
+                    Replies::mod_unbanned(&display_name)
+                } else {
+                    Replies::mod_not_banned(&display_name)
+                };
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Unban a person from the queue.",
+        "!mod_unban <user>",
+        "mod_unban",
+        PermissionLevel::Moderator,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ban_duration_is_capped() {
+        assert_eq!(parse_ban_duration("30m"), Some(1800));
+        assert_eq!(parse_ban_duration("600"), Some(600));
+        assert_eq!(parse_ban_duration("9999d"), Some(MAX_BAN_DURATION));
+        assert_eq!(parse_ban_duration("9223372036854775807d"), None);
+        assert_eq!(parse_ban_duration("0h"), None);
+    }
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+
+use crate::{bot::{commands::{CommandGroup, CommandRegistration, commands::{CommandT, FnCommand}, queue::logic::{prioritize, queue_settings, resolve_queue}, viewer::{ViewerScope, resolve_viewer}}, db::{ChannelId, UserId, config::save_channel_config, queue::queue_position, points::{add_points, fetch_leaderboard, fetch_points, spend_points}}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::BotError, web::sse::SseEvent}, cmd};
+
+pub static POINTS_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
+    Arc::new(CommandGroup {
+        name: "points".into(),
+        commands: vec![
+            cmd!(points_command(), "points"),
+            cmd!(give_points_command(), "givepoints"),
+            cmd!(leaderboard_command(), "leaderboard", "top"),
+            cmd!(buy_prio_command(), "buyprio"),
+            cmd!(points_config_command(), "points_config"),
+        ]
+    })
+});
+
+pub fn points_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let Some(user) = &event.user else { return Ok(()); };
+                let caller = ChannelId::new(event.platform, &event.channel);
+
+                let (user_id, name) = match event.message.split_whitespace().nth(1) {
+                    Some(target) => {
+                        let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Anywhere, target).await?;
+                        (viewer.user_id, viewer.display_name)
+                    }
+                    None => (UserId::new(user.identity.platform, &user.identity.platform_user_id), user.name.display.clone()),
+                };
+
+                let reply = match fetch_points(&pool, &caller, &user_id).await? {
+                    Some((points, rank)) => Replies::points_balance(&name, points, rank),
+                    None => Replies::points_none(&name),
+                };
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show your (or someone's) points",
+        "!points [user]",
+        "points",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn give_points_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let args: Vec<&str> = event.message.split_whitespace().collect();
+                let (Some(target), Some(amount)) = (args.get(1), args.get(2).and_then(|a| a.parse::<i64>().ok())) else {
+                    return Err(BotError::Chat("Usage: !givepoints <user> <amount>".to_string()));
+                };
+
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Anywhere, target).await?;
+                let balance = add_points(&pool, &caller, &viewer.user_id, &viewer.display_name, amount).await?;
+
+                client.send_message(&caller, &Replies::points_given(&viewer.display_name, amount, balance)).await?;
+                Ok(())
+            })
+        },
+        "Give (or take with a negative amount) points",
+        "!givepoints <user> <amount>",
+        "givepoints",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn leaderboard_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, _state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let top = fetch_leaderboard(&pool, &caller, 5).await?;
+                client.send_message(&caller, &Replies::points_leaderboard(&top)).await?;
+                Ok(())
+            })
+        },
+        "Show who has the most points",
+        "!leaderboard",
+        "leaderboard",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn buy_prio_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let Some(user) = &event.user else { return Ok(()); };
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
+
+                let cost = {
+                    let cfg = state.config.read().await;
+                    cfg.get_channel_config(&caller).map(|c| c.points.prio_cost).unwrap_or_default()
+                };
+                if cost <= 0 {
+                    client.send_message(&caller, &Replies::points_prio_disabled()).await?;
+                    return Ok(());
+                }
+
+                // Hráč v aktuální skupině by se prio posunul až za ni, body mu nebereme
+                let user_id = UserId::new(user.identity.platform, &user.identity.platform_user_id);
+                let teamsize = queue_settings(&state, &owner, &queue).await?.teamsize as i32;
+                if queue_position(&pool, &user_id, &owner, &queue).await?.is_some_and(|p| p <= teamsize) {
+                    client.send_message(&caller, &Replies::reward_prio_in_group(&user.name.display)).await?;
+                    return Ok(());
+                }
+
+                let Some(left) = spend_points(&pool, &caller, &user_id, cost).await? else {
+                    client.send_message(&caller, &Replies::points_not_enough(&user.name.display, cost)).await?;
+                    return Ok(());
+                };
+
+                // Stejná cesta jako !prio <user> 1, kdo ve frontě není, dostane body zpět
+                let prioritized = prioritize(&pool, &owner, &queue, &user_id, Some(1), teamsize).await;
+                if !matches!(prioritized, Ok(true)) {
+                    add_points(&pool, &caller, &user_id, &user.name.display, cost).await?;
+                    prioritized?;
+                    client.send_message(&caller, &Replies::points_prio_not_queued(&user.name.display)).await?;
+                    return Ok(());
+                }
+
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+                client.send_message(&caller, &Replies::points_prio_bought(&user.name.display, cost, left)).await?;
+                Ok(())
+            })
+        },
+        "Spend points on a priority run",
+        "!buyprio [queue]",
+        "buyprio",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn points_config_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let args: Vec<&str> = event.message.split_whitespace().skip(1).collect();
+
+                let mut cfg = state.config.write().await;
+                let policy = &mut cfg.get_channel_config_mut(caller.clone()).points;
+
+                for pair in args.chunks(2) {
+                    let [key, value] = pair else {
+                        return Err(BotError::Chat(
+                            "Usage: !points_config [presence <n>] [chat <n>] [interval <minutes>] [prio <cost>]".to_string(),
+                        ));
+                    };
+                    let invalid = || BotError::Chat(format!("Invalid value for {}: {}", key, value));
+                    match key.to_lowercase().as_str() {
+                        "presence" => policy.presence = value.parse().map_err(|_| invalid())?,
+                        "chat" => policy.chat = value.parse().map_err(|_| invalid())?,
+                        "interval" => policy.interval_minutes = value.parse().ok().filter(|m| *m > 0).ok_or_else(invalid)?,
+                        "prio" => policy.prio_cost = value.parse().map_err(|_| invalid())?,
+                        other => return Err(BotError::Chat(format!("Unknown points setting: {}", other))),
+                    }
+                }
+
+                let reply = Replies::points_policy(&policy.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &caller, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change how points are earned and spent",
+        "!points_config [presence <n>] [chat <n>] [interval <minutes>] [prio <cost>]",
+        "points_config",
+        PermissionLevel::Moderator,
+    ))
+}
@@ -11,17 +11,41 @@ use crate::{
         commands::{
             commands::{parse_channel_id, BotResult, CommandT, FnCommand},
             queue::logic::{
-                next_handler, process_queue_entry, randomize_queue, resolve_queue_owner,
-                toggle_queue, QueueEntry, QueueKey,
+                prioritize, process_queue_entry, public_queue_url, queue_settings, resolve_named_queue, resolve_queue,
+                resolve_queue_owner, run_next, set_queue_len, set_queue_size, toggle_queue, undo_last_change,
+                QueueEntry, QueueKey,
             },
+            queue::export::create_export_link,
+            queue::party::{party_accept_command, party_decline_command},
+            queue::raffle::{parse_bonus, raffle_odds},
+            viewer::{resolve_viewer, ViewerScope},
+            queue::tags::{canonical_tag, format_tags, known_tags, split_tags},
+            queue::ready::{mark_ready, ReadyMiss},
+            queue::rewards::reward_command,
+            queue::subs::sub_prio_command,
+            queue::requirements::requirements_command,
+            queue::presence::{group_presence, presence_command},
             CommandGroup, CommandRegistration,
         },
-        db::{bungie::register_bungie_name, config::save_channel_config, ChannelId, UserId},
+        db::{
+            bungie::register_bungie_name,
+            config::save_channel_config,
+            cooldown::clear_cooldown,
+            queue::{begin_queue_tx, renumber_queue},
+            history::{
+                fetch_recent_runs, fetch_run_pace, fetch_viewer_runs, HISTORY_WINDOW_HOURS,
+            },
+            stats::{fetch_channel_stats, STATS_DEFAULT_DAYS},
+            raffle::{fetch_raffle_entrants_pool, set_raffle_bonus},
+            snapshots::{attach_queue_settings, seal_snapshot, snapshot_queue},
+            ChannelId, UserId,
+        },
         handler::handler::{ChatClient, UnifiedChatClient},
         permissions::permissions::PermissionLevel,
         replies::Replies,
+        runtime::auto_queue::{AutoQueueMode, ScheduleSlot},
         state::{
-            def::{AppState, BotError},
+            def::{AppState, BotError, QueueSettings, MAIN_QUEUE},
             state::get_twitch_access_token,
         },
         web::sse::SseEvent,
@@ -40,6 +64,14 @@ pub static QUEUE_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
             cmd!(Arc::new(QueueSize), "queue_size", "size"),
             cmd!(list(), "list"),
             cmd!(random(), "random"),
+            cmd!(raffle_weights_command(), "raffle_weights", "weights"),
+            cmd!(raffle_bonus_command(), "raffle_bonus"),
+            cmd!(balance_command(), "balance"),
+            cmd!(ready_command(), "ready", "rdy"),
+            cmd!(party_accept_command(), "accept"),
+            cmd!(party_decline_command(), "decline"),
+            cmd!(ready_check_command(), "ready_check"),
+            cmd!(presence_command(), "online", "ingame"),
             cmd!(toggle_queue_command(true), "open", "open_queue"),
             cmd!(toggle_queue_command(false), "close", "close_queue"),
             cmd!(queue_share(), "queue_share", "share"),
@@ -48,6 +80,19 @@ pub static QUEUE_COMMANDS: Lazy<Arc<CommandGroup>> = Lazy::new(|| {
             cmd!(remove_command(), "remove"),
             cmd!(prio_command(), "prio", "bribe"),
             cmd!(pos(), "pos", "position"),
+            cmd!(history_command(), "history"),
+            cmd!(stats_command(), "stats"),
+            cmd!(undo_command(), "undo"),
+            cmd!(cooldown_command(), "cooldown"),
+            cmd!(cooldown_clear_command(), "cooldown_clear", "cdclear"),
+            cmd!(requirements_command(), "requirements", "reqs"),
+            cmd!(auto_queue_command(), "autoqueue", "auto_queue"),
+            cmd!(queues_command(), "queues"),
+            cmd!(queue_create_command(), "queue_create"),
+            cmd!(queue_delete_command(), "queue_delete"),
+            cmd!(queue_export_command(), "queue_export", "export"),
+            cmd!(reward_command(), "reward", "rewards"),
+            cmd!(sub_prio_command(), "subprio", "sub_prio"),
         ],
     })
 });
@@ -62,7 +107,7 @@ impl CommandT for JoinCommand {
         "Join the queue"
     }
     fn usage(&self) -> &str {
-        "!join"
+        "!join [queue] [BungieName#1234] [titan|hunter|warlock|sherpa|first]"
     }
     fn permission(&self) -> PermissionLevel {
         PermissionLevel::Everyone
@@ -114,15 +159,18 @@ impl CommandT for ForceAddCommand {
         client: Arc<UnifiedChatClient>,
     ) -> BoxFuture<'static, BotResult<()>> {
         Box::pin(async move {
-            let words: Vec<&str> = event.message.split_whitespace().collect();
-            if words.len() < 3 {
+            let (channel_id, queue, args) =
+                resolve_named_queue(&state, &ChannelId::new(event.platform, &event.channel), &event.message)
+                    .await?;
+            let words: Vec<&str> = args.split_whitespace().collect();
+            if words.len() < 2 {
                 return Err(BotError::Chat(
-                    "Usage: !add @name BungieName#1234".to_string(),
+                    "Usage: !add [queue] @name BungieName#1234".to_string(),
                 ));
             }
 
-            let name = words[1].strip_prefix("@").unwrap_or(words[1]).to_string();
-            let bungie_name = words[2..].join(" ");
+            let name = words[0].strip_prefix("@").unwrap_or(words[0]).to_string();
+            let (bungie_name, tags) = split_tags(&words[1..].join(" "));
             let entry = if event.platform == Platform::Twitch {
                 let token = get_twitch_access_token(&state).await?;
                 let (platform_id, display_name) =
@@ -133,31 +181,20 @@ impl CommandT for ForceAddCommand {
                     user_id,
                     bungie_name: bungie_name.clone(),
                     display_name: display_name.clone(),
+                    tags,
                 }
             } else {
                 return Err(BotError::Custom("Missing Platform".to_string()));
             };
 
-            let channel_id =
-                resolve_queue_owner(&state, &ChannelId::new(event.platform, event.channel)).await?;
-
-            let cfg = {
-                let s = state.config.read().await;
-                match s.get_channel_config(&channel_id) {
-                    Some(c) => c.clone(),
-                    None => {
-                        return Err(BotError::Chat(
-                            "‼️Channel Configuration missing ‼️".to_string(),
-                        ));
-                    }
-                }
-            };
+            let cfg = queue_settings(&state, &channel_id, &queue).await?;
 
             let reply = process_queue_entry(
                 &pool,
                 cfg.size,
                 entry,
                 &channel_id,
+                &queue,
                 crate::bot::commands::queue::logic::Queue::ForceJoin,
                 cfg.random_queue,
             )
@@ -197,30 +234,10 @@ impl CommandT for NextCommand {
     ) -> BoxFuture<'static, BotResult<()>> {
         Box::pin(async move {
             let caller = ChannelId::new(event.platform, &event.channel);
-            let owner = resolve_queue_owner(&state, &caller).await?;
-            let (teamsize, random_queue) = {
-                let cfg = state.config.read().await;
-                let c = cfg
-                    .get_channel_config(&owner)
-                    .ok_or(BotError::ConfigMissing(owner.clone()))?;
-                (c.teamsize as i64, c.random_queue)
-            };
-
-            let result = if random_queue {
-                randomize_queue(&owner, &pool, teamsize).await?
-            } else {
-                next_handler(&owner, &pool, teamsize).await?
-            };
-
-            {
-                let mut cfg = state.config.write().await;
-                cfg.get_channel_config_mut(owner).runs += 1;
-            }
+            let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
+            let result = run_next(&pool, state.clone(), &owner, &queue).await?;
 
             client.send_message(&caller, &result).await?;
-            let _ = &state.sse_bus.send(SseEvent::QueueUpdated {
-                channel: ChannelId::new(event.platform, &event.channel),
-            })?;
             Ok(())
         })
     }
@@ -250,20 +267,15 @@ impl CommandT for QueueSize {
     ) -> BoxFuture<'static, BotResult<()>> {
         Box::pin(async move {
             let caller = ChannelId::new(event.platform, &event.channel);
-            let owner = resolve_queue_owner(&state, &caller).await?;
+            let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
 
-            let new_size: usize = event
-                .message
+            let new_size: usize = args
                 .split_whitespace()
-                .nth(1)
-                .ok_or_else(|| BotError::Chat("Usage: !size <n>".to_string()))?
+                .next()
+                .ok_or_else(|| BotError::Chat("Usage: !size [queue] <n>".to_string()))?
                 .parse()
                 .map_err(|_| BotError::Chat("Invalid Number".to_string()))?;
-            {
-                let mut cfg = state.config.write().await;
-                cfg.get_channel_config_mut(owner.clone()).teamsize = new_size;
-                save_channel_config(&pool, &owner, &cfg).await?;
-            }
+            set_queue_size(&pool, state.clone(), &owner, &queue, new_size).await?;
 
             client
                 .send_message(&caller, &Replies::queue_size(&new_size.to_string()))
@@ -297,20 +309,15 @@ impl CommandT for QueueLength {
     ) -> BoxFuture<'static, BotResult<()>> {
         Box::pin(async move {
             let caller = ChannelId::new(event.platform, &event.channel);
-            let owner = resolve_queue_owner(&state, &caller).await?;
+            let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
 
-            let new_len: usize = event
-                .message
+            let new_len: usize = args
                 .split_whitespace()
-                .nth(1)
-                .ok_or_else(|| BotError::Chat("Usage: !len <n>".to_string()))?
+                .next()
+                .ok_or_else(|| BotError::Chat("Usage: !len [queue] <n>".to_string()))?
                 .parse()
                 .map_err(|_| BotError::Chat("Invalid Number".to_string()))?;
-            {
-                let mut cfg = state.config.write().await;
-                cfg.get_channel_config_mut(owner.clone()).size = new_len;
-                save_channel_config(&pool, &owner, &cfg).await?;
-            }
+            set_queue_len(&pool, state.clone(), &owner, &queue, new_len).await?;
 
             client
                 .send_message(&caller, &Replies::queue_length(&new_len.to_string()))
@@ -326,16 +333,17 @@ pub fn toggle_queue_command(open: bool) -> Arc<dyn CommandT> {
         move |event, pool, state, client| {
             Box::pin(async move {
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
 
-                let msg = toggle_queue(&pool, &state, &owner, open).await?;
+                let msg = toggle_queue(&pool, &state, &owner, &queue, open).await?;
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
 
                 client.send_message(&caller, &msg).await?;
                 Ok(())
             })
         },
         if open { "Open queue" } else { "Close queue" },
-        if open { "!open" } else { "!close" },
+        if open { "!open [queue]" } else { "!close [queue]" },
         if open { "open" } else { "close" },
         PermissionLevel::Moderator,
     ))
@@ -346,25 +354,22 @@ pub fn list() -> Arc<dyn CommandT> {
         |event, pool, state, client| {
             Box::pin(async move {
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
 
                 let (teamsize, random_queue) = {
-                    let cfg = state.config.read().await;
-                    let c = cfg
-                        .channels
-                        .get(&owner)
-                        .ok_or(BotError::ConfigMissing(owner.clone()))?;
-                    (c.teamsize as usize, c.random_queue)
+                    let c = queue_settings(&state, &owner, &queue).await?;
+                    (c.teamsize, c.random_queue)
                 };
 
                 let queue_entries = sqlx::query!(
                     r#"
-                    SELECT display_name, bungie_name
+                    SELECT user_id, display_name, bungie_name, tags
                     FROM krapbott_v2.queue
-                    WHERE channel_id = $1
+                    WHERE channel_id = $1 AND queue_name = $2
                     ORDER BY position ASC, locked_first DESC, group_priority ASC
                     "#,
-                    owner.as_str()
+                    owner.as_str(),
+                    queue
                 )
                 .fetch_all(&pool)
                 .await?;
@@ -373,10 +378,15 @@ pub fn list() -> Arc<dyn CommandT> {
                     return Err(BotError::Chat("❌❌❌The queue is empty❌❌❌".to_string()));
                 }
 
+                // Stav ve hře jen u aktuální skupiny, když je kontrola zapnutá
+                let presence = group_presence(&pool, &state, &owner, &queue).await?.unwrap_or_default();
                 let queue_msg: Vec<String> = queue_entries
                     .iter()
                     .enumerate()
-                    .map(|(i, q)| format!("{}. {} ({})", i + 1, q.display_name, q.bungie_name))
+                    .map(|(i, q)| {
+                        let icon = presence.get(&q.user_id).filter(|_| i < teamsize).map(|p| format!("{} ", p.icon())).unwrap_or_default();
+                        format!("{}. {}{} ({}){}", i + 1, icon, q.display_name, q.bungie_name, format_tags(&q.tags))
+                    })
                     .collect();
 
                 let format_group = |group: &[String]| group.join(", ");
@@ -402,8 +412,8 @@ pub fn list() -> Arc<dyn CommandT> {
                     )
                 } else {
                     format!(
-                        "You can find queue here: https://krapbott.up.railway.app/queue/{}",
-                        owner.channel()
+                        "You can find queue here: {}",
+                        public_queue_url(&owner, &queue)
                     )
                 };
 
@@ -412,7 +422,7 @@ pub fn list() -> Arc<dyn CommandT> {
             })
         },
         "Shows the queue list or site",
-        "!list, !queue",
+        "!list [queue]",
         "list",
         PermissionLevel::Everyone,
     ))
@@ -423,13 +433,16 @@ pub fn random() -> Arc<dyn CommandT> {
         |event, pool, state, client| {
             Box::pin(async move {
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
 
                 {
                     let mut cfg = state.config.write().await;
-                    let channel_config = cfg.get_channel_config_mut(owner.clone());
-                    let random = channel_config.random_queue;
-                    channel_config.random_queue = !random;
+                    let queue_config = cfg
+                        .get_channel_config_mut(owner.clone())
+                        .queue_mut(&queue)
+                        .ok_or_else(|| BotError::Chat(Replies::queue_unknown(&queue)))?;
+                    let random = queue_config.random_queue;
+                    queue_config.random_queue = !random;
 
                     save_channel_config(&pool, &owner, &cfg).await?;
 
@@ -444,7 +457,7 @@ pub fn random() -> Arc<dyn CommandT> {
             })
         },
         "Raffle Mode",
-        "",
+        "!random [queue]",
         "Random",
         PermissionLevel::Moderator,
     ))
@@ -459,22 +472,20 @@ pub fn pos() -> Arc<dyn CommandT> {
                     .as_ref()
                     .ok_or_else(|| BotError::Custom("No user".to_string()))?;
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
 
-                let (teamsize, random_queue, open, max_len) = {
-                    let cfg = state.config.read().await;
-                    let c = cfg
-                        .get_channel_config(&owner)
-                        .ok_or(BotError::ConfigMissing(owner.clone()))?;
-                    (c.teamsize as i64, c.random_queue, c.open, c.size as i64)
+                let (teamsize, random_queue, open, max_len, policy) = {
+                    let c = queue_settings(&state, &owner, &queue).await?;
+                    (c.teamsize as i64, c.random_queue, c.open, c.size as i64, c.raffle)
                 };
                 let user_id = UserId::new(
                     user.identity.platform,
                     user.identity.platform_user_id.clone(),
                 );
                 let max_count: i64 = sqlx::query_scalar!(
-                    r#"SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1"#,
-                    owner.as_str()
+                    r#"SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2"#,
+                    owner.as_str(),
+                    queue
                 )
                 .fetch_one(&pool)
                 .await?
@@ -486,10 +497,11 @@ pub fn pos() -> Arc<dyn CommandT> {
                         SELECT user_id,
                                ROW_NUMBER() OVER (ORDER BY position) AS pos
                         FROM krapbott_v2.queue
-                        WHERE channel_id = $1
+                        WHERE channel_id = $1 AND queue_name = $2
                     )
-                    SELECT pos FROM RankedQueue WHERE user_id = $2"#,
+                    SELECT pos FROM RankedQueue WHERE user_id = $3"#,
                     owner.as_str(),
+                    queue,
                     user_id.as_str()
                 )
                 .fetch_optional(&pool)
@@ -502,11 +514,16 @@ pub fn pos() -> Arc<dyn CommandT> {
                     match pos {
                         Some(index) => {
                             let group = (index - 1) / teamsize + 1;
+                            let eta = fetch_run_pace(&pool, &owner, &queue)
+                                .await?
+                                .eta_seconds(index, teamsize)
+                                .map(|s| (s + 59) / 60);
                             Replies::pos_reply(
                                 group,
                                 &index.to_string(),
                                 &max_count.to_string(),
                                 &user.name.display,
+                                eta,
                             )
                         }
                         None => {
@@ -527,7 +544,15 @@ pub fn pos() -> Arc<dyn CommandT> {
                     }
                 } else {
                     match pos {
-                        Some(_) => format!("✅ You are entered in the raffle, {}", sender),
+                        Some(index) if index <= teamsize => Replies::raffle_drawn(sender),
+                        Some(_) => {
+                            let entrants = fetch_raffle_entrants_pool(&pool, &owner, &queue).await?;
+                            let odds = raffle_odds(&entrants, &policy, teamsize as usize);
+                            match odds.get(user_id.as_str()) {
+                                Some(odds) => Replies::raffle_odds(sender, *odds),
+                                None => format!("✅ You are entered in the raffle, {}", sender),
+                            }
+                        }
                         None => format!("❌ You are not entered in the raffle, {}", sender),
                     }
                 };
@@ -538,12 +563,421 @@ pub fn pos() -> Arc<dyn CommandT> {
             })
         },
         "Show position in queue",
-        "!pos",
+        "!pos [queue]",
         "position",
         PermissionLevel::Everyone,
     ))
 }
 
+pub fn queues_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, _pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+
+                let summary = {
+                    let cfg = state.config.read().await;
+                    let c = cfg
+                        .get_channel_config(&owner)
+                        .ok_or(BotError::ConfigMissing(owner.clone()))?;
+                    c.queue_names()
+                        .iter()
+                        .filter_map(|name| c.queue(name).map(|q| (name, q)))
+                        .map(|(name, q)| {
+                            format!(
+                                "{} ({}, {}x{}{})",
+                                name,
+                                if q.open { "open" } else { "closed" },
+                                q.teamsize,
+                                q.size,
+                                if q.random_queue { ", raffle" } else { "" }
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                };
+
+                client.send_message(&caller, &Replies::queues_list(&summary)).await?;
+                Ok(())
+            })
+        },
+        "List queues of this channel",
+        "!queues",
+        "queues",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn queue_create_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let usage = || BotError::Chat("Usage: !queue_create <name> [teamsize] [length]".to_string());
+                let args: Vec<&str> = event.message.split_whitespace().skip(1).collect();
+                let Some(name) = args.first().map(|n| n.to_lowercase()) else {
+                    return Err(usage());
+                };
+                if name == MAIN_QUEUE || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                    return Err(BotError::Chat(format!("Invalid queue name: {}", name)));
+                }
+
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+
+                // Nulová velikost by rozbila dělení na skupiny (!pos, !list, ETA)
+                let mut settings = QueueSettings::default();
+                for (arg, value) in [(args.get(1), &mut settings.teamsize), (args.get(2), &mut settings.size)] {
+                    if let Some(arg) = arg {
+                        *value = arg.parse().ok().filter(|n| *n >= 1).ok_or_else(usage)?;
+                    }
+                }
+
+                {
+                    let mut cfg = state.config.write().await;
+                    let c = cfg.get_channel_config_mut(owner.clone());
+                    if c.queues.contains_key(&name) {
+                        return Err(BotError::Chat(format!("Queue {} already exists", name)));
+                    }
+                    c.queues.insert(name.clone(), settings);
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+                client.send_message(&caller, &Replies::queue_created(&name)).await?;
+                Ok(())
+            })
+        },
+        "Create a named queue",
+        "!queue_create <name> [teamsize] [length]",
+        "queue_create",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn queue_delete_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let Some(name) = event.message.split_whitespace().nth(1).map(|n| n.to_lowercase()) else {
+                    return Err(BotError::Chat("Usage: !queue_delete <name>".to_string()));
+                };
+
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+
+                let settings = {
+                    let cfg = state.config.read().await;
+                    let queues = cfg.get_channel_config(&owner).map(|c| &c.queues);
+                    match queues.and_then(|q| q.get(&name)) {
+                        Some(settings) => settings.clone(),
+                        None => return Err(BotError::Chat(Replies::queue_unknown(&name))),
+                    }
+                };
+
+                // Jako každá změna fronty: pod zámkem a se snapshotem, `!undo` vrátí frontu i nastavení.
+                // Zámek fronty se bere před zámkem konfigurace, stejně jako v `!undo`.
+                let mut tx = begin_queue_tx(&pool, &owner).await?;
+                let snapshot = snapshot_queue(&mut tx, &owner, &name, "queue delete").await?;
+                attach_queue_settings(&mut tx, snapshot, &serde_json::to_string(&settings)?).await?;
+                sqlx::query!(
+                    "DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2",
+                    owner.as_str(),
+                    name
+                )
+                .execute(&mut *tx)
+                .await?;
+                seal_snapshot(&mut tx, snapshot).await?;
+                tx.commit().await?;
+
+                {
+                    let mut cfg = state.config.write().await;
+                    cfg.get_channel_config_mut(owner.clone()).queues.remove(&name);
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+                client.send_message(&caller, &Replies::queue_deleted(&name)).await?;
+                Ok(())
+            })
+        },
+        "Delete a named queue and its entries",
+        "!queue_delete <name>",
+        "queue_delete",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn queue_export_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
+
+                let (link, count) = create_export_link(&pool, &state, &owner, &queue).await?;
+                client.send_message(&caller, &Replies::queue_exported(&queue, count, &link)).await?;
+                Ok(())
+            })
+        },
+        "Save the queue and post a download link (JSON or CSV)",
+        "!queue_export [queue]",
+        "queue_export",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn undo_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, rest) = resolve_named_queue(&state, &caller, &event.message).await?;
+                if !rest.is_empty() {
+                    return Err(BotError::Chat(Replies::queue_unknown(&rest)));
+                }
+
+                // Bez argumentu vrací poslední změnu kterékoli fronty kanálu
+                let queue = event.message.split_whitespace().nth(1).map(|_| queue);
+                let reply = undo_last_change(&pool, &state, &owner, queue.as_deref()).await?;
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Undo the last change to the queue",
+        "!undo [queue]",
+        "undo",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn cooldown_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let args: Vec<&str> = event.message.split_whitespace().skip(1).collect();
+
+                let mut cfg = state.config.write().await;
+                let cooldown = &mut cfg.get_channel_config_mut(owner.clone()).cooldown;
+
+                if args.first().is_some_and(|a| a.eq_ignore_ascii_case("off")) {
+                    cooldown.runs = 0;
+                    cooldown.minutes = 0;
+                } else {
+                    for pair in args.chunks(2) {
+                        let [key, value] = pair else {
+                            return Err(BotError::Chat("Usage: !cooldown [off] [runs <n>] [minutes <n>]".to_string()));
+                        };
+                        let invalid = || BotError::Chat(format!("Invalid value for {}: {}", key, value));
+                        match key.to_lowercase().as_str() {
+                            "runs" => cooldown.runs = value.parse().map_err(|_| invalid())?,
+                            "minutes" | "min" => cooldown.minutes = value.parse().map_err(|_| invalid())?,
+                            other => return Err(BotError::Chat(format!("Unknown cooldown setting: {}", other))),
+                        }
+                    }
+                }
+
+                let reply = Replies::cooldown_policy(&cooldown.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change the join cooldown after a run",
+        "!cooldown [off] [runs <n>] [minutes <n>]",
+        "cooldown",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn cooldown_clear_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let Some(target) = event.message.split_whitespace().nth(1) else {
+                    return Err(BotError::Chat("Usage: !cooldown_clear <user>".to_string()));
+                };
+
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Channel(&owner), target).await?;
+
+                clear_cooldown(&pool, &owner, viewer.user_id.as_str()).await?;
+                client.send_message(&caller, &Replies::cooldown_cleared(&viewer.display_name)).await?;
+                Ok(())
+            })
+        },
+        "Let a viewer join again right after their run",
+        "!cooldown_clear <user>",
+        "cooldown_clear",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn auto_queue_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let args: Vec<&str> = event.message.split_whitespace().skip(1).collect();
+                let usage = || BotError::Chat(
+                    "Usage: !autoqueue [off | live [delay min] | schedule | tz <zone> | add <day> <HH:MM> <HH:MM> | clear]".to_string(),
+                );
+
+                let mut cfg = state.config.write().await;
+                let auto = &mut cfg.get_channel_config_mut(owner.clone()).auto_queue;
+
+                match args.as_slice() {
+                    [] => {}
+                    ["off"] => auto.mode = AutoQueueMode::Off,
+                    ["live"] => auto.mode = AutoQueueMode::Live,
+                    ["live", delay] => {
+                        auto.open_delay_minutes = delay.parse().map_err(|_| usage())?;
+                        auto.mode = AutoQueueMode::Live;
+                    }
+                    ["schedule"] => auto.mode = AutoQueueMode::Schedule,
+                    ["tz", zone] => {
+                        zone.parse::<chrono_tz::Tz>().map_err(|_| BotError::Chat(format!("Unknown timezone {}", zone)))?;
+                        auto.timezone = zone.to_string();
+                    }
+                    ["add", day, open, close] => {
+                        let slot = ScheduleSlot::parse(day, open, close).ok_or_else(usage)?;
+                        auto.schedule.push(slot);
+                        auto.mode = AutoQueueMode::Schedule;
+                    }
+                    ["clear"] => auto.schedule.clear(),
+                    _ => return Err(usage()),
+                }
+
+                let reply = Replies::auto_queue_policy(&auto.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Open and close the queue automatically with the stream or a weekly schedule",
+        "!autoqueue [off | live [delay min] | schedule | tz <zone> | add <day> <HH:MM> <HH:MM> | clear]",
+        "autoqueue",
+        PermissionLevel::Broadcaster,
+    ))
+}
+
+pub fn history_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let now = chrono::Utc::now().timestamp();
+
+                // Bungie names may contain spaces, take the whole rest of the message
+                let viewer = event
+                    .message
+                    .split_once(' ')
+                    .map(|(_, rest)| rest.trim().trim_start_matches('@'))
+                    .filter(|s| !s.is_empty())
+                    .map(String::from);
+
+                let reply = match viewer {
+                    Some(viewer) => {
+                        let runs =
+                            fetch_viewer_runs(&pool, &owner, &viewer, HISTORY_WINDOW_HOURS).await?;
+                        match runs.first() {
+                            Some(last) => Replies::history_played(
+                                &viewer,
+                                runs.len(),
+                                HISTORY_WINDOW_HOURS,
+                                &Replies::time_ago(now - last.played_at),
+                            ),
+                            None => Replies::history_not_played(&viewer, HISTORY_WINDOW_HOURS),
+                        }
+                    }
+                    None => {
+                        let runs = fetch_recent_runs(&pool, &owner, 3).await?;
+                        if runs.is_empty() {
+                            Replies::history_empty()
+                        } else {
+                            let summary = runs
+                                .iter()
+                                .map(|run| {
+                                    let names = run
+                                        .members
+                                        .iter()
+                                        .map(|m| m.display_name.as_str())
+                                        .collect::<Vec<_>>()
+                                        .join(", ");
+                                    let label = if run.queue_name == MAIN_QUEUE {
+                                        String::new()
+                                    } else {
+                                        format!("[{}] ", run.queue_name)
+                                    };
+                                    format!(
+                                        "{}{}{} ({})",
+                                        label,
+                                        if run.raffle { "🎲 " } else { "" },
+                                        names,
+                                        Replies::time_ago(now - run.played_at)
+                                    )
+                                })
+                                .collect::<Vec<_>>()
+                                .join(" | ");
+                            Replies::history_recent(&summary)
+                        }
+                    }
+                };
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show recent runs or whether a viewer already played tonight",
+        "!history [user]",
+        "history",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn stats_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let days = event
+                    .message
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|d| d.parse::<i32>().ok())
+                    .unwrap_or(STATS_DEFAULT_DAYS)
+                    .clamp(1, 365);
+                let timezone = {
+                    let cfg = state.config.read().await;
+                    cfg.get_channel_config(&owner).map(|c| c.auto_queue.timezone.clone()).unwrap_or_else(|| "UTC".to_string())
+                };
+
+                let stats = fetch_channel_stats(&pool, &owner, days, &timezone, 3).await?;
+                client.send_message(&caller, &Replies::stats_summary(&stats)).await?;
+                Ok(())
+            })
+        },
+        "Summarize runs, players and wait times",
+        "!stats [days]",
+        "stats",
+        PermissionLevel::Everyone,
+    ))
+}
+
 pub fn bungie_name_command() -> Arc<dyn CommandT> {
     Arc::new(FnCommand::new(
         |event, pool, state, client| {
@@ -705,14 +1139,10 @@ pub fn leave_command() -> Arc<dyn CommandT> {
                     .ok_or_else(|| BotError::Custom("No user".into()))?;
 
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue) = resolve_queue(&state, &caller, &event.message).await?;
 
                 let (teamsize, random_queue) = {
-                    let cfg = state.config.read().await;
-                    let c = cfg
-                        .channels
-                        .get(&owner)
-                        .ok_or(BotError::ConfigMissing(owner.clone()))?;
+                    let c = queue_settings(&state, &owner, &queue).await?;
                     (c.teamsize as i64, c.random_queue)
                 };
 
@@ -722,17 +1152,20 @@ pub fn leave_command() -> Arc<dyn CommandT> {
                 );
                 let name = &user.name.display;
 
+                let mut tx = begin_queue_tx(&pool, &owner).await?;
+
                 // fetch position
                 let position = sqlx::query_scalar!(
                     r#"
                     SELECT position
                     FROM krapbott_v2.queue
-                    WHERE user_id = $1 AND channel_id = $2
+                    WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3
                     "#,
                     user_id.as_str(),
-                    owner.as_str()
+                    owner.as_str(),
+                    queue
                 )
-                .fetch_optional(&pool)
+                .fetch_optional(&mut *tx)
                 .await?;
 
                 let reply = if let Some(pos) = position {
@@ -740,44 +1173,21 @@ pub fn leave_command() -> Arc<dyn CommandT> {
                         "You cannot leave the LIVE group! Ask the streamer or wait for !next"
                             .to_string()
                     } else {
-                        let mut tx = pool.begin().await?;
-
                         // delete user
                         sqlx::query!(
                             r#"
                             DELETE FROM krapbott_v2.queue
-                            WHERE user_id = $1 AND channel_id = $2
+                            WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3
                             "#,
                             user_id.as_str(),
-                            owner.as_str()
-                        )
-                        .execute(&mut *tx)
-                        .await?;
-
-                        // re-pack positions
-                        sqlx::query!(
-                            r#"
-                                UPDATE krapbott_v2.queue
-                                SET position = -position
-                                WHERE channel_id = $1 AND position > $2
-                            "#,
                             owner.as_str(),
-                            pos
-                        )
-                        .execute(&mut *tx)
-                        .await?;
-
-                        sqlx::query!(
-                            r#"
-                            UPDATE krapbott_v2.queue
-                            SET position = (-position) - 1
-                            WHERE channel_id = $1 AND position < 0
-                            "#,
-                            owner.as_str()
+                            queue
                         )
                         .execute(&mut *tx)
                         .await?;
 
+                        // re-pack positions
+                        renumber_queue(&mut tx, &owner, &queue, &[]).await?;
                         tx.commit().await?;
 
                         if random_queue {
@@ -798,7 +1208,7 @@ pub fn leave_command() -> Arc<dyn CommandT> {
             })
         },
         "Leave the queue",
-        "!leave",
+        "!leave [queue]",
         "leave",
         PermissionLevel::Everyone,
     ))
@@ -810,39 +1220,32 @@ pub fn move_command() -> Arc<dyn CommandT> {
     Arc::new(FnCommand::new(
         |event, pool, state, client| {
             Box::pin(async move {
-                let args: Vec<&str> = event.message.split_whitespace().collect();
-                if args.len() < 2 {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+                if args.is_empty() {
                     client
-                        .send_message(
-                            &ChannelId::new(event.platform, &event.channel),
-                            "Usage: !move <user>",
-                        )
+                        .send_message(&caller, "Usage: !move [queue] <user>")
                         .await?;
                     return Ok(());
                 }
 
-                let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
-
-                let teamsize = {
-                    let cfg = state.config.read().await;
-                    cfg.channels
-                        .get(&owner)
-                        .ok_or(BotError::ConfigMissing(owner.clone()))?
-                        .teamsize as i64
-                };
+                let teamsize = queue_settings(&state, &owner, &queue).await?.teamsize as i64;
 
-                let target = args[1].trim_start_matches('@');
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Queue(&owner, &queue), args[0]).await?;
+                let target = viewer.display_name.as_str();
 
-                let mut tx = pool.begin().await?;
+                let mut tx = begin_queue_tx(&pool, &owner).await?;
+                let snapshot = snapshot_queue(&mut tx, &owner, &queue, "move").await?;
 
                 let pos: Option<i32> = sqlx::query_scalar!(
                     r#"
                     SELECT position FROM krapbott_v2.queue
-                    WHERE display_name = $1 AND channel_id = $2
+                    WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3
                     "#,
-                    target,
-                    owner.as_str()
+                    viewer.user_id.as_str(),
+                    owner.as_str(),
+                    queue
                 )
                 .fetch_optional(&mut *tx)
                 .await?;
@@ -858,8 +1261,8 @@ pub fn move_command() -> Arc<dyn CommandT> {
                 };
 
                 let max_pos: i32 = sqlx::query_scalar!(
-                    r#"SELECT COALESCE(MAX(position), 0) FROM krapbott_v2.queue WHERE channel_id = $1"#,
-                    owner.as_str()
+                    r#"SELECT COALESCE(MAX(position), 0) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2"#,
+                    owner.as_str(), queue
                 ).fetch_one(&mut *tx).await?.unwrap_or(0);
 
                 let new_pos = pos + (teamsize as i32);
@@ -876,17 +1279,18 @@ pub fn move_command() -> Arc<dyn CommandT> {
                 let temp = max_pos + 1000;
 
                 sqlx::query!(
-                    r#"UPDATE krapbott_v2.queue SET position = $1 WHERE display_name = $2 AND channel_id = $3"#,
-                    temp, target, owner.as_str()
+                    r#"UPDATE krapbott_v2.queue SET position = $1 WHERE user_id = $2 AND channel_id = $3 AND queue_name = $4"#,
+                    temp, viewer.user_id.as_str(), owner.as_str(), queue
                 ).execute(&mut *tx).await?;
 
                 sqlx::query!(
                     r#"
                     UPDATE krapbott_v2.queue
                     SET position = position - 1
-                    WHERE channel_id = $1 AND position BETWEEN $2 AND $3
+                    WHERE channel_id = $1 AND queue_name = $2 AND position BETWEEN $3 AND $4
                     "#,
                     owner.as_str(),
+                    queue,
                     pos + 1,
                     new_pos
                 )
@@ -894,10 +1298,11 @@ pub fn move_command() -> Arc<dyn CommandT> {
                 .await?;
 
                 sqlx::query!(
-                    r#"UPDATE krapbott_v2.queue SET position = $1 WHERE display_name = $2 AND channel_id = $3"#,
-                    new_pos, target, owner.as_str()
+                    r#"UPDATE krapbott_v2.queue SET position = $1 WHERE user_id = $2 AND channel_id = $3 AND queue_name = $4"#,
+                    new_pos, viewer.user_id.as_str(), owner.as_str(), queue
                 ).execute(&mut *tx).await?;
 
+                seal_snapshot(&mut tx, snapshot).await?;
                 tx.commit().await?;
 
                 client
@@ -913,7 +1318,7 @@ pub fn move_command() -> Arc<dyn CommandT> {
             })
         },
         "Move user to next group",
-        "!move <user>",
+        "!move [queue] <user>",
         "move",
         PermissionLevel::Moderator,
     ))
@@ -923,50 +1328,32 @@ pub fn remove_command() -> Arc<dyn CommandT> {
     Arc::new(FnCommand::new(
         |event, pool, state, client| {
             Box::pin(async move {
-                let args: Vec<&str> = event.message.split_whitespace().collect();
-                if args.len() != 2 {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+                if args.len() != 1 {
                     return Ok(());
                 }
 
-                let target = args[1].trim_start_matches('@');
-                let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Queue(&owner, &queue), args[0]).await?;
+                let target = viewer.display_name.as_str();
 
-                let pos = sqlx::query_scalar!(
-                    r#"SELECT position FROM krapbott_v2.queue WHERE display_name = $1 AND channel_id = $2"#,
-                    target, owner.as_str()
-                ).fetch_optional(&pool).await?;
+                let mut tx = begin_queue_tx(&pool, &owner).await?;
 
-                let mut tx = pool.begin().await?;
+                let pos = sqlx::query_scalar!(
+                    r#"SELECT position FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3"#,
+                    viewer.user_id.as_str(), owner.as_str(), queue
+                ).fetch_optional(&mut *tx).await?;
 
                 let reply = if pos.is_some() {
+                    let snapshot = snapshot_queue(&mut tx, &owner, &queue, "remove").await?;
                     sqlx::query!(
-                        r#"DELETE FROM krapbott_v2.queue WHERE display_name = $1 AND channel_id = $2"#,
-                        target, owner.as_str()
+                        r#"DELETE FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3"#,
+                        viewer.user_id.as_str(), owner.as_str(), queue
                     ).execute(&mut *tx).await?;
 
-                    sqlx::query!(
-                        r#"
-                            UPDATE krapbott_v2.queue
-                            SET position = -position
-                            WHERE channel_id = $1 AND position > $2
-                        "#,
-                        owner.as_str(),
-                        pos
-                    )
-                    .execute(&mut *tx)
-                    .await?;
-
-                    sqlx::query!(
-                        r#"
-                        UPDATE krapbott_v2.queue
-                        SET position = (-position) - 1
-                        WHERE channel_id = $1 AND position < 0
-                        "#,
-                        owner.as_str()
-                    )
-                    .execute(&mut *tx)
-                    .await?;
+                    renumber_queue(&mut tx, &owner, &queue, &[]).await?;
+                    seal_snapshot(&mut tx, snapshot).await?;
 
                     Replies::queue_removed(target)
                 } else {
@@ -982,103 +1369,245 @@ pub fn remove_command() -> Arc<dyn CommandT> {
             })
         },
         "Remove user from queue",
-        "!remove <user>",
+        "!remove [queue] <user>",
         "remove",
         PermissionLevel::Moderator,
     ))
 }
 
-pub fn prio_command() -> Arc<dyn CommandT> {
+pub fn raffle_weights_command() -> Arc<dyn CommandT> {
     Arc::new(FnCommand::new(
         |event, pool, state, client| {
             Box::pin(async move {
-                let args: Vec<&str> = event.message.split_whitespace().collect();
-                if args.len() < 2 {
-                    client
-                        .send_message(
-                            &ChannelId::new(event.platform, &event.channel),
-                            "Usage: !prio <user> [runs]",
-                        )
-                        .await?;
-                    return Ok(());
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+
+                let mut cfg = state.config.write().await;
+                let policy = &mut cfg
+                    .get_channel_config_mut(owner.clone())
+                    .queue_mut(&queue)
+                    .ok_or_else(|| BotError::Chat(Replies::queue_unknown(&queue)))?
+                    .raffle;
+
+                for pair in args.chunks(2) {
+                    let [key, value] = pair else {
+                        return Err(BotError::Chat(
+                            "Usage: !raffle_weights [queue] [sub <n>] [vip <n>] [loss <n>] [maxloss <n>]".to_string(),
+                        ));
+                    };
+                    let invalid = || BotError::Chat(format!("Invalid value for {}: {}", key, value));
+                    let bonus = || parse_bonus(value).ok_or_else(invalid);
+                    match key.to_lowercase().as_str() {
+                        "sub" => policy.subscriber_bonus = bonus()?,
+                        "vip" => policy.vip_bonus = bonus()?,
+                        "loss" => policy.loss_bonus = bonus()?,
+                        "maxloss" => policy.max_losses = value.parse().map_err(|_| invalid())?,
+                        other => return Err(BotError::Chat(format!("Unknown raffle weight: {}", other))),
+                    }
+                }
+
+                let reply = Replies::raffle_policy(&policy.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
                 }
+                drop(cfg);
 
-                let target = args[1].trim_start_matches('@');
-                let runs = args.get(2).and_then(|r| r.parse::<i32>().ok());
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change raffle weighting",
+        "!raffle_weights [queue] [sub <n>] [vip <n>] [loss <n>] [maxloss <n>]",
+        "raffle_weights",
+        PermissionLevel::Moderator,
+    ))
+}
 
+pub fn balance_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
                 let caller = ChannelId::new(event.platform, &event.channel);
-                let owner = resolve_queue_owner(&state, &caller).await?;
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
 
-                let teamsize = {
-                    let cfg = state.config.read().await;
-                    cfg.channels
-                        .get(&owner)
-                        .ok_or(BotError::ConfigMissing(owner.clone()))?
-                        .teamsize as i32
+                let mut cfg = state.config.write().await;
+                let balance = &mut cfg
+                    .get_channel_config_mut(owner.clone())
+                    .queue_mut(&queue)
+                    .ok_or_else(|| BotError::Chat(Replies::queue_unknown(&queue)))?
+                    .balance;
+
+                if let [off] = args.as_slice() {
+                    if !off.eq_ignore_ascii_case("off") {
+                        return Err(BotError::Chat("Usage: !balance [queue] [<tag> <max>|off]... | off".to_string()));
+                    }
+                    balance.max_per_group.clear();
+                } else {
+                    for pair in args.chunks(2) {
+                        let [tag, value] = pair else {
+                            return Err(BotError::Chat("Usage: !balance [queue] [<tag> <max>|off]... | off".to_string()));
+                        };
+                        let tag = canonical_tag(tag)
+                            .ok_or_else(|| BotError::Chat(format!("Unknown tag {}, use one of: {}", tag, known_tags())))?;
+                        if value.eq_ignore_ascii_case("off") {
+                            balance.max_per_group.remove(tag);
+                        } else {
+                            let max = value.parse().map_err(|_| BotError::Chat(format!("Invalid value for {}: {}", tag, value)))?;
+                            balance.max_per_group.insert(tag.to_string(), max);
+                        }
+                    }
+                }
+
+                let reply = Replies::group_balance(&balance.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change how many players with a tag can be in one group",
+        "!balance [queue] [<tag> <max>|off]... | off",
+        "balance",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn raffle_bonus_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+                let (Some(target), Some(bonus)) = (args.first(), args.get(1).and_then(|b| parse_bonus(b))) else {
+                    return Err(BotError::Chat("Usage: !raffle_bonus [queue] <user> <bonus>".to_string()));
                 };
 
-                let second_group = teamsize + 1;
-                let mut tx = pool.begin().await?;
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Channel(&owner), target).await?;
 
-                let exists: Option<i32> = sqlx::query_scalar!(
-                    r#"SELECT position FROM krapbott_v2.queue WHERE display_name = $1 AND channel_id = $2"#,
-                    target, owner.as_str()
-                ).fetch_optional(&mut *tx).await?;
+                set_raffle_bonus(&pool, &owner, &queue, viewer.user_id.as_str(), bonus).await?;
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+                client.send_message(&caller, &Replies::raffle_bonus_set(&viewer.display_name, bonus)).await?;
+                Ok(())
+            })
+        },
+        "Give a viewer extra raffle weight",
+        "!raffle_bonus [queue] <user> <bonus>",
+        "raffle_bonus",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn ready_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, _pool, state, client| {
+            Box::pin(async move {
+                let user = event
+                    .user
+                    .as_ref()
+                    .ok_or_else(|| BotError::Custom("No user".into()))?;
+
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let user_id = UserId::new(user.identity.platform, user.identity.platform_user_id.clone());
+
+                // Bez probíhajícího checku se nic neděje, ať to nespamuje chat
+                if let Some(reply) = mark_ready(&state, &owner, user_id.as_str()).await {
+                    client.send_message(&caller, &reply).await?;
+                }
+                Ok(())
+            })
+        },
+        "Confirm you are ready for your run",
+        "!ready",
+        "ready",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn ready_check_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
 
-                if exists.is_none() {
+                let mut cfg = state.config.write().await;
+                let policy = &mut cfg
+                    .get_channel_config_mut(owner.clone())
+                    .queue_mut(&queue)
+                    .ok_or_else(|| BotError::Chat(Replies::queue_unknown(&queue)))?
+                    .ready_check;
+
+                for arg in &args {
+                    match arg.to_lowercase().as_str() {
+                        "on" => policy.enabled = true,
+                        "off" => policy.enabled = false,
+                        "back" => policy.on_miss = ReadyMiss::Back,
+                        "drop" => policy.on_miss = ReadyMiss::Drop,
+                        other => match other.trim_end_matches('s').parse::<u64>() {
+                            Ok(secs) if secs > 0 => {
+                                policy.window_secs = secs;
+                                policy.enabled = true;
+                            }
+                            _ => {
+                                return Err(BotError::Chat(
+                                    "Usage: !ready_check [queue] [on|off] [seconds] [back|drop]".to_string(),
+                                ))
+                            }
+                        },
+                    }
+                }
+
+                let reply = Replies::ready_check_policy(&policy.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change the ready check after !next",
+        "!ready_check [queue] [on|off] [seconds] [back|drop]",
+        "ready_check",
+        PermissionLevel::Moderator,
+    ))
+}
+
+pub fn prio_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+                if args.is_empty() {
                     client
-                        .send_message(&caller, &format!("User {} not found in the queue", target))
+                        .send_message(&caller, "Usage: !prio [queue] <user> [runs]")
                         .await?;
                     return Ok(());
                 }
 
-                sqlx::query!(
-                    r#"UPDATE krapbott_v2.queue SET position = position + 10000 WHERE channel_id = $1 AND position >= $2"#,
-                    owner.as_str(), second_group
-                ).execute(&mut *tx).await?;
-
-                if let Some(runs) = runs {
-                    sqlx::query!(
-                        r#"
-                        UPDATE krapbott_v2.queue
-                        SET position = $1,
-                            group_priority = 1,
-                            priority_runs_left = $2,
-                            locked_first = FALSE
-                        WHERE display_name = $3 AND channel_id = $4
-                        "#,
-                        second_group,
-                        runs,
-                        target,
-                        owner.as_str()
-                    )
-                    .execute(&mut *tx)
-                    .await?;
-                } else {
-                    sqlx::query!(
-                        r#"UPDATE krapbott_v2.queue SET position = $1 WHERE display_name = $2 AND channel_id = $3"#,
-                        second_group, target, owner.as_str()
-                    ).execute(&mut *tx).await?;
-                }
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Queue(&owner, &queue), args[0]).await?;
+                let target = viewer.display_name.as_str();
+                let runs = args.get(1).and_then(|r| r.parse::<i32>().ok());
 
-                sqlx::query!(
-                    r#"
-                    WITH ranked AS (
-                        SELECT user_id, ROW_NUMBER() OVER (ORDER BY position) AS p
-                        FROM krapbott_v2.queue
-                        WHERE channel_id = $1
-                    )
-                    UPDATE krapbott_v2.queue q
-                    SET position = ranked.p
-                    FROM ranked
-                    WHERE q.user_id = ranked.user_id AND q.channel_id = $1
-                    "#,
-                    owner.as_str()
-                )
-                .execute(&mut *tx)
-                .await?;
+                let teamsize = queue_settings(&state, &owner, &queue).await?.teamsize as i32;
 
-                tx.commit().await?;
+                if !prioritize(&pool, &owner, &queue, &viewer.user_id, runs, teamsize).await? {
+                    client
+                        .send_message(&caller, &format!("User {} not found in the queue", target))
+                        .await?;
+                    return Ok(());
+                }
 
                 let reply = match runs {
                     Some(r) => Replies::priod_for__queue(target, &r.to_string()),
@@ -1092,7 +1621,7 @@ pub fn prio_command() -> Arc<dyn CommandT> {
             })
         },
         "Give priority or move to second group",
-        "!prio <user> [runs]",
+        "!prio [queue] <user> [runs]",
         "prio",
         PermissionLevel::Moderator,
     ))
@@ -1119,7 +1648,7 @@ pub fn register_command() -> Arc<dyn CommandT> {
                     &user.name.login,
                     &user.name.display,
                     bungie_name,
-                    &state.secrets.x_api_key,
+                    &state.bungie,
                 )
                 .await?;
 
@@ -1147,29 +1676,17 @@ pub fn mod_register_command() -> Arc<dyn CommandT> {
                     ));
                 }
 
-                let target_name = args[1].trim_start_matches('@');
                 let bungie_name = args[2];
-
-                let (platform_user_id, display_name) = match event.platform {
-                    Platform::Twitch => {
-                        let token = get_twitch_access_token(&state).await?;
-                        resolve_twitch_user_id(target_name, &state.secrets, &token).await?
-                    }
-                    _ => {
-                        return Err(BotError::Custom(
-                            "mod_register not supported on this platform yet".into(),
-                        ));
-                    }
-                };
+                let viewer = resolve_viewer(&pool, &state, event.platform, ViewerScope::Anywhere, args[1]).await?;
 
                 let reply = register_bungie_name(
                     &pool,
-                    event.platform,
-                    &platform_user_id,
-                    target_name,
-                    &display_name,
+                    viewer.user_id.platform(),
+                    viewer.user_id.platform_user_id(),
+                    &viewer.login,
+                    &viewer.display_name,
                     bungie_name,
-                    &state.secrets.x_api_key,
+                    &state.bungie,
                 )
                 .await?;
 
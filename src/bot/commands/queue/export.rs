@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, transfer::{QueueExport, export_queue}}, state::def::AppState};
+
+/// Jak dlouho platí odkaz z `!queue_export`
+const EXPORT_LINK_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Export zachycený v okamžiku příkazu, aby odkaz vrátil stav fronty z té chvíle
+pub struct ExportLink {
+    export: QueueExport,
+    created: Instant,
+}
+
+/// Krátké odkazy na exporty, klíčem je token v URL
+pub type ExportLinks = DashMap<String, ExportLink>;
+
+/// Uloží export fronty a vrátí krátký odkaz na něj
+pub async fn create_export_link(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str) -> BotResult<(String, usize)> {
+    let export = export_queue(pool, owner, queue).await?;
+    let count = export.entries.len();
+
+    let links = &state.runtime.export_links;
+    links.retain(|_, link| link.created.elapsed() < EXPORT_LINK_TTL);
+
+    let token = uuid::Uuid::new_v4().simple().to_string()[..10].to_string();
+    links.insert(token.clone(), ExportLink { export, created: Instant::now() });
+
+    Ok((format!("https://krapbott.up.railway.app/x/{token}"), count))
+}
+
+pub fn get_export_link(state: &AppState, token: &str) -> Option<QueueExport> {
+    let link = state.runtime.export_links.get(token)?;
+    (link.created.elapsed() < EXPORT_LINK_TTL).then(|| link.export.clone())
+}
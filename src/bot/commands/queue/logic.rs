@@ -1,18 +1,30 @@
 use std::sync::Arc;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use tracing::info;
+use sqlx::{PgConnection, PgPool};
+use tracing::{info, warn};
 use crate::bot::db::config::save_channel_config;
+use crate::bot::commands::queue::raffle::{RafflePolicy, raffle_status, weighted_order};
+use crate::bot::commands::queue::presence::check_called_group;
+use crate::bot::commands::queue::ready::{cancel_ready_check, start_ready_check};
+use crate::bot::commands::queue::requirements::check_requirements;
+use crate::bot::db::history::{RunMember, fetch_run_pace, record_run};
 use crate::bot::db::queue::BanStatus;
-use crate::bot::db::queue::add_to_queue;
+use crate::bot::db::queue::{add_to_queue, insert_into_queue, queue_position};
+use crate::bot::db::sub_prio::take_pending_prio;
 use crate::bot::db::queue::is_banned_from_queue;
 use crate::bot::db::queue::user_exists_in_queue;
-use crate::bot::db::queue::update_queue;
+use crate::bot::db::queue::{begin_queue_tx, renumber_queue};
+use crate::bot::db::cooldown::fetch_last_run;
+use crate::bot::db::snapshots::{UndoOutcome, attach_draw, attach_run, restore_last_snapshot, seal_snapshot, snapshot_queue};
+use crate::bot::db::raffle::{fetch_raffle_entrants, record_draw, set_queue_status};
 use crate::bot::replies::Replies;
-use crate::bot::state::def::BotError;
+use crate::bot::state::def::{BotError, ChannelConfig, MAIN_QUEUE, ObsQueueEntry, QueueSettings};
 use crate::bot::web::sse::SseEvent;
-use crate::bot::{chat_event::chat_event::ChatEvent, commands::commands::BotResult, db::{ChannelId, UserId, bungie::is_bungiename, users::get_queue_user_by_id}, state::def::AppState};
+use crate::bot::commands::queue::party::invite_to_party;
+use crate::bot::commands::queue::tags::{GroupBalance, format_tags, split_tags};
+use crate::bot::db::party::{pack_groups, set_party};
+use crate::bot::{chat_event::chat_event::{ChatEvent, ChatUser}, commands::commands::BotResult, db::{ChannelId, UserId, bungie::{is_bungiename, load_membership}, users::get_queue_user_by_id}, state::def::AppState};
 
 lazy_static::lazy_static!{
     static ref BUNGIE_REGEX: Regex = Regex::new(r"^(?P<name>.+)#(?P<digits>\d{4})").unwrap();
@@ -60,6 +72,81 @@ pub async fn resolve_queue_owner(state: &AppState, caller: &ChannelId) -> BotRes
     Ok(channel_cfg.queue_target.owner_channel().clone())
 }
 
+/// Všechny kanály, které používají frontu vlastníka (u sdílené fronty i ostatní platformy)
+pub async fn queue_channels(state: &AppState, owner: &ChannelId) -> Vec<ChannelId> {
+    let cfg = state.config.read().await;
+    let mut channels: Vec<ChannelId> = cfg.channels
+        .iter()
+        .filter(|(_, c)| c.queue_target.owner_channel() == owner)
+        .map(|(id, _)| id.clone())
+        .collect();
+    if !channels.contains(owner) {
+        channels.push(owner.clone());
+    }
+    channels
+}
+
+/// Jméno fronty ze slova `raid` nebo `raid:`, `None` když taková fronta není
+fn queue_name_of(cfg: &ChannelConfig, word: &str) -> Option<String> {
+    let word = word.strip_suffix(':').unwrap_or(word);
+    if word.eq_ignore_ascii_case(MAIN_QUEUE) {
+        return Some(MAIN_QUEUE.to_string());
+    }
+    cfg.queues.keys().find(|name| name.eq_ignore_ascii_case(word)).cloned()
+}
+
+/// Rozdělí argumenty příkazu na jméno fronty a zbytek (`!add raid @name Name#1234`).
+/// Pokud první slovo není pojmenovaná fronta, jde o hlavní frontu a argumenty zůstanou celé.
+/// `raid:` s dvojtečkou fronta být musí, Bungie jméno začínající jménem fronty jde do hlavní přes `main: raid Lord#1234`.
+pub fn split_queue_name<'a>(cfg: &ChannelConfig, args: &'a str) -> Result<(String, &'a str), String> {
+    let args = args.trim();
+    let (first, rest) = args.split_once(' ').unwrap_or((args, ""));
+
+    match queue_name_of(cfg, first) {
+        Some(name) => Ok((name, rest.trim())),
+        None if first.ends_with(':') => Err(Replies::queue_unknown(first.trim_end_matches(':'))),
+        None => Ok((MAIN_QUEUE.to_string(), args)),
+    }
+}
+
+/// Owner channel, queue name and the remaining arguments of a chat command
+pub async fn resolve_named_queue(state: &AppState, caller: &ChannelId, message: &str) -> BotResult<(ChannelId, String, String)> {
+    let owner = resolve_queue_owner(state, caller).await?;
+    let cfg = state.config.read().await;
+    let owner_cfg = cfg.get_channel_config(&owner).ok_or(BotError::ConfigMissing(owner.clone()))?;
+
+    let args = message.split_once(' ').map(|(_, rest)| rest).unwrap_or("");
+    let (queue, rest) = split_queue_name(owner_cfg, args).map_err(BotError::Chat)?;
+    Ok((owner, queue, rest.to_string()))
+}
+
+/// Owner channel and queue name of a command that takes nothing but the queue (`!next raid`)
+pub async fn resolve_queue(state: &AppState, caller: &ChannelId, message: &str) -> BotResult<(ChannelId, String)> {
+    let (owner, queue, rest) = resolve_named_queue(state, caller, message).await?;
+    match rest.split_whitespace().next() {
+        Some(unknown) => Err(BotError::Chat(Replies::queue_unknown(unknown))),
+        None => Ok((owner, queue)),
+    }
+}
+
+/// Odkaz na veřejnou stránku fronty
+pub fn public_queue_url(owner: &ChannelId, queue: &str) -> String {
+    if queue == MAIN_QUEUE {
+        format!("https://krapbott.up.railway.app/queue/{}", owner.channel())
+    } else {
+        format!("https://krapbott.up.railway.app/queue/{}?queue={}", owner.channel(), queue)
+    }
+}
+
+pub async fn queue_settings(state: &AppState, owner: &ChannelId, queue: &str) -> BotResult<QueueSettings> {
+    let cfg = state.config.read().await;
+    cfg.get_channel_config(owner)
+        .ok_or(BotError::ConfigMissing(owner.clone()))?
+        .queue(queue)
+        .cloned()
+        .ok_or_else(|| BotError::Chat(Replies::queue_unknown(queue)))
+}
+
 impl AppState {
     pub async fn handle_join(&self, event: ChatEvent, pool: &PgPool) -> BotResult<Option<String>> {
         info!("Handling !join command from user");
@@ -77,37 +164,83 @@ impl AppState {
 
         let channel_id = ChannelId::new(event.platform, &event.channel);
 
-        let (queue_owner, open, queue_len, random_queue) = {
-            let state = self.config.read().await;
-            let cfg = match state.get_channel_config(&channel_id) {
-                Some(cfg) => cfg,
-                None => return Ok(None),
-            };
+        let (queue_owner, queue, args) = match resolve_named_queue(self, &channel_id, &event.message).await {
+            Ok(resolved) => resolved,
+            Err(BotError::ConfigMissing(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let QueueSettings { open, size: queue_len, random_queue, teamsize, .. } = queue_settings(self, &queue_owner, &queue).await?;
 
-            let owner = cfg.queue_target.owner_channel().clone();
-            let cfg = match state.get_channel_config(&owner) {
-                Some(cfg) => cfg,
-                None => return Ok(None),
-            };
-            (
-                owner,
-                cfg.open,
-                cfg.size,
-                cfg.random_queue
-            )
-        };  
-        
         if !open {
             return Ok(Some(Replies::join_closed(&user.name.display)));
         }
 
+        // !join party @a @b [Bungie Name#1234] — spoluhráči jsou zmínky s @, zbytek je Bungie jméno
+        let mut words = args.split_whitespace().peekable();
+        let party = words.next_if(|w| w.eq_ignore_ascii_case("party")).is_some();
+        let (invitees, provided): (Vec<&str>, Vec<&str>) = if party {
+            words.partition(|w| w.starts_with('@'))
+        } else {
+            (Vec::new(), args.split_whitespace().collect())
+        };
+        let mut invitees: Vec<String> = invitees.iter().map(|i| i.trim_start_matches('@').to_lowercase()).filter(|i| !i.is_empty() && *i != user.name.login).collect();
+        invitees.sort();
+        invitees.dedup();
+
+        if party {
+            if invitees.is_empty() || random_queue {
+                return Ok(Some(Replies::party_usage()));
+            }
+            if invitees.len() + 1 > teamsize {
+                return Ok(Some(Replies::party_too_big(&user.name.display, teamsize)));
+            }
+        }
+
+        let entry = match self.resolve_joiner(pool, user, &queue_owner, &queue, &provided.join(" ")).await? {
+            Ok(entry) => entry,
+            Err(rejection) => return Ok(Some(rejection)),
+        };
+        let user_id = entry.user_id.clone();
+
+        // Status i party se zapíšou pod stejným zámkem jako nový řádek
+        let mut tx = begin_queue_tx(pool, &queue_owner).await?;
+        let mut reply = insert_into_queue(&mut tx, queue_len, &entry, &queue_owner, &queue, Queue::Join, random_queue).await?;
+        let queued = user_exists_in_queue(&mut *tx, &user_id, &queue_owner, &queue).await?;
+        if queued {
+            let (subscriber, vip) = raffle_status(user);
+            set_queue_status(&mut *tx, &queue_owner, &queue, &user_id, subscriber, vip).await?;
+            if party {
+                set_party(&mut *tx, &queue_owner, &queue, user_id.as_str(), Some(user_id.as_str())).await?;
+            }
+        }
+        tx.commit().await?;
+
+        // Prio za sub, který přišel, když divák ještě nečekal ve frontě
+        let waiting = queue_position(pool, &user_id, &queue_owner, &queue).await?.is_some_and(|p| p > teamsize as i32);
+        if waiting {
+            if let Some(runs) = take_pending_prio(pool, &queue_owner, &user_id).await? {
+                if prioritize(pool, &queue_owner, &queue, &user_id, Some(runs), teamsize as i32).await? {
+                    reply = format!("{} {}", reply, Replies::sub_prio_applied(runs));
+                }
+            }
+        }
+
+        if party && queued {
+            invite_to_party(self, &queue_owner, &queue, &user_id, &user.name.display, &invitees).await;
+            return Ok(Some(format!("{} {}", reply, Replies::party_invited(&user.name.display, &invitees))));
+        }
+
+        Ok(Some(reply))
+    }
+
+    /// Bungie jméno, ban a cooldown jednoho hráče. `Err` je odmítnutí pro chat.
+    pub async fn resolve_joiner(&self, pool: &PgPool, user: &ChatUser, queue_owner: &ChannelId, queue: &str, provided: &str) -> BotResult<Result<QueueEntry, String>> {
         let user_id = UserId::new(user.identity.platform, user.identity.platform_user_id.clone());
         let stored_user = get_queue_user_by_id(&pool, &user_id).await?;
-        
-        let provided_name = event
-            .message
-            .split_once(' ')
-            .map(|(_, rest)| rest.trim())
+
+        // Role / class tagy jsou na konci (`!join Name#1234 titan sherpa`)
+        let (provided, tags) = split_tags(provided);
+        let provided_name = Some(provided.trim())
             .filter(|s| !s.is_empty())
             .map(String::from);
 
@@ -115,19 +248,33 @@ impl AppState {
             (Some(provided), Some(stored)) => {
                 if stored.bungie_name == provided {
                     Some(stored.bungie_name)
-                } else if is_valid_bungie_name(&provided).is_some()
-                    && is_bungiename(pool, &user, &provided, &self.secrets.x_api_key).await {
-                    Some(provided)
-                } else {
+                } else if is_valid_bungie_name(&provided).is_none() {
                     Some(stored.bungie_name)
+                } else {
+                    // Když Bungie neodpovídá, pustíme ho s uloženým jménem
+                    match is_bungiename(pool, user, &provided, &self.bungie).await {
+                        Ok(true) => Some(provided),
+                        Ok(false) => Some(stored.bungie_name),
+                        Err(err) => {
+                            warn!("Bungie lookup for {} failed, using stored name: {}", provided, err);
+                            Some(stored.bungie_name)
+                        }
+                    }
                 }
             }
 
             (Some(provided), None) => {
-                if is_valid_bungie_name(&provided).is_some() && is_bungiename(pool, &user, &provided, &self.secrets.x_api_key).await {
-                    Some(provided)
-                } else {
+                if is_valid_bungie_name(&provided).is_none() {
                     None
+                } else {
+                    match is_bungiename(pool, user, &provided, &self.bungie).await {
+                        Ok(true) => Some(provided),
+                        Ok(false) => return Ok(Err(Replies::join_bungie_not_found(&user.name.display, &provided))),
+                        Err(err) => {
+                            warn!("Bungie lookup for {} failed: {}", provided, err);
+                            return Ok(Err(Replies::join_bungie_unavailable(&user.name.display)));
+                        }
+                    }
                 }
             }
 
@@ -138,29 +285,56 @@ impl AppState {
         let bungie_name = match bungie_name {
             Some(name) => name,
             None => {
-                return Ok(Some(Replies::join_invalid_bungie(&user.name.display)));
+                return Ok(Err(Replies::join_invalid_bungie(&user.name.display)));
             }
         };
 
-        match is_banned_from_queue(pool, &user_id).await? {
+        match is_banned_from_queue(pool, queue_owner, &user_id).await? {
             BanStatus::NotBanned => {}
             BanStatus::Permanent { reason } => {
-                return Ok(Some(Replies::join_banned(&user.name.display, reason.as_deref())));
+                return Ok(Err(Replies::join_banned(&user.name.display, reason.as_deref())));
             }
-            BanStatus::Timed { reason, .. } => {
-                return Ok(Some(Replies::join_timed_out(&user.name.display)));
+            BanStatus::Timed { reason, banned_until } => {
+                let seconds_left = banned_until.unix_timestamp() - chrono::Utc::now().timestamp();
+                return Ok(Err(Replies::join_timed_out(&user.name.display, reason.as_deref(), seconds_left)));
             }
         }
 
-        let entry = QueueEntry {
-            user_id,
-            bungie_name: bungie_name.clone(),
-            display_name: user.name.display.clone(),
-        };
+        // Cooldown po runu, kdo už ve frontě je, si jen mění jméno
+        let cooldown = self.config.read().await.get_channel_config(queue_owner).map(|c| c.cooldown.clone()).unwrap_or_default();
+        if cooldown.is_enabled() && !user_exists_in_queue(pool, &user_id, queue_owner, queue).await? {
+            if let Some((runs, minutes)) = fetch_last_run(pool, queue_owner, &user_id).await?.and_then(|last| cooldown.remaining(&last)) {
+                return Ok(Err(Replies::join_cooldown(&user.name.display, runs, minutes)));
+            }
+        }
 
-        let reply = process_queue_entry(pool, queue_len, entry, &queue_owner, Queue::Join,random_queue).await?;
+        // Požadavky na cleary (experienced only / sherpa runy)
+        let requirements = self.config.read().await.get_channel_config(queue_owner).map(|c| c.requirements.clone()).unwrap_or_default();
+        if requirements.is_enabled() {
+            let membership = match load_membership(pool, user.identity.platform, &user.identity.platform_user_id).await {
+                Some(membership) => Ok(membership),
+                None => self.bungie.find_membership(&bungie_name).await,
+            };
+            let checked = match membership {
+                Ok(membership) => check_requirements(pool, self, &membership, &requirements).await,
+                Err(err) => Err(err.into()),
+            };
+            match checked {
+                Ok(None) => {}
+                Ok(Some(unmet)) => return Ok(Err(Replies::join_requirement_unmet(&user.name.display, &unmet))),
+                Err(err) => {
+                    warn!("Checking join requirements for {} failed: {}", bungie_name, err);
+                    return Ok(Err(Replies::join_requirements_unavailable(&user.name.display)));
+                }
+            }
+        }
 
-        Ok(Some(reply))
+        Ok(Ok(QueueEntry {
+            user_id,
+            bungie_name,
+            display_name: user.name.display.clone(),
+            tags,
+        }))
     }
 }
 #[derive(Debug)]
@@ -168,77 +342,111 @@ pub struct QueueEntry {
     pub user_id: UserId,
     pub bungie_name: String,
     pub display_name: String,
+    pub tags: Vec<String>,
 }
-pub async fn process_queue_entry(pool: &PgPool, queue_len: usize, user: QueueEntry, channel_id: &ChannelId, queue_join: Queue, raffle: bool) -> BotResult<String> {
-    let reply = if user_exists_in_queue(&pool, &user.user_id, channel_id).await? {
-        update_queue(&pool, &user, channel_id).await?;
-        format!("✅ {} has updated their bungie name to {}", user.display_name, user.bungie_name)
-    } else {
-        add_to_queue(queue_len, &pool, &user, channel_id, queue_join, raffle).await?
-    };
-    Ok(reply)
+pub async fn process_queue_entry(pool: &PgPool, queue_len: usize, user: QueueEntry, channel_id: &ChannelId, queue: &str, queue_join: Queue, raffle: bool) -> BotResult<String> {
+    add_to_queue(queue_len, pool, &user, channel_id, queue, queue_join, raffle).await
 }
 
 pub fn is_valid_bungie_name(name: &str) -> Option<String> {
     BUNGIE_REGEX.captures(name).map(|caps| format!("{}#{}", &caps["name"].trim(), &caps["digits"]))
 }
 
-pub async fn randomize_queue(channel: &ChannelId, pool: &PgPool, teamsize: i64) -> Result<String, BotError> {
-    let mut tx = pool.begin().await?;
-    let entries = sqlx::query!(
-        "SELECT user_id FROM krapbott_v2.queue WHERE channel_id = $1 ORDER BY position LIMIT $2",
-        channel.as_str(), teamsize
-    ).fetch_all(&mut *tx).await?;
+/// Skupina, která právě odehrála run (vrchol fronty)
+#[derive(sqlx::FromRow)]
+struct FinishedEntry {
+    user_id: String,
+    display_name: String,
+    bungie_name: String,
+    group_priority: Option<i32>,
+    priority_runs_left: Option<i32>,
+    locked_first: Option<bool>,
+    joined_at: Option<i64>,
+}
+
+impl FinishedEntry {
+    fn run_member(&self) -> RunMember {
+        RunMember {
+            user_id: self.user_id.clone(),
+            display_name: self.display_name.clone(),
+            bungie_name: self.bungie_name.clone(),
+            had_prio: self.group_priority == Some(1) || self.priority_runs_left.unwrap_or(0) > 0,
+            joined_at: self.joined_at,
+        }
+    }
+}
+
+async fn fetch_finished_group(conn: &mut PgConnection, channel: &ChannelId, queue: &str, teamsize: i64) -> BotResult<Vec<FinishedEntry>> {
+    let entries = sqlx::query_as::<_, FinishedEntry>(
+        "SELECT user_id, display_name, bungie_name, group_priority, priority_runs_left, locked_first,
+                EXTRACT(EPOCH FROM joined_at)::BIGINT AS joined_at
+         FROM krapbott_v2.queue
+         WHERE channel_id = $1 AND queue_name = $2
+         ORDER BY position ASC
+         LIMIT $3",
+    ).bind(channel.as_str()).bind(queue).bind(teamsize).fetch_all(&mut *conn).await?;
+
+    Ok(entries)
+}
+
+pub async fn randomize_queue(channel: &ChannelId, queue: &str, pool: &PgPool, teamsize: i64, policy: &RafflePolicy) -> Result<String, BotError> {
+    let mut tx = begin_queue_tx(pool, channel).await?;
+    let snapshot = snapshot_queue(&mut tx, channel, queue, "random").await?;
+    let entries = fetch_finished_group(&mut tx, channel, queue, teamsize).await?;
+    let members: Vec<RunMember> = entries.iter().map(FinishedEntry::run_member).collect();
+    let run_id = record_run(&mut tx, channel, queue, &members, true, teamsize).await?;
+    attach_run(&mut tx, snapshot, run_id).await?;
     for entry in entries {
         sqlx::query!(
-            "DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND user_id = $2",
-            channel.as_str(), entry.user_id
+            "DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND user_id = $3",
+            channel.as_str(), queue, entry.user_id
         ).fetch_all(&mut *tx).await?;
     }
     sqlx::query!(
         "UPDATE krapbott_v2.queue
          SET position = position + 10000
-         WHERE channel_id = $1;",
-        channel.as_str()
+         WHERE channel_id = $1 AND queue_name = $2;",
+        channel.as_str(), queue
     ).execute(&mut *tx).await?;
 
-    // Step 2: Randomly assign new sequential positions
-    sqlx::query!(
-        "WITH shuffled AS (
-            SELECT position, user_id, bungie_name, 
-                   ROW_NUMBER() OVER () AS new_position
-            FROM (SELECT * FROM krapbott_v2.queue WHERE channel_id = $1 ORDER BY RANDOM())
-        )
-        UPDATE krapbott_v2.queue
-        SET position = (SELECT new_position FROM shuffled WHERE shuffled.position = krapbott_v2.queue.position)
-        WHERE channel_id = $1;",
-        channel.as_str()
-    ).execute(&mut *tx).await?;
+    // Step 2: Weighted draw, the order becomes the new positions
+    let entrants = fetch_raffle_entrants(&mut tx, channel, queue).await?;
+    let order = weighted_order(&entrants, policy);
+    sqlx::query(
+        "UPDATE krapbott_v2.queue q
+         SET position = o.new_position
+         FROM UNNEST($3::text[]) WITH ORDINALITY AS o(user_id, new_position)
+         WHERE q.channel_id = $1 AND q.queue_name = $2 AND q.user_id = o.user_id",
+    ).bind(channel.as_str()).bind(queue).bind(&order).execute(&mut *tx).await?;
+
+    let split = (teamsize as usize).min(order.len());
+    let (winners, losers) = order.split_at(split);
+    attach_draw(&mut tx, snapshot, channel, queue, &order).await?;
+    record_draw(&mut tx, channel, queue, winners, losers).await?;
 
     let next_group = sqlx::query!(
-        "SELECT display_name, bungie_name FROM krapbott_v2.queue WHERE channel_id = $1 ORDER BY position ASC LIMIT $2",
-        channel.as_str(), teamsize
+        "SELECT display_name, bungie_name FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 ORDER BY position ASC LIMIT $3",
+        channel.as_str(), queue, teamsize
     ).fetch_all(&mut *tx).await?;
-    
 
+    seal_snapshot(&mut tx, snapshot).await?;
     tx.commit().await?;
     let selected_team = next_group.iter().map(|q| format!("{}", q.display_name)).collect::<Vec<String>>().join(", ");
         // Announce the random selection
     Ok(Replies::raffle_won(&selected_team))
 }
 
-pub async fn next_handler(channel: &ChannelId, pool: &PgPool, teamsize: i64) -> BotResult<String> {
-    let mut tx = pool.begin().await?;
+pub async fn next_handler(channel: &ChannelId, queue: &str, pool: &PgPool, teamsize: i64, balance: &GroupBalance) -> BotResult<String> {
+    let mut tx = begin_queue_tx(pool, channel).await?;
+    let snapshot = snapshot_queue(&mut tx, channel, queue, "next").await?;
 
     // Step 1: Fetch current group
-    let queue_entries = sqlx::query!(
-        "SELECT user_id, bungie_name, priority_runs_left, locked_first 
-         FROM krapbott_v2.queue 
-         WHERE channel_id = $1 
-         ORDER BY position ASC 
-         LIMIT $2",
-        channel.as_str(), teamsize
-    ).fetch_all(&mut *tx).await?;
+    let queue_entries = fetch_finished_group(&mut tx, channel, queue, teamsize).await?;
+
+    // Step 2: Remember who played
+    let members: Vec<RunMember> = queue_entries.iter().map(FinishedEntry::run_member).collect();
+    let run_id = record_run(&mut tx, channel, queue, &members, false, teamsize).await?;
+    attach_run(&mut tx, snapshot, run_id).await?;
 
     for entry in &queue_entries {
         match (entry.locked_first.unwrap_or(false), entry.priority_runs_left.unwrap_or(0)) {
@@ -247,68 +455,62 @@ pub async fn next_handler(channel: &ChannelId, pool: &PgPool, teamsize: i64) ->
                 if new_count == 0 {
                     // Priority user done
                     sqlx::query!(
-                        "DELETE FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2",
-                        entry.user_id, channel.as_str()
+                        "DELETE FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3",
+                        entry.user_id, channel.as_str(), queue
                     ).execute(&mut *tx).await?;
                 } else {
                     // Decrement runs
                     sqlx::query!(
-                        "UPDATE krapbott_v2.queue SET priority_runs_left = $1 WHERE user_id = $2 AND channel_id = $3",
-                        new_count, entry.user_id, channel.as_str()
+                        "UPDATE krapbott_v2.queue SET priority_runs_left = $1 WHERE user_id = $2 AND channel_id = $3 AND queue_name = $4",
+                        new_count, entry.user_id, channel.as_str(), queue
                     ).execute(&mut *tx).await?;
                 }
             }
             _ => {
                 // Not a prio user — remove immediately
                 sqlx::query!(
-                    "DELETE FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2",
-                    entry.user_id, channel.as_str()
+                    "DELETE FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3",
+                    entry.user_id, channel.as_str(), queue
                 ).execute(&mut *tx).await?;
             }
         }
     }
 
+    // Party se nesmí rozdělit mezi dvě skupiny, skupiny drží limity tagů.
+    // Odehraná skupina je pryč, prio hráči se zbývajícími runy zůstávají připnutí nahoře.
+    pack_groups(&mut tx, channel, queue, 0, teamsize as usize, balance).await?;
+
     // Step 3: Lock next group of priority users (if any)
     sqlx::query!(
         "UPDATE krapbott_v2.queue SET locked_first = TRUE
-         WHERE channel_id = $1 AND group_priority = 1 AND locked_first = FALSE 
+         WHERE channel_id = $1 AND queue_name = $2 AND group_priority = 1 AND locked_first = FALSE 
          AND user_id IN (
              SELECT user_id FROM krapbott_v2.queue 
-             WHERE channel_id = $1 
+             WHERE channel_id = $1 AND queue_name = $2 
              ORDER BY position ASC 
-             LIMIT $2
+             LIMIT $3
          )",
-        channel.as_str(), teamsize
+        channel.as_str(), queue, teamsize
     ).execute(&mut *tx).await?;
 
     // Step 4: Get the new top of the queue for response
     let remaining_queue = sqlx::query!(
-        "SELECT display_name, bungie_name FROM krapbott_v2.queue 
-         WHERE channel_id = $1 
+        "SELECT display_name, bungie_name, tags FROM krapbott_v2.queue 
+         WHERE channel_id = $1 AND queue_name = $2 
          ORDER BY position ASC 
-         LIMIT $2",
-        channel.as_str(), teamsize
+         LIMIT $3",
+        channel.as_str(), queue, teamsize
     ).fetch_all(&mut *tx).await?;
 
     let result: Vec<_> = remaining_queue
         .into_iter()
-        .map(|row| format!("@{} ({})", row.display_name, row.bungie_name))
+        .map(|row| format!("@{} ({}){}", row.display_name, row.bungie_name, format_tags(&row.tags)))
         .collect();
 
     // Step 5: Recalculate positions
-    let rows = sqlx::query!(
-        "SELECT position, user_id FROM krapbott_v2.queue WHERE channel_id = $1 ORDER BY position ASC",
-        channel.as_str()
-    ).fetch_all(&mut *tx).await?;
-
-    for (index, row) in rows.iter().enumerate() {
-        let new_pos = index as i32 + 1;
-    sqlx::query!(
-            "UPDATE krapbott_v2.queue SET position = $1 WHERE channel_id = $2 AND user_id = $3",
-            new_pos, channel.as_str(), row.user_id
-        ).execute(&mut *tx).await?;
-    }
+    renumber_queue(&mut tx, channel, queue, &[]).await?;
 
+    seal_snapshot(&mut tx, snapshot).await?;
     tx.commit().await?;
 
     Ok(if result.is_empty() {
@@ -318,15 +520,13 @@ pub async fn next_handler(channel: &ChannelId, pool: &PgPool, teamsize: i64) ->
     })
 }
 
-pub async fn toggle_queue(pool: &PgPool, state: &AppState, caller: &ChannelId, open: bool) -> BotResult<String> {
-    let owner = resolve_queue_owner(state, caller).await?;
-
+pub async fn toggle_queue(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str, open: bool) -> BotResult<String> {
     {
         let mut cfg = state.config.write().await;
-        let owner_cfg = cfg.channels.get_mut(&owner).ok_or(BotError::ConfigMissing(owner.clone()))?;
+        let owner_cfg = cfg.channels.get_mut(owner).ok_or(BotError::ConfigMissing(owner.clone()))?;
 
-        owner_cfg.open = open;
-        save_channel_config(pool, &owner, &cfg).await?;
+        owner_cfg.queue_mut(queue).ok_or_else(|| BotError::Chat(Replies::queue_unknown(queue)))?.open = open;
+        save_channel_config(pool, owner, &cfg).await?;
     }
 
     Ok(if open {
@@ -340,19 +540,15 @@ pub async fn run_next(
     pool: &PgPool,
     state: Arc<AppState>,
     owner: &ChannelId,
+    queue: &str,
 ) -> BotResult<String> {
-    let (teamsize, random_queue) = {
-        let cfg = state.config.read().await;
-        let c = cfg
-            .get_channel_config(owner)
-            .ok_or(BotError::ConfigMissing(owner.clone()))?;
-        (c.teamsize as i64, c.random_queue)
-    };
+    let settings = queue_settings(&state, owner, queue).await?;
+    let teamsize = settings.teamsize as i64;
 
-    let result = if random_queue {
-        randomize_queue(owner, pool, teamsize).await?
+    let result = if settings.random_queue {
+        randomize_queue(owner, queue, pool, teamsize, &settings.raffle).await?
     } else {
-        next_handler(owner, pool, teamsize).await?
+        next_handler(owner, queue, pool, teamsize, &settings.balance).await?
     };
 
     {
@@ -364,84 +560,158 @@ pub async fn run_next(
 
     
     &state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone()});
-    Ok(result)
+
+    // Kontrola, jestli je nová skupina ve hře, offline hráči jdou případně dozadu ještě před ready-checkem
+    let result = match check_called_group(pool, &state, owner, queue).await {
+        Ok(Some(presence)) => format!("{result} {presence}"),
+        Ok(None) => result,
+        Err(e) => {
+            warn!("Presence check for {} ({}) failed: {}", owner.as_str(), queue, e);
+            result
+        }
+    };
+
+    // Volitelný ready-check nové skupiny
+    Ok(match start_ready_check(pool, state.clone(), owner, queue).await? {
+        Some(prompt) => format!("{result} {prompt}"),
+        None => result,
+    })
 }
 
-pub async fn remove_from_queue(pool: &PgPool, owner: &ChannelId, user_id: &UserId, state: Arc<AppState>) -> BotResult<()> {
-    let position = sqlx::query_scalar!(
-        r#"
-        SELECT position
-        FROM krapbott_v2.queue
-        WHERE user_id = $1 AND channel_id = $2
-        "#,
-        user_id.as_str(),
-        owner.as_str()
-    )
-    .fetch_optional(pool)
-    .await?;
+/// Vrátí poslední změnu fronty (`!undo` / tlačítko v docku), bez `queue` poslední změnu kanálu
+pub async fn undo_last_change(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: Option<&str>) -> BotResult<String> {
+    let restored = match restore_last_snapshot(pool, owner, queue).await? {
+        UndoOutcome::Restored(restored) => restored,
+        UndoOutcome::Nothing => return Ok(Replies::undo_empty()),
+    };
+
+    // Vrácený `!next` / losování se nepočítá do runů dne, smazaná fronta se vrátí do konfigurace
+    let settings = restored.queue_settings.as_deref().map(serde_json::from_str::<QueueSettings>).transpose()?;
+    if restored.run_counted || settings.is_some() {
+        let mut cfg = state.config.write().await;
+        let c = cfg.get_channel_config_mut(owner.to_owned());
+        if restored.run_counted {
+            c.runs = c.runs.saturating_sub(1);
+        }
+        if let Some(settings) = settings {
+            c.queues.entry(restored.queue_name.clone()).or_insert(settings);
+        }
+        save_channel_config(pool, owner, &cfg).await?;
+    }
 
-    let Some(position) = position else {
+    // Obnovená skupina už neodpovídá běžícímu ready-checku
+    cancel_ready_check(state, owner, &restored.queue_name).await;
+    let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone() });
+
+    Ok(Replies::undo_done(&restored.action, &restored.queue_name))
+}
+
+pub async fn remove_from_queue(pool: &PgPool, owner: &ChannelId, queue: &str, user_id: &UserId, state: Arc<AppState>) -> BotResult<()> {
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    if !user_exists_in_queue(&mut *tx, user_id, owner, queue).await? {
         return Ok(()); // user not in queue
-    };
+    }
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "remove").await?;
 
     // Delete user
     let res = sqlx::query!(
         r#"
         DELETE FROM krapbott_v2.queue
-        WHERE user_id = $1 AND channel_id = $2
+        WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3
         "#,
         user_id.as_str(),
-        owner.as_str()
+        owner.as_str(),
+        queue
     )
-    .execute(pool)
+    .execute(&mut *tx)
     .await?;
 
     tracing::info!("Deleted rows: {}", res.rows_affected());
 
     // Shift positions
-    sqlx::query!(
-        r#"
-        UPDATE krapbott_v2.queue
-        SET position = position - 1
-        WHERE channel_id = $1 AND position > $2
-        "#,
-        owner.as_str(),
-        position
-    )
-    .execute(pool)
-    .await?;
+    renumber_queue(&mut tx, owner, queue, &[]).await?;
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
 
     // Notify OBS
     let _ = &state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone() });
     Ok(())
 }
 
-pub async fn reorder_queue(pool: &PgPool, owner: &ChannelId, order: Vec<UserId>) -> BotResult<()> {
-    let mut tx = pool.begin().await?;
+/// Posune hráče na začátek druhé skupiny, s `runs` dostane i prio runy.
+/// Vrací false, pokud ve frontě není.
+pub async fn prioritize(pool: &PgPool, owner: &ChannelId, queue: &str, user_id: &UserId, runs: Option<i32>, teamsize: i32) -> BotResult<bool> {
+    let second_group = teamsize + 1;
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    if !user_exists_in_queue(&mut *tx, user_id, owner, queue).await? {
+        return Ok(false);
+    }
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "prio").await?;
 
     sqlx::query!(
-        "UPDATE krapbott_v2.queue SET position = -position WHERE channel_id = $1",
-        owner.as_str()
+        r#"UPDATE krapbott_v2.queue SET position = position + 10000 WHERE channel_id = $1 AND queue_name = $2 AND position >= $3"#,
+        owner.as_str(), queue, second_group
     ).execute(&mut *tx).await?;
 
-    for (index, user_id) in order.into_iter().enumerate() {
-        let new_pos = (index + 1) as i32; // 
-        
+    if let Some(runs) = runs {
         sqlx::query!(
-            "UPDATE krapbott_v2.queue 
-             SET position = $1 
-             WHERE channel_id = $2 AND user_id = $3",
-            new_pos,
+            r#"
+            UPDATE krapbott_v2.queue
+            SET position = $1,
+                group_priority = 1,
+                priority_runs_left = $2,
+                locked_first = FALSE
+            WHERE user_id = $3 AND channel_id = $4 AND queue_name = $5
+            "#,
+            second_group,
+            runs,
+            user_id.as_str(),
             owner.as_str(),
-            user_id.as_str()
+            queue
+        )
+        .execute(&mut *tx)
+        .await?;
+    } else {
+        sqlx::query!(
+            r#"UPDATE krapbott_v2.queue SET position = $1 WHERE user_id = $2 AND channel_id = $3 AND queue_name = $4"#,
+            second_group, user_id.as_str(), owner.as_str(), queue
         ).execute(&mut *tx).await?;
     }
 
+    renumber_queue(&mut tx, owner, queue, &[]).await?;
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn reorder_queue(pool: &PgPool, owner: &ChannelId, queue: &str, order: Vec<UserId>) -> BotResult<()> {
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "reorder").await?;
+
+    sqlx::query!(
+        "UPDATE krapbott_v2.queue SET position = -position WHERE channel_id = $1 AND queue_name = $2",
+        owner.as_str(), queue
+    ).execute(&mut *tx).await?;
+
+    // Kdo v novém pořadí chybí, zůstane za ostatními ve svém původním pořadí
+    let order: Vec<String> = order.iter().map(|u| u.as_str().to_string()).collect();
+    sqlx::query(
+        "UPDATE krapbott_v2.queue q
+         SET position = o.new_position
+         FROM (
+             SELECT user_id, ROW_NUMBER() OVER (ORDER BY COALESCE(array_position($3::text[], user_id), 2147483647), position DESC)::INT AS new_position
+             FROM krapbott_v2.queue
+             WHERE channel_id = $1 AND queue_name = $2
+         ) o
+         WHERE q.channel_id = $1 AND q.queue_name = $2 AND q.user_id = o.user_id",
+    ).bind(owner.as_str()).bind(queue).bind(&order).execute(&mut *tx).await?;
+
+    seal_snapshot(&mut tx, snapshot).await?;
     tx.commit().await?;
     Ok(())
 }
 
-pub async fn set_queue_open(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, open: bool) -> BotResult<()> {
+pub async fn set_queue_open(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, queue: &str, open: bool) -> BotResult<()> {
     {
         let mut cfg = state.config.write().await;
 
@@ -450,7 +720,7 @@ pub async fn set_queue_open(pool: &PgPool, state: Arc<AppState>, owner: &Channel
             .get_mut(owner)
             .ok_or(BotError::ConfigMissing(owner.clone()))?;
 
-        channel_cfg.open = open;
+        channel_cfg.queue_mut(queue).ok_or_else(|| BotError::Chat(Replies::queue_unknown(queue)))?.open = open;
         save_channel_config(pool, owner, &cfg).await?;
 
     }
@@ -458,7 +728,7 @@ pub async fn set_queue_open(pool: &PgPool, state: Arc<AppState>, owner: &Channel
     Ok(())
 }
 
-pub async fn set_queue_len(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, len: usize) -> BotResult<()> {
+pub async fn set_queue_len(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, queue: &str, len: usize) -> BotResult<()> {
     {
         let mut cfg = state.config.write().await;
 
@@ -467,7 +737,7 @@ pub async fn set_queue_len(pool: &PgPool, state: Arc<AppState>, owner: &ChannelI
             .get_mut(owner)
             .ok_or(BotError::ConfigMissing(owner.clone()))?;
 
-        channel_cfg.size = len;
+        channel_cfg.queue_mut(queue).ok_or_else(|| BotError::Chat(Replies::queue_unknown(queue)))?.size = len;
         save_channel_config(pool, owner, &cfg).await?;
 
     }
@@ -475,7 +745,10 @@ pub async fn set_queue_len(pool: &PgPool, state: Arc<AppState>, owner: &ChannelI
     Ok(())
 }
 
-pub async fn set_queue_size(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, size: usize) -> BotResult<()> {
+pub async fn set_queue_size(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, queue: &str, size: usize) -> BotResult<()> {
+    if size == 0 {
+        return Err(BotError::Chat("Usage: !size [queue] <n>, n must be at least 1".to_string()));
+    }
     {
         let mut cfg = state.config.write().await;
 
@@ -484,7 +757,7 @@ pub async fn set_queue_size(pool: &PgPool, state: Arc<AppState>, owner: &Channel
             .get_mut(owner)
             .ok_or(BotError::ConfigMissing(owner.clone()))?;
 
-        channel_cfg.teamsize = size;
+        channel_cfg.queue_mut(queue).ok_or_else(|| BotError::Chat(Replies::queue_unknown(queue)))?.teamsize = size;
         save_channel_config(pool, owner, &cfg).await?;
 
     }
@@ -505,4 +778,42 @@ pub async fn reset_queue_runs(pool: &PgPool, state: Arc<AppState>, owner: &Chann
     let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.to_owned() });
 
     Ok(())
-}
\ No newline at end of file
+}
+/// Doplní odhad čekání do položek fronty pro OBS / veřejnou stránku
+pub async fn attach_queue_eta(pool: &PgPool, owner: &ChannelId, queue: &str, entries: &mut [ObsQueueEntry], teamsize: usize) -> BotResult<()> {
+    let pace = fetch_run_pace(pool, owner, queue).await?;
+    for (index, entry) in entries.iter_mut().enumerate() {
+        entry.eta_minutes = pace.eta_seconds(index as i64 + 1, teamsize as i64).map(|s| (s + 59) / 60);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::chat_event::chat_event::Platform;
+
+    fn config() -> ChannelConfig {
+        let mut cfg = ChannelConfig::new(ChannelId::new(Platform::Twitch, "krapmatt"));
+        cfg.queues.insert("raid".to_string(), QueueSettings::default());
+        cfg
+    }
+
+    #[test]
+    fn unknown_explicit_queue_is_rejected() {
+        let cfg = config();
+        assert_eq!(split_queue_name(&cfg, "RAID"), Ok(("raid".to_string(), "")));
+        assert_eq!(split_queue_name(&cfg, "raid: @name"), Ok(("raid".to_string(), "@name")));
+        assert_eq!(split_queue_name(&cfg, "@name Name#1234"), Ok((MAIN_QUEUE.to_string(), "@name Name#1234")));
+        assert!(split_queue_name(&cfg, "raidd: @name").is_err());
+    }
+
+    #[test]
+    fn join_picks_the_queue_before_the_bungie_name() {
+        let cfg = config();
+        assert_eq!(split_queue_name(&cfg, "raid Lord#1234"), Ok(("raid".to_string(), "Lord#1234")));
+        assert_eq!(split_queue_name(&cfg, "raid: Lord#1234"), Ok(("raid".to_string(), "Lord#1234")));
+        assert_eq!(split_queue_name(&cfg, "main: raid Lord#1234"), Ok((MAIN_QUEUE.to_string(), "raid Lord#1234")));
+        assert_eq!(split_queue_name(&cfg, "raid titan"), Ok(("raid".to_string(), "titan")));
+    }
+}
@@ -1,2 +1,11 @@
 pub mod commands;
-pub mod logic;
\ No newline at end of file
+pub mod export;
+pub mod logic;
+pub mod party;
+pub mod presence;
+pub mod raffle;
+pub mod ready;
+pub mod requirements;
+pub mod rewards;
+pub mod subs;
+pub mod tags;
\ No newline at end of file
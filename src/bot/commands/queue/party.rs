@@ -0,0 +1,98 @@
+use std::{collections::HashMap, sync::Arc, time::{Duration, Instant}};
+
+use tokio::sync::Mutex;
+
+use crate::bot::{chat_event::chat_event::Platform, commands::{commands::{CommandT, FnCommand}, queue::logic::{queue_settings, resolve_queue_owner}}, db::{ChannelId, UserId, party::add_party_member}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::AppState, web::sse::SseEvent};
+
+/// Jak dlouho má pozvaný čas na `!accept`
+const PARTY_INVITE_TTL: Duration = Duration::from_secs(120);
+
+/// Pozvánka do party čekající na `!accept`
+pub struct PartyInvite {
+    /// user_id zakladatele, stejné jako `party_id` ve frontě
+    party_id: String,
+    leader: String,
+    queue: String,
+    created: Instant,
+}
+
+/// Pozvánky všech kanálů, klíčem je (vlastník fronty, platforma a login pozvaného).
+/// Sdílená fronta spojuje Twitch i Kick, stejný login na druhé platformě je jiný člověk.
+pub type PartyInvites = Mutex<HashMap<(ChannelId, Platform, String), PartyInvite>>;
+
+pub async fn invite_to_party(state: &AppState, owner: &ChannelId, queue: &str, leader_id: &UserId, leader: &str, invitees: &[String]) {
+    let mut invites = state.runtime.party_invites.lock().await;
+    invites.retain(|_, invite| invite.created.elapsed() < PARTY_INVITE_TTL);
+    for login in invitees {
+        invites.insert(
+            (owner.clone(), leader_id.platform(), login.clone()),
+            PartyInvite { party_id: leader_id.as_str().to_string(), leader: leader.to_string(), queue: queue.to_string(), created: Instant::now() },
+        );
+    }
+}
+
+async fn take_invite(state: &AppState, owner: &ChannelId, platform: Platform, login: &str) -> Option<PartyInvite> {
+    let invite = state.runtime.party_invites.lock().await.remove(&(owner.clone(), platform, login.to_lowercase()))?;
+    (invite.created.elapsed() < PARTY_INVITE_TTL).then_some(invite)
+}
+
+pub fn party_accept_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let Some(user) = &event.user else { return Ok(()); };
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+
+                let Some(invite) = take_invite(&state, &owner, event.platform, &user.name.login).await else {
+                    client.send_message(&caller, &Replies::party_no_invite(&user.name.display)).await?;
+                    return Ok(());
+                };
+
+                // Každý člen party prochází stejnými kontrolami jako !join
+                let provided = event.message.split_whitespace().skip(1).collect::<Vec<_>>().join(" ");
+                let entry = match state.resolve_joiner(&pool, user, &owner, &invite.queue, &provided).await? {
+                    Ok(entry) => entry,
+                    Err(rejection) => {
+                        client.send_message(&caller, &rejection).await?;
+                        return Ok(());
+                    }
+                };
+
+                let settings = queue_settings(&state, &owner, &invite.queue).await?;
+                let reply = add_party_member(&pool, &owner, &invite.queue, &entry, &invite.party_id, &invite.leader, &settings).await?;
+
+                let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Accept a party invite and join the queue with your party",
+        "!accept [BungieName#1234]",
+        "accept",
+        PermissionLevel::Everyone,
+    ))
+}
+
+pub fn party_decline_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, _pool, state, client| {
+            Box::pin(async move {
+                let Some(user) = &event.user else { return Ok(()); };
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+
+                let reply = match take_invite(&state, &owner, event.platform, &user.name.login).await {
+                    Some(invite) => Replies::party_declined(&user.name.display, &invite.leader),
+                    None => Replies::party_no_invite(&user.name.display),
+                };
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Decline a party invite",
+        "!decline",
+        "decline",
+        PermissionLevel::Everyone,
+    ))
+}
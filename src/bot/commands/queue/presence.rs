@@ -0,0 +1,226 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::{Duration, Instant}};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{api::{api::get_profile_presence, bungie::{BungieError, MemberShip}}, bot::{commands::{commands::{BotResult, CommandT, FnCommand}, queue::logic::{is_valid_bungie_name, queue_settings, resolve_named_queue}}, db::{ChannelId, UserId, bungie::load_membership, config::save_channel_config, queue::{fetch_group_members, move_users_to_back}}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::{AppState, BotError, ObsQueueEntry}, web::sse::SseEvent}};
+
+/// Jak dlouho platí zjištěný stav skupiny pro `!list` a dock
+const PRESENCE_TTL: Duration = Duration::from_secs(2 * 60);
+
+/// Kontrola, jestli je volaná skupina ve hře, ve výchozím stavu vypnutá
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct PresenceCheck {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Offline hráči po `!next` jdou na konec fronty
+    #[serde(default)]
+    pub skip_offline: bool,
+    /// Bungie jméno streamera, hráči v jeho fireteamu se označí
+    #[serde(default)]
+    pub fireteam: Option<String>,
+}
+
+impl PresenceCheck {
+    pub fn summary(&self) -> String {
+        if !self.enabled {
+            return "off".to_string();
+        }
+        let mut summary = if self.skip_offline {
+            "on, offline players are moved to the back".to_string()
+        } else {
+            "on, offline players are only flagged".to_string()
+        };
+        if let Some(fireteam) = &self.fireteam {
+            summary.push_str(&format!(", fireteam of {fireteam}"));
+        }
+        summary
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    Offline,
+    Online,
+    InFireteam,
+    /// Soukromý profil, chybí membership nebo Bungie neodpovídá
+    Unknown,
+}
+
+impl Presence {
+    pub fn icon(self) -> &'static str {
+        match self {
+            Presence::Offline => "🔴",
+            Presence::Online => "🟢",
+            Presence::InFireteam => "🎮",
+            Presence::Unknown => "❔",
+        }
+    }
+}
+
+/// Poslední zjištěný stav skupiny podle user_id, klíčem je (vlastník fronty, název fronty)
+pub type PresenceCache = DashMap<(ChannelId, String), (Instant, HashMap<String, Presence>)>;
+
+async fn member_membership(pool: &PgPool, user_id: &str) -> Option<MemberShip> {
+    let user_id = UserId::from_str(user_id).ok()?;
+    load_membership(pool, user_id.platform(), user_id.platform_user_id()).await
+}
+
+/// Zjistí online stav aktuální skupiny a uloží ho do cache
+pub async fn refresh_presence(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str) -> BotResult<HashMap<String, Presence>> {
+    let settings = queue_settings(state, owner, queue).await?;
+    let members = fetch_group_members(pool, owner, queue, settings.teamsize as i64).await?;
+
+    let streamer = match &settings.presence.fireteam {
+        Some(name) => match state.bungie.find_membership(name).await {
+            Ok(membership) => Some(membership.id),
+            Err(err) => {
+                warn!("Fireteam owner {} lookup failed: {}", name, err);
+                None
+            }
+        },
+        None => None,
+    };
+
+    let checks = members.iter().map(|(user_id, _)| {
+        let streamer = streamer.as_deref();
+        async move {
+            let Some(membership) = member_membership(pool, user_id).await else {
+                return (user_id.clone(), Presence::Unknown);
+            };
+            let presence = match get_profile_presence(&state.bungie, &membership).await {
+                Ok(p) if !p.online => Presence::Offline,
+                Ok(p) if streamer.is_some_and(|s| s == membership.id || p.party.iter().any(|m| m == s)) => Presence::InFireteam,
+                Ok(_) => Presence::Online,
+                Err(BungieError::Private) => Presence::Unknown,
+                Err(err) => {
+                    warn!("Presence check for {} failed: {}", user_id, err);
+                    Presence::Unknown
+                }
+            };
+            (user_id.clone(), presence)
+        }
+    });
+    let presence: HashMap<String, Presence> = futures::future::join_all(checks).await.into_iter().collect();
+
+    state.runtime.presence.insert((owner.clone(), queue.to_string()), (Instant::now(), presence.clone()));
+    let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone() });
+    Ok(presence)
+}
+
+/// Stav skupiny pro `!list`, zastaralý se zjistí znovu. `None` když je kontrola vypnutá.
+pub async fn group_presence(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str) -> BotResult<Option<HashMap<String, Presence>>> {
+    if !queue_settings(state, owner, queue).await?.presence.enabled {
+        return Ok(None);
+    }
+    let cached = state
+        .runtime
+        .presence
+        .get(&(owner.clone(), queue.to_string()))
+        .filter(|entry| entry.0.elapsed() < PRESENCE_TTL)
+        .map(|entry| entry.1.clone());
+    match cached {
+        Some(presence) => Ok(Some(presence)),
+        None => refresh_presence(pool, state, owner, queue).await.map(Some),
+    }
+}
+
+/// Kontrola nové skupiny po `!next`. Offline hráče případně přesune dozadu, vrací text pro chat.
+pub async fn check_called_group(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str) -> BotResult<Option<String>> {
+    let settings = queue_settings(state, owner, queue).await?;
+    if !settings.presence.enabled {
+        return Ok(None);
+    }
+
+    let members = fetch_group_members(pool, owner, queue, settings.teamsize as i64).await?;
+    let presence = refresh_presence(pool, state, owner, queue).await?;
+    let offline: Vec<(String, String)> = members
+        .into_iter()
+        .filter(|(id, _)| presence.get(id) == Some(&Presence::Offline))
+        .collect();
+    if offline.is_empty() {
+        return Ok(None);
+    }
+
+    let names = offline.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>().join(", ");
+    if !settings.presence.skip_offline {
+        return Ok(Some(Replies::presence_offline(&names)));
+    }
+
+    let ids: Vec<String> = offline.into_iter().map(|(id, _)| id).collect();
+    move_users_to_back(pool, owner, queue, &ids, "offline skip").await?;
+    // Náhradníci dostanou vlastní stav
+    refresh_presence(pool, state, owner, queue).await?;
+    Ok(Some(Replies::presence_skipped(&names)))
+}
+
+/// Doplní `presence` do položek živé skupiny z poslední kontroly
+pub fn attach_presence(state: &AppState, owner: &ChannelId, queue: &str, entries: &mut [ObsQueueEntry]) {
+    let Some(cached) = state.runtime.presence.get(&(owner.clone(), queue.to_string())) else {
+        return;
+    };
+    if cached.0.elapsed() >= PRESENCE_TTL {
+        return;
+    }
+    for entry in entries.iter_mut() {
+        entry.presence = cached.1.get(&entry.user_id).copied();
+    }
+}
+
+pub fn presence_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let (owner, queue, args) = resolve_named_queue(&state, &caller, &event.message).await?;
+                let args: Vec<&str> = args.split_whitespace().collect();
+                let usage = || BotError::Chat("Usage: !online [queue] [on|off] [skip|flag] [fireteam <BungieName#1234|off>]".to_string());
+
+                let mut cfg = state.config.write().await;
+                let policy = &mut cfg
+                    .get_channel_config_mut(owner.clone())
+                    .queue_mut(&queue)
+                    .ok_or_else(|| BotError::Chat(Replies::queue_unknown(&queue)))?
+                    .presence;
+
+                for (i, arg) in args.iter().enumerate() {
+                    match arg.to_lowercase().as_str() {
+                        "on" => policy.enabled = true,
+                        "off" => policy.enabled = false,
+                        "skip" => {
+                            policy.enabled = true;
+                            policy.skip_offline = true;
+                        }
+                        "flag" => policy.skip_offline = false,
+                        // Bungie jméno může obsahovat mezery, bere zbytek řádku
+                        "fireteam" => {
+                            let name = args[i + 1..].join(" ");
+                            policy.fireteam = match name.as_str() {
+                                "off" => None,
+                                name => Some(is_valid_bungie_name(name).ok_or_else(usage)?),
+                            };
+                            break;
+                        }
+                        _ => return Err(usage()),
+                    }
+                }
+
+                let reply = Replies::presence_policy(&policy.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change the in-game check for the called group",
+        "!online [queue] [on|off] [skip|flag] [fireteam <BungieName#1234|off>]",
+        "online",
+        PermissionLevel::Moderator,
+    ))
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::bot::{chat_event::chat_event::ChatUser, commands::commands::BotResult, db::{ChannelId, raffle::{RaffleEntrant, fetch_raffle_entrants_pool}}, permissions::permissions::PermissionLevel, state::def::ObsQueueEntry};
+
+/// Jak moc se zvýhodňují účastníci raffle. Každý začíná s váhou 1.0, bonusy se přičítají.
+/// Výchozí hodnoty jsou nulové, takže se chová jako obyčejné náhodné losování.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RafflePolicy {
+    #[serde(default)]
+    pub subscriber_bonus: f64,
+    #[serde(default)]
+    pub vip_bonus: f64,
+    /// Added per lost draw
+    #[serde(default)]
+    pub loss_bonus: f64,
+    /// Cap on how many lost draws count towards the weight
+    #[serde(default = "default_max_losses")]
+    pub max_losses: u32,
+}
+
+fn default_max_losses() -> u32 {
+    5
+}
+
+impl Default for RafflePolicy {
+    fn default() -> Self {
+        RafflePolicy {
+            subscriber_bonus: 0.0,
+            vip_bonus: 0.0,
+            loss_bonus: 0.0,
+            max_losses: default_max_losses(),
+        }
+    }
+}
+
+impl RafflePolicy {
+    pub fn weight(&self, entrant: &RaffleEntrant) -> f64 {
+        let status = if entrant.vip {
+            self.vip_bonus
+        } else if entrant.subscriber {
+            self.subscriber_bonus
+        } else {
+            0.0
+        };
+        let losses = entrant.losses.clamp(0, self.max_losses as i32) as f64;
+
+        (1.0 + status + losses * self.loss_bonus + entrant.bonus).max(0.01)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "sub +{}, vip +{}, per lost draw +{} (max {} draws)",
+            self.subscriber_bonus, self.vip_bonus, self.loss_bonus, self.max_losses
+        )
+    }
+}
+
+/// (subscriber, vip) podle badge a oprávnění v chatu. VIP bonus dostanou jen opravdoví VIP,
+/// sub bonus každý sub včetně modů a streamera.
+pub fn raffle_status(user: &ChatUser) -> (bool, bool) {
+    (user.subscriber, user.permission == PermissionLevel::Vip)
+}
+
+/// Bonus k váze z chatu, záporný, `inf` nebo `NaN` by losování rozbily
+pub fn parse_bonus(value: &str) -> Option<f64> {
+    value.parse::<f64>().ok().filter(|v| v.is_finite() && *v >= 0.0)
+}
+
+/// Vážené pořadí bez opakování (Efraimidis–Spirakis): klíč u^(1/w), seřazeno sestupně
+pub fn weighted_order(entrants: &[RaffleEntrant], policy: &RafflePolicy) -> Vec<String> {
+    let mut rng = rand::rng();
+    let mut keyed: Vec<(f64, &str)> = entrants
+        .iter()
+        .map(|e| {
+            let u: f64 = rng.random::<f64>().max(f64::MIN_POSITIVE);
+            (u.powf(1.0 / policy.weight(e)), e.user_id.as_str())
+        })
+        .collect();
+
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, id)| id.to_string()).collect()
+}
+
+/// Approximate chance (0.0 - 1.0) of each entrant being picked in the next draw.
+/// Entrants within the first `teamsize` positions were already drawn and are skipped.
+pub fn raffle_odds(entrants: &[RaffleEntrant], policy: &RafflePolicy, teamsize: usize) -> HashMap<String, f64> {
+    let waiting: Vec<&RaffleEntrant> = entrants.iter().skip(teamsize).collect();
+    let total: f64 = waiting.iter().map(|e| policy.weight(e)).sum();
+
+    waiting
+        .iter()
+        .map(|e| {
+            let odds = if waiting.len() <= teamsize {
+                1.0
+            } else {
+                (teamsize as f64 * policy.weight(e) / total).min(1.0)
+            };
+            (e.user_id.clone(), odds)
+        })
+        .collect()
+}
+
+/// Doplní `odds` do položek fronty pro OBS / veřejnou stránku
+pub async fn attach_raffle_odds(pool: &PgPool, owner: &ChannelId, queue: &str, entries: &mut [ObsQueueEntry], policy: &RafflePolicy, teamsize: usize) -> BotResult<()> {
+    let entrants = fetch_raffle_entrants_pool(pool, owner, queue).await?;
+    let odds = raffle_odds(&entrants, policy, teamsize);
+    for entry in entries.iter_mut() {
+        entry.odds = odds.get(&entry.user_id).copied();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::chat_event::chat_event::{DisplayName, Platform, UserIdentity};
+
+    fn entrant(user_id: &str, subscriber: bool, vip: bool, losses: i32, bonus: f64) -> RaffleEntrant {
+        RaffleEntrant { user_id: user_id.to_string(), subscriber, vip, losses, bonus }
+    }
+
+    fn policy() -> RafflePolicy {
+        RafflePolicy { subscriber_bonus: 1.0, vip_bonus: 2.0, loss_bonus: 0.5, max_losses: 4 }
+    }
+
+    #[test]
+    fn weight_adds_status_losses_and_bonus() {
+        let policy = policy();
+        assert_eq!(policy.weight(&entrant("a", false, false, 0, 0.0)), 1.0);
+        assert_eq!(policy.weight(&entrant("sub", true, false, 0, 0.0)), 2.0);
+        // VIP bonus se nesčítá se sub bonusem
+        assert_eq!(policy.weight(&entrant("vip", true, true, 0, 0.0)), 3.0);
+        assert_eq!(policy.weight(&entrant("loser", false, false, 2, 0.25)), 2.25);
+    }
+
+    #[test]
+    fn weight_caps_losses_and_never_drops_to_zero() {
+        let policy = policy();
+        assert_eq!(policy.weight(&entrant("a", false, false, 100, 0.0)), 3.0);
+        assert_eq!(policy.weight(&entrant("b", false, false, -3, 0.0)), 1.0);
+        assert_eq!(policy.weight(&entrant("c", false, false, 0, -5.0)), 0.01);
+    }
+
+    #[test]
+    fn weighted_order_keeps_everyone_once() {
+        let entrants: Vec<_> = ["a", "b", "c", "d"].iter().map(|id| entrant(id, false, false, 0, 0.0)).collect();
+        let mut order = weighted_order(&entrants, &policy());
+        order.sort();
+        assert_eq!(order, ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn weighted_order_favours_heavier_entrants() {
+        let entrants = vec![entrant("light", false, false, 0, -5.0), entrant("heavy", false, false, 0, 99.0)];
+        let heavy_first = (0..200).filter(|_| weighted_order(&entrants, &policy())[0] == "heavy").count();
+        assert!(heavy_first > 190, "heavy entrant won only {heavy_first} of 200 draws");
+    }
+
+    #[test]
+    fn raffle_odds_skip_the_drawn_group() {
+        let entrants = vec![
+            entrant("drawn", false, false, 0, 0.0),
+            entrant("a", false, false, 0, 0.0),
+            entrant("b", false, false, 0, 2.0),
+            entrant("c", false, false, 0, 0.0),
+        ];
+        let odds = raffle_odds(&entrants, &policy(), 1);
+        assert!(!odds.contains_key("drawn"));
+        assert_eq!(odds["a"], 0.2);
+        assert_eq!(odds["b"], 0.6);
+        assert_eq!(odds["c"], 0.2);
+    }
+
+    #[test]
+    fn raffle_odds_are_certain_when_everyone_fits() {
+        let entrants: Vec<_> = ["drawn1", "drawn2", "a", "b"].iter().map(|id| entrant(id, false, false, 0, 0.0)).collect();
+        let odds = raffle_odds(&entrants, &policy(), 2);
+        assert_eq!(odds.len(), 2);
+        assert!(odds.values().all(|&o| o == 1.0));
+    }
+
+    #[test]
+    fn bonuses_must_be_finite_and_not_negative() {
+        assert_eq!(parse_bonus("1.5"), Some(1.5));
+        assert_eq!(parse_bonus("-1.5"), None);
+        assert_eq!(parse_bonus("inf"), None);
+        assert_eq!(parse_bonus("NaN"), None);
+    }
+
+    #[test]
+    fn subscribed_mods_keep_the_sub_bonus_and_only_vips_get_the_vip_bonus() {
+        let user = |permission, subscriber| ChatUser {
+            identity: UserIdentity { platform: Platform::Twitch, platform_user_id: "1".to_string() },
+            name: DisplayName { login: "viewer".to_string(), display: "viewer".to_string() },
+            permission,
+            subscriber,
+        };
+        assert_eq!(raffle_status(&user(PermissionLevel::Vip, false)), (false, true));
+        assert_eq!(raffle_status(&user(PermissionLevel::Moderator, true)), (true, false));
+        assert_eq!(raffle_status(&user(PermissionLevel::Broadcaster, true)), (true, false));
+        assert_eq!(raffle_status(&user(PermissionLevel::Moderator, false)), (false, false));
+    }
+}
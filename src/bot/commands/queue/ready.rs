@@ -0,0 +1,303 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::{sync::Mutex, task::JoinHandle};
+use tracing::{error, warn};
+
+use crate::bot::{commands::{commands::BotResult, queue::logic::{queue_channels, queue_settings}}, db::{ChannelId, queue::{fetch_group_members, move_users_to_back, remove_users_from_queue}}, handler::handler::ChatClient, replies::Replies, state::def::{AppState, ObsQueueEntry}, web::sse::SseEvent};
+
+/// Co se stane s hráčem, který se v ready-checku neozval
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadyMiss {
+    /// Přesune se na konec fronty
+    #[default]
+    Back,
+    /// Vypadne z fronty úplně
+    Drop,
+}
+
+/// Ready-check po `!next`, ve výchozím stavu vypnutý
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReadyCheckPolicy {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+    #[serde(default)]
+    pub on_miss: ReadyMiss,
+    /// How many times replacements get pulled in before giving up
+    #[serde(default = "default_max_rounds")]
+    pub max_rounds: u32,
+}
+
+fn default_window_secs() -> u64 {
+    60
+}
+
+fn default_max_rounds() -> u32 {
+    3
+}
+
+impl Default for ReadyCheckPolicy {
+    fn default() -> Self {
+        ReadyCheckPolicy {
+            enabled: false,
+            window_secs: default_window_secs(),
+            on_miss: ReadyMiss::default(),
+            max_rounds: default_max_rounds(),
+        }
+    }
+}
+
+impl ReadyCheckPolicy {
+    pub fn summary(&self) -> String {
+        if !self.enabled {
+            return "off".to_string();
+        }
+        let miss = match self.on_miss {
+            ReadyMiss::Back => "moved to the back",
+            ReadyMiss::Drop => "dropped",
+        };
+        format!("{}s to type !ready, missing players are {}", self.window_secs, miss)
+    }
+}
+
+/// Probíhající ready-check jedné fronty
+pub struct ReadyCheck {
+    id: u64,
+    round: u32,
+    /// (user_id, display_name) ve stejném pořadí jako ve frontě
+    members: Vec<(String, String)>,
+    ready: HashSet<String>,
+    deadline: Instant,
+    timer: JoinHandle<()>,
+    /// Okno vypršelo a fronta se právě upravuje, pozdní `!ready` už se nepočítá
+    expiring: bool,
+}
+
+impl ReadyCheck {
+    fn pending(&self) -> Vec<&(String, String)> {
+        self.members.iter().filter(|(id, _)| !self.ready.contains(id)).collect()
+    }
+
+    pub fn seconds_left(&self) -> u64 {
+        self.deadline.saturating_duration_since(Instant::now()).as_secs()
+    }
+}
+
+/// Ready-checky všech kanálů, klíčem je (vlastník fronty, název fronty)
+pub type ReadyChecks = Mutex<HashMap<(ChannelId, String), ReadyCheck>>;
+
+static NEXT_CHECK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Spustí ready-check pro aktuální skupinu. Vrací text pro chat, pokud je check zapnutý.
+pub async fn start_ready_check(pool: &PgPool, state: Arc<AppState>, owner: &ChannelId, queue: &str) -> BotResult<Option<String>> {
+    let settings = queue_settings(&state, owner, queue).await?;
+    if !settings.ready_check.enabled {
+        return Ok(None);
+    }
+
+    let members = fetch_group_members(pool, owner, queue, settings.teamsize as i64).await?;
+    if members.is_empty() {
+        cancel_ready_check(&state, owner, queue).await;
+        return Ok(None);
+    }
+
+    let names = members.iter().map(|(_, name)| format!("@{name}")).collect::<Vec<_>>().join(", ");
+    let window_secs = settings.ready_check.window_secs;
+    let id = NEXT_CHECK_ID.fetch_add(1, Ordering::Relaxed);
+
+    // Jeden task na celý check, další kola (náhradníci) běží ve stejné smyčce
+    let timer = {
+        let (pool, state, owner, queue) = (pool.clone(), state.clone(), owner.clone(), queue.to_string());
+        tokio::spawn(async move {
+            let mut window = window_secs;
+            loop {
+                tokio::time::sleep(Duration::from_secs(window)).await;
+                match expire_ready_check(&pool, &state, &owner, &queue, id).await {
+                    Ok(Some(next)) => window = next,
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("Ready check for {} ({}) failed: {:?}", owner.as_str(), queue, e);
+                        break;
+                    }
+                }
+            }
+        })
+    };
+
+    let check = ReadyCheck {
+        id,
+        round: 1,
+        members,
+        ready: HashSet::new(),
+        deadline: Instant::now() + Duration::from_secs(window_secs),
+        timer,
+        expiring: false,
+    };
+
+    let previous = state.runtime.ready_checks.lock().await.insert((owner.clone(), queue.to_string()), check);
+    if let Some(previous) = previous {
+        previous.timer.abort();
+    }
+
+    let _ = state.sse_bus.send(SseEvent::ReadyCheckUpdated { channel: owner.clone(), queue: queue.to_string() });
+
+    Ok(Some(Replies::ready_check_started(&names, window_secs)))
+}
+
+pub async fn cancel_ready_check(state: &AppState, owner: &ChannelId, queue: &str) {
+    if let Some(check) = state.runtime.ready_checks.lock().await.remove(&(owner.clone(), queue.to_string())) {
+        check.timer.abort();
+        let _ = state.sse_bus.send(SseEvent::ReadyCheckUpdated { channel: owner.clone(), queue: queue.to_string() });
+    }
+}
+
+/// Potvrdí připravenost hráče v kterékoliv frontě vlastníka. `None` = uživatel žádný check nemá.
+pub async fn mark_ready(state: &AppState, owner: &ChannelId, user_id: &str) -> Option<String> {
+    let mut checks = state.runtime.ready_checks.lock().await;
+
+    let key = checks
+        .iter()
+        .find(|((o, _), c)| o == owner && c.members.iter().any(|(id, _)| id == user_id) && !c.ready.contains(user_id))
+        .map(|(k, _)| k.clone())?;
+
+    let check = checks.get_mut(&key)?;
+    let name = check.members.iter().find(|(id, _)| id == user_id).map(|(_, n)| n.clone()).unwrap_or_default();
+    if check.expiring {
+        return Some(Replies::ready_too_late(&name));
+    }
+    check.ready.insert(user_id.to_string());
+
+    let reply = if check.pending().is_empty() {
+        if let Some(done) = checks.remove(&key) {
+            done.timer.abort();
+        }
+        Replies::ready_all()
+    } else {
+        Replies::ready_confirmed(&name, check.ready.len(), check.members.len())
+    };
+    drop(checks);
+
+    let _ = state.sse_bus.send(SseEvent::ReadyCheckUpdated { channel: key.0, queue: key.1 });
+    Some(reply)
+}
+
+/// Hráči ve sdílené frontě můžou být na kterékoliv platformě, zpráva jde do všech jejích kanálů
+async fn announce(state: &AppState, owner: &ChannelId, message: &str) {
+    for channel in queue_channels(state, owner).await {
+        if let Err(e) = state.chat_client.send_message(&channel, message).await {
+            warn!("Ready check announcement to {} failed: {:?}", channel.as_str(), e);
+        }
+    }
+}
+
+/// Vyprší okno: neozvaní jdou dozadu / ven a do skupiny se dotáhnou další.
+/// Vrací délku dalšího kola, pokud se čeká na náhradníky.
+async fn expire_ready_check(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str, id: u64) -> BotResult<Option<u64>> {
+    let key = (owner.clone(), queue.to_string());
+    // Check zůstane v mapě, dokud se fronta neupraví, ať pozdní `!ready` dostane odpověď
+    let (round, members, ready) = {
+        let mut checks = state.runtime.ready_checks.lock().await;
+        match checks.get_mut(&key) {
+            Some(c) if c.id == id => {
+                c.expiring = true;
+                (c.round, c.members.clone(), c.ready.clone())
+            }
+            _ => return Ok(None),
+        }
+    };
+
+    let result = replace_missing(pool, state, owner, queue, round, &members, &ready).await;
+
+    let mut checks = state.runtime.ready_checks.lock().await;
+    // Mezitím mohl začít nový check (další !next) nebo ho někdo zrušil
+    let ours = checks.get(&key).is_some_and(|c| c.id == id);
+    let next_round = match result {
+        Ok(Some(next_round)) if ours => next_round,
+        other => {
+            if ours {
+                checks.remove(&key);
+            }
+            drop(checks);
+            let _ = state.sse_bus.send(SseEvent::ReadyCheckUpdated { channel: owner.clone(), queue: queue.to_string() });
+            return other.map(|_| None);
+        }
+    };
+
+    let window_secs = next_round.window_secs;
+    if let Some(check) = checks.get_mut(&key) {
+        check.round += 1;
+        check.members = next_round.members;
+        check.ready = next_round.ready;
+        check.deadline = Instant::now() + Duration::from_secs(window_secs);
+        check.expiring = false;
+    }
+    drop(checks);
+
+    announce(state, owner, &Replies::ready_check_started(&next_round.newcomers.join(", "), window_secs)).await;
+    let _ = state.sse_bus.send(SseEvent::ReadyCheckUpdated { channel: owner.clone(), queue: queue.to_string() });
+    Ok(Some(window_secs))
+}
+
+/// Skupina pro další kolo ready-checku
+struct NextRound {
+    members: Vec<(String, String)>,
+    ready: HashSet<String>,
+    newcomers: Vec<String>,
+    window_secs: u64,
+}
+
+/// Přesune / vyhodí neozvané hráče. `None` = není na koho čekat.
+async fn replace_missing(pool: &PgPool, state: &AppState, owner: &ChannelId, queue: &str, round: u32, members: &[(String, String)], ready: &HashSet<String>) -> BotResult<Option<NextRound>> {
+    let missing: Vec<&(String, String)> = members.iter().filter(|(id, _)| !ready.contains(id)).collect();
+    if missing.is_empty() {
+        return Ok(None);
+    }
+
+    let settings = queue_settings(state, owner, queue).await?;
+    let policy = settings.ready_check;
+    let missing_ids: Vec<String> = missing.iter().map(|(id, _)| id.clone()).collect();
+    let missing_names = missing.iter().map(|(_, n)| n.as_str()).collect::<Vec<_>>().join(", ");
+
+    let announcement = match policy.on_miss {
+        ReadyMiss::Back => {
+            move_users_to_back(pool, owner, queue, &missing_ids, "ready check").await?;
+            Replies::ready_missed_back(&missing_names)
+        }
+        ReadyMiss::Drop => {
+            remove_users_from_queue(pool, owner, queue, &missing_ids).await?;
+            Replies::ready_missed_dropped(&missing_names)
+        }
+    };
+    announce(state, owner, &announcement).await;
+    let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone() });
+
+    let group = fetch_group_members(pool, owner, queue, settings.teamsize as i64).await?;
+    let still_ready: HashSet<String> = group.iter().filter(|(id, _)| ready.contains(id)).map(|(id, _)| id.clone()).collect();
+    let newcomers: Vec<String> = group
+        .iter()
+        .filter(|(id, _)| !members.iter().any(|(m, _)| m == id))
+        .map(|(_, name)| format!("@{name}"))
+        .collect();
+
+    if newcomers.is_empty() || round >= policy.max_rounds {
+        return Ok(None);
+    }
+
+    Ok(Some(NextRound { members: group, ready: still_ready, newcomers, window_secs: policy.window_secs }))
+}
+
+/// Doplní `ready` do položek živé skupiny, vrací zbývající sekundy checku
+pub async fn attach_ready_state(state: &AppState, owner: &ChannelId, queue: &str, entries: &mut [ObsQueueEntry]) -> Option<u64> {
+    let checks = state.runtime.ready_checks.lock().await;
+    let check = checks.get(&(owner.clone(), queue.to_string()))?;
+    for entry in entries.iter_mut() {
+        if check.members.iter().any(|(id, _)| *id == entry.user_id) {
+            entry.ready = Some(check.ready.contains(&entry.user_id));
+        }
+    }
+    Some(check.seconds_left())
+}
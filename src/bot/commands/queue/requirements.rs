@@ -0,0 +1,293 @@
+use std::{collections::{BTreeMap, HashMap}, sync::Arc, time::{Duration, Instant}};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{api::{api::{get_activity_completions, get_users_clears}, bungie::MemberShip}, bot::{commands::{commands::{BotResult, CommandT, FnCommand}, queue::logic::resolve_queue_owner}, db::{ChannelId, config::save_channel_config}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::{AppState, BotError}}};
+
+/// Raidy pro požadavky: klíč v příkazu -> začátek názvu aktivity v manifestu (všechny obtížnosti)
+pub const RAIDS: &[(&str, &str)] = &[
+    ("lw", "Last Wish"),
+    ("gos", "Garden of Salvation"),
+    ("dsc", "Deep Stone Crypt"),
+    ("vog", "Vault of Glass"),
+    ("vow", "Vow of the Disciple"),
+    ("kf", "King's Fall"),
+    ("ron", "Root of Nightmares"),
+    ("ce", "Crota's End"),
+    ("se", "Salvation's Edge"),
+    ("dp", "The Desert Perpetual"),
+];
+
+/// Jak dlouho věříme načteným clearům jednoho účtu
+const CLEARS_CACHE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// Rozsah clearů, `None` = bez omezení
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClearRange {
+    #[serde(default)]
+    pub min: Option<u32>,
+    #[serde(default)]
+    pub max: Option<u32>,
+}
+
+impl ClearRange {
+    pub fn is_set(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// Rozsah, do kterého se dá vejít (min <= max)
+    fn is_valid(&self) -> bool {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => min <= max,
+            _ => true,
+        }
+    }
+
+    fn summary(&self, label: &str) -> String {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => format!("{min}-{max} {label}"),
+            (Some(min), None) => format!("{min}+ {label}"),
+            (None, Some(max)) => format!("max {max} {label}"),
+            (None, None) => format!("any {label}"),
+        }
+    }
+}
+
+/// Požadavky na join podle Bungie dat (experienced only, sherpa runy)
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct JoinRequirements {
+    /// Všechny raid cleary
+    #[serde(default)]
+    pub total: ClearRange,
+    /// Cleary konkrétního raidu, klíč z `RAIDS`
+    #[serde(default)]
+    pub raids: BTreeMap<String, ClearRange>,
+}
+
+impl JoinRequirements {
+    pub fn is_enabled(&self) -> bool {
+        self.total.is_set() || !self.raids.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if !self.is_enabled() {
+            return "none".to_string();
+        }
+        let mut parts = Vec::new();
+        if self.total.is_set() {
+            parts.push(self.total.summary("raid clears"));
+        }
+        for (raid, range) in &self.raids {
+            parts.push(range.summary(&format!("{} clears", raid.to_uppercase())));
+        }
+        parts.join(", ")
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct RaidClears {
+    total: Option<u32>,
+    /// Cleary podle klíče z `RAIDS`
+    raids: Option<Arc<HashMap<String, u32>>>,
+}
+
+/// Cleary podle membership id, sdílené pro všechny kanály
+pub type RaidClearsCache = DashMap<String, (Instant, RaidClears)>;
+
+/// Který požadavek hráč nesplnil
+pub enum UnmetRequirement {
+    TooFew { label: String, min: u32, have: u32 },
+    TooMany { label: String, max: u32, have: u32 },
+}
+
+fn check_range(range: &ClearRange, label: String, have: u32) -> Option<UnmetRequirement> {
+    if let Some(min) = range.min.filter(|min| have < *min) {
+        return Some(UnmetRequirement::TooFew { label, min, have });
+    }
+    if let Some(max) = range.max.filter(|max| have > *max) {
+        return Some(UnmetRequirement::TooMany { label, max, have });
+    }
+    None
+}
+
+/// Zkontroluje požadavky kanálu, cleary se stahují jen ty, které požadavky potřebují
+pub async fn check_requirements(pool: &PgPool, state: &AppState, membership: &MemberShip, requirements: &JoinRequirements) -> BotResult<Option<UnmetRequirement>> {
+    let (fetched_at, mut clears) = state
+        .runtime
+        .raid_clears
+        .get(&membership.id)
+        .filter(|entry| entry.0.elapsed() < CLEARS_CACHE_TTL)
+        .map(|entry| entry.clone())
+        .unwrap_or_else(|| (Instant::now(), RaidClears::default()));
+
+    let mut changed = false;
+    if requirements.total.is_set() && clears.total.is_none() {
+        clears.total = Some(get_users_clears(&state.bungie, membership).await? as u32);
+        changed = true;
+    }
+    if !requirements.raids.is_empty() && clears.raids.is_none() {
+        let completions = get_activity_completions(pool, &state.bungie, membership).await?;
+        clears.raids = Some(Arc::new(raid_clears(&completions)));
+        changed = true;
+    }
+    // Doplněná data vyprší spolu s tím, co už v cache bylo
+    if changed {
+        state.runtime.raid_clears.insert(membership.id.clone(), (fetched_at, clears.clone()));
+    }
+
+    if let Some(total) = clears.total.filter(|_| requirements.total.is_set()) {
+        if let Some(unmet) = check_range(&requirements.total, "raid clears".to_string(), total) {
+            return Ok(Some(unmet));
+        }
+    }
+    if let Some(raids) = &clears.raids {
+        for (raid, range) in &requirements.raids {
+            let have = raids.get(raid).copied().unwrap_or(0);
+            if let Some(unmet) = check_range(range, format!("{} clears", raid.to_uppercase()), have) {
+                return Ok(Some(unmet));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Sečte dokončení všech verzí raidu (normal, master, ...) pod jeho klíč
+fn raid_clears(completions: &HashMap<String, u32>) -> HashMap<String, u32> {
+    RAIDS
+        .iter()
+        .map(|(key, name)| {
+            let name = name.to_lowercase();
+            let count = completions.iter().filter(|(activity, _)| activity.starts_with(&name)).map(|(_, count)| count).sum();
+            (key.to_string(), count)
+        })
+        .collect()
+}
+
+fn parse_limit(value: Option<&str>) -> Result<Option<u32>, ()> {
+    match value {
+        None | Some("-") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(|_| ()),
+    }
+}
+
+pub fn requirements_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let owner = resolve_queue_owner(&state, &caller).await?;
+                let usage = || BotError::Chat("Usage: !requirements [off] | clears <min|-> [max] | raid <key> <min|-> [max] | raid <key> off".to_string());
+                let args: Vec<String> = event.message.split_whitespace().skip(1).map(|a| a.to_lowercase()).collect();
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+                let mut cfg = state.config.write().await;
+                let requirements = &mut cfg.get_channel_config_mut(owner.clone()).requirements;
+
+                match args.as_slice() {
+                    [] => {}
+                    ["off"] => *requirements = JoinRequirements::default(),
+                    ["clears", "off"] => requirements.total = ClearRange::default(),
+                    ["clears", min, rest @ ..] if rest.len() <= 1 => {
+                        let range = ClearRange {
+                            min: parse_limit(Some(min)).map_err(|_| usage())?,
+                            max: parse_limit(rest.first().copied()).map_err(|_| usage())?,
+                        };
+                        if !range.is_valid() {
+                            return Err(usage());
+                        }
+                        requirements.total = range;
+                    }
+                    ["raid", raid, rest @ ..] if (1..=2).contains(&rest.len()) => {
+                        if !RAIDS.iter().any(|(key, _)| key == raid) {
+                            let keys = RAIDS.iter().map(|(key, _)| *key).collect::<Vec<_>>().join("|");
+                            return Err(BotError::Chat(format!("Unknown raid {}, use one of: {}", raid, keys)));
+                        }
+                        if rest == ["off"] {
+                            requirements.raids.remove(*raid);
+                        } else {
+                            let range = ClearRange {
+                                min: parse_limit(Some(rest[0])).map_err(|_| usage())?,
+                                max: parse_limit(rest.get(1).copied()).map_err(|_| usage())?,
+                            };
+                            if !range.is_valid() {
+                                return Err(usage());
+                            }
+                            if range.is_set() {
+                                requirements.raids.insert(raid.to_string(), range);
+                            } else {
+                                requirements.raids.remove(*raid);
+                            }
+                        }
+                    }
+                    _ => return Err(usage()),
+                }
+
+                let reply = Replies::join_requirements(&requirements.summary());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &owner, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change the raid clear requirements for joining",
+        "!requirements [off] | clears <min|-> [max] | raid <key> <min|-> [max] | raid <key> off",
+        "requirements",
+        PermissionLevel::Moderator,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: Option<u32>, max: Option<u32>) -> ClearRange {
+        ClearRange { min, max }
+    }
+
+    #[test]
+    fn clear_range_validity_and_summary() {
+        assert!(!ClearRange::default().is_set());
+        assert!(range(Some(5), Some(5)).is_valid());
+        assert!(!range(Some(10), Some(2)).is_valid());
+        assert!(range(Some(10), None).is_valid());
+        assert_eq!(range(Some(1), Some(5)).summary("raid clears"), "1-5 raid clears");
+        assert_eq!(range(Some(1), None).summary("raid clears"), "1+ raid clears");
+        assert_eq!(range(None, Some(0)).summary("VOG clears"), "max 0 VOG clears");
+    }
+
+    #[test]
+    fn check_range_reports_the_broken_bound() {
+        let sherpa = range(Some(10), Some(50));
+        assert!(check_range(&sherpa, "raid clears".to_string(), 10).is_none());
+        assert!(check_range(&sherpa, "raid clears".to_string(), 50).is_none());
+        assert!(matches!(
+            check_range(&sherpa, "raid clears".to_string(), 3),
+            Some(UnmetRequirement::TooFew { min: 10, have: 3, .. })
+        ));
+        assert!(matches!(
+            check_range(&sherpa, "raid clears".to_string(), 51),
+            Some(UnmetRequirement::TooMany { max: 50, have: 51, .. })
+        ));
+        assert!(check_range(&ClearRange::default(), "raid clears".to_string(), 0).is_none());
+    }
+
+    #[test]
+    fn raid_clears_sum_every_difficulty() {
+        let completions = HashMap::from([
+            ("vault of glass: normal".to_string(), 4),
+            ("vault of glass: master".to_string(), 2),
+            ("king's fall: legend".to_string(), 1),
+            ("grasp of avarice".to_string(), 9),
+        ]);
+        let clears = raid_clears(&completions);
+        assert_eq!(clears.len(), RAIDS.len());
+        assert_eq!(clears["vog"], 6);
+        assert_eq!(clears["kf"], 1);
+        assert_eq!(clears["lw"], 0);
+    }
+}
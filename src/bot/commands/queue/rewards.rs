@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{api::twitch_api::{create_custom_reward, update_redemption_status}, bot::{chat_event::chat_event::{ChatUser, DisplayName, Platform, UserIdentity}, commands::{commands::{BotResult, CommandT, FnCommand}, queue::logic::{Queue, prioritize, process_queue_entry, queue_settings, resolve_named_queue, resolve_queue}}, db::{ChannelId, UserId, config::save_channel_config, queue::{queue_position, user_exists_in_queue}}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::{def::{AppState, BotError, MAIN_QUEUE}, state::get_broadcaster_token}, web::sse::SseEvent}};
+
+/// Co se stane po uplatnění odměny za kanálové body
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RewardAction {
+    /// Zařadí diváka s uloženým Bungie jménem
+    Join,
+    /// Jeden prioritní run, "Skip the line"
+    Prio,
+}
+
+impl RewardAction {
+    fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "join" | "queue" => Some(RewardAction::Join),
+            "prio" | "skip" => Some(RewardAction::Prio),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            RewardAction::Join => "join",
+            RewardAction::Prio => "prio",
+        }
+    }
+}
+
+/// Odměna z Twitche namapovaná na akci ve frontě
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RewardMapping {
+    pub title: String,
+    pub action: RewardAction,
+    #[serde(default = "default_queue")]
+    pub queue: String,
+    /// Jen u odměn vytvořených přes `!reward create`, ty jde potvrdit i vrátit
+    #[serde(default)]
+    pub reward_id: Option<String>,
+}
+
+fn default_queue() -> String {
+    MAIN_QUEUE.to_string()
+}
+
+impl RewardMapping {
+    fn matches(&self, reward_id: &str, title: &str) -> bool {
+        match &self.reward_id {
+            Some(id) => id == reward_id,
+            None => self.title.eq_ignore_ascii_case(title),
+        }
+    }
+
+    fn summary(&self) -> String {
+        if self.queue == MAIN_QUEUE {
+            format!("\"{}\" → {}", self.title, self.action.as_str())
+        } else {
+            format!("\"{}\" → {} {}", self.title, self.action.as_str(), self.queue)
+        }
+    }
+}
+
+/// Uplatnění odměny z EventSub `channel.channel_points_custom_reward_redemption.add`
+#[derive(Deserialize, Debug, Clone)]
+pub struct Redemption {
+    pub id: String,
+    pub broadcaster_user_id: String,
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    pub status: String,
+    pub reward: RedeemedReward,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RedeemedReward {
+    pub id: String,
+    pub title: String,
+}
+
+/// Provede akci namapovanou na odměnu. U odměn vytvořených botem úspěch odměnu potvrdí
+/// a neúspěch ji zruší, takže se body vrátí.
+pub async fn handle_redemption(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, redemption: Redemption) -> BotResult<()> {
+    let mapping = {
+        let cfg = state.config.read().await;
+        cfg.get_channel_config(channel).and_then(|c| c.rewards.iter().find(|m| m.matches(&redemption.reward.id, &redemption.reward.title)).cloned())
+    };
+    let Some(mapping) = mapping else { return Ok(()); };
+    // Automaticky potvrzené odměny a odměny cizí aplikace Helix změnit nedovolí
+    let manageable = redemption.status == "unfulfilled" && mapping.reward_id.is_some();
+
+    let user = ChatUser {
+        identity: UserIdentity { platform: Platform::Twitch, platform_user_id: redemption.user_id.clone() },
+        name: DisplayName { login: redemption.user_login.clone(), display: redemption.user_name.clone() },
+        permission: PermissionLevel::Everyone,
+        subscriber: false,
+    };
+
+    let outcome = match redeem(pool, state, channel, &mapping, &user).await {
+        Ok(outcome) => outcome,
+        Err(BotError::Chat(msg)) => Err(msg),
+        Err(e) => {
+            warn!("Redemption {} in {} failed: {:?}", redemption.id, channel.as_str(), e);
+            Err(Replies::reward_failed(&user.name.display))
+        }
+    };
+
+    let fulfilled = outcome.is_ok();
+    let updated = manageable && set_redemption_status(pool, state, channel, &redemption, fulfilled).await;
+    let reply = match outcome {
+        Ok(reply) => reply,
+        Err(rejection) if updated => format!("{} {}", rejection, Replies::reward_refunded(&mapping.title)),
+        Err(rejection) => format!("{} {}", rejection, Replies::reward_not_refunded(&mapping.title)),
+    };
+
+    state.chat_client.send_message(channel, &reply).await?;
+    Ok(())
+}
+
+/// Potvrdí nebo zruší uplatnění přes Helix, `false` když se to nepovedlo
+async fn set_redemption_status(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, redemption: &Redemption, fulfilled: bool) -> bool {
+    let token = match get_broadcaster_token(pool, state, channel).await {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            warn!("No broadcaster token for {}, redemption {} left unfulfilled", channel.as_str(), redemption.id);
+            return false;
+        }
+        Err(e) => {
+            warn!("Loading broadcaster token for {} failed: {:?}", channel.as_str(), e);
+            return false;
+        }
+    };
+    match update_redemption_status(&state.secrets, &token.access_token, &redemption.broadcaster_user_id, &redemption.reward.id, &redemption.id, fulfilled).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Could not update redemption {} in {}: {:?}", redemption.id, channel.as_str(), e);
+            false
+        }
+    }
+}
+
+/// `Err` je odmítnutí pro chat, po kterém se body vrací
+async fn redeem(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, mapping: &RewardMapping, user: &ChatUser) -> BotResult<Result<String, String>> {
+    let (owner, queue) = resolve_queue(state, channel, &format!("reward {}", mapping.queue)).await?;
+    let settings = queue_settings(state, &owner, &queue).await?;
+    let user_id = UserId::new(Platform::Twitch, &user.identity.platform_user_id);
+
+    let reply = match mapping.action {
+        RewardAction::Join => {
+            if !settings.open {
+                return Ok(Err(Replies::join_closed(&user.name.display)));
+            }
+            if user_exists_in_queue(pool, &user_id, &owner, &queue).await? {
+                return Ok(Err(Replies::reward_already_queued(&user.name.display)));
+            }
+            // Bez zadaného jména se použije uložené Bungie jméno
+            let entry = match state.resolve_joiner(pool, user, &owner, &queue, "").await? {
+                Ok(entry) => entry,
+                Err(rejection) => return Ok(Err(rejection)),
+            };
+            let reply = process_queue_entry(pool, settings.size, entry, &owner, &queue, Queue::Join, settings.random_queue).await?;
+            if !user_exists_in_queue(pool, &user_id, &owner, &queue).await? {
+                return Ok(Err(reply));
+            }
+            reply
+        }
+        RewardAction::Prio => {
+            // Hráč v aktuální skupině by se prio posunul až za ni
+            if queue_position(pool, &user_id, &owner, &queue).await?.is_some_and(|p| p <= settings.teamsize as i32) {
+                return Ok(Err(Replies::reward_prio_in_group(&user.name.display)));
+            }
+            if !prioritize(pool, &owner, &queue, &user_id, Some(1), settings.teamsize as i32).await? {
+                return Ok(Err(format!("User {} not found in the queue", user.name.display)));
+            }
+            Replies::reward_prio(&user.name.display)
+        }
+    };
+
+    let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+    Ok(Ok(reply))
+}
+
+pub fn reward_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let usage = || BotError::Chat(
+                    "Usage: !reward add <join|prio> [queue] <title> | create <join|prio> <cost> [queue] <title> | remove <title>".to_string(),
+                );
+                if caller.platform() != Platform::Twitch {
+                    return Err(BotError::Chat(Replies::reward_twitch_only()));
+                }
+
+                let args: Vec<&str> = event.message.split_whitespace().skip(1).collect();
+                let reply = match args.first().map(|a| a.to_lowercase()).as_deref() {
+                    None | Some("list") => {
+                        let cfg = state.config.read().await;
+                        let mappings: Vec<String> = cfg.get_channel_config(&caller).map(|c| c.rewards.iter().map(RewardMapping::summary).collect()).unwrap_or_default();
+                        Replies::reward_list(&mappings)
+                    }
+                    Some("remove") => {
+                        let title = args[1..].join(" ");
+                        let mut cfg = state.config.write().await;
+                        let rewards = &mut cfg.get_channel_config_mut(caller.clone()).rewards;
+                        let before = rewards.len();
+                        rewards.retain(|m| !m.title.eq_ignore_ascii_case(&title));
+                        if rewards.len() == before {
+                            return Err(BotError::Chat(Replies::reward_unknown(&title)));
+                        }
+                        save_channel_config(&pool, &caller, &cfg).await?;
+                        Replies::reward_removed(&title)
+                    }
+                    Some(sub @ ("add" | "create")) => {
+                        let action = args.get(1).and_then(|a| RewardAction::parse(a)).ok_or_else(usage)?;
+                        let (cost, rest) = if sub == "create" {
+                            let cost = args.get(2).and_then(|c| c.parse::<i64>().ok()).filter(|c| *c > 0).ok_or_else(usage)?;
+                            (Some(cost), args.get(3..).unwrap_or_default().join(" "))
+                        } else {
+                            (None, args[2..].join(" "))
+                        };
+                        let (owner, queue, title) = resolve_named_queue(&state, &caller, &format!("reward {}", rest)).await?;
+                        let title = title.trim().to_string();
+                        if title.is_empty() {
+                            return Err(usage());
+                        }
+                        queue_settings(&state, &owner, &queue).await?;
+
+                        let reward_id = match cost {
+                            Some(cost) => {
+                                let token = get_broadcaster_token(&pool, &state, &caller).await?
+                                    .ok_or_else(|| BotError::Chat(Replies::reward_needs_login()))?;
+                                let prompt = match action {
+                                    RewardAction::Join => "Joins the queue with your saved Bungie name",
+                                    RewardAction::Prio => "Moves you to the next group",
+                                };
+                                Some(create_custom_reward(&state.secrets, &token.access_token, &token.broadcaster_id, &title, cost, prompt).await
+                                    .map_err(|e| BotError::Chat(format!("❌ Twitch refused the reward: {}", e)))?)
+                            }
+                            None => None,
+                        };
+
+                        let mapping = RewardMapping { title: title.clone(), action, queue, reward_id };
+                        let reply = Replies::reward_added(&mapping.summary());
+                        let mut cfg = state.config.write().await;
+                        let rewards = &mut cfg.get_channel_config_mut(caller.clone()).rewards;
+                        rewards.retain(|m| !m.title.eq_ignore_ascii_case(&title));
+                        rewards.push(mapping);
+                        save_channel_config(&pool, &caller, &cfg).await?;
+                        reply
+                    }
+                    Some(_) => return Err(usage()),
+                };
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Map channel point rewards to queue actions",
+        "!reward [list] | add <join|prio> [queue] <title> | create <join|prio> <cost> [queue] <title> | remove <title>",
+        "reward",
+        PermissionLevel::Moderator,
+    ))
+}
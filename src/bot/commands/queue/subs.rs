@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::bot::{chat_event::chat_event::{ChatEvent, SupportEvent, SupportKind}, commands::{commands::{BotResult, CommandT, FnCommand}, queue::logic::{prioritize, resolve_queue_owner}, viewer::{ViewerScope, resolve_viewer}}, db::{ChannelId, UserId, config::save_channel_config, queue::queue_position, sub_prio::store_pending_prio}, handler::handler::ChatClient, permissions::permissions::PermissionLevel, replies::Replies, state::def::{AppState, BotError, MAIN_QUEUE}, web::sse::SseEvent};
+
+/// Kolik prioritních runů dostane sub nebo dárce
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubRule {
+    pub kind: SupportKind,
+    /// 0 = jakýkoli tier
+    #[serde(default)]
+    pub tier: u8,
+    /// Minimální počet darovaných subů, u subu 1
+    #[serde(default = "default_min")]
+    pub min: u32,
+    pub runs: i32,
+}
+
+fn default_min() -> u32 {
+    1
+}
+
+impl SubRule {
+    fn matches(&self, event: &SupportEvent) -> bool {
+        self.kind == event.kind && (self.tier == 0 || self.tier == event.tier) && event.count >= self.min
+    }
+
+    fn summary(&self) -> String {
+        let runs = if self.runs == 1 { "1 prio run".to_string() } else { format!("{} prio runs", self.runs) };
+        match self.kind {
+            SupportKind::Sub if self.tier == 0 => format!("any sub → {runs}"),
+            SupportKind::Sub => format!("tier {} sub → {runs}", self.tier),
+            SupportKind::Gift => format!("{}+ gifted → {runs}", self.min),
+        }
+    }
+}
+
+/// Nejštědřejší pravidlo, které na event sedí
+pub fn matching_rule<'a>(rules: &'a [SubRule], event: &SupportEvent) -> Option<&'a SubRule> {
+    rules.iter().filter(|r| r.matches(event)).max_by_key(|r| r.runs)
+}
+
+/// Sub / resub / dar z Twitche nebo Kicku. Kdo ještě nečeká ve frontě, dostane prio při příštím joinu.
+pub async fn handle_support_event(event: &ChatEvent, pool: &PgPool, state: &Arc<AppState>) -> BotResult<()> {
+    let (Some(support), Some(user)) = (&event.support, &event.user) else { return Ok(()); };
+    let caller = ChannelId::new(event.platform, &event.channel);
+
+    let rule = {
+        let cfg = state.config.read().await;
+        cfg.get_channel_config(&caller).and_then(|c| matching_rule(&c.sub_rules, support).cloned())
+    };
+    let Some(rule) = rule else { return Ok(()); };
+    let owner = resolve_queue_owner(state, &caller).await?;
+
+    // Kick posílá jen username
+    let user_id = if user.identity.platform_user_id.is_empty() {
+        match resolve_viewer(pool, state, event.platform, ViewerScope::Channel(&owner), &format!("{}:{}", event.platform, user.name.login)).await {
+            Ok(viewer) => viewer.user_id,
+            Err(_) => return Ok(()),
+        }
+    } else {
+        UserId::new(user.identity.platform, &user.identity.platform_user_id)
+    };
+
+    let queues: Vec<(String, usize)> = {
+        let cfg = state.config.read().await;
+        let Some(owner_cfg) = cfg.get_channel_config(&owner) else { return Ok(()); };
+        std::iter::once((MAIN_QUEUE.to_string(), owner_cfg.main.teamsize))
+            .chain(owner_cfg.queues.iter().map(|(name, q)| (name.clone(), q.teamsize)))
+            .collect()
+    };
+
+    for (queue, teamsize) in queues {
+        // Kdo už hraje v aktuální skupině, by se prio posunul až za ni
+        match queue_position(pool, &user_id, &owner, &queue).await? {
+            Some(position) if position > teamsize as i32 => {}
+            _ => continue,
+        }
+        if prioritize(pool, &owner, &queue, &user_id, Some(rule.runs), teamsize as i32).await? {
+            let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+            state.chat_client.send_message(&caller, &Replies::sub_prio_granted(&user.name.display, support.kind, rule.runs)).await?;
+            return Ok(());
+        }
+    }
+
+    // Ještě nečeká ve frontě (nebo už hraje), prio se použije při příštím joinu
+    info!("{} supported {} but is not waiting in any queue, keeping the prio for their next join", user.name.display, caller.as_str());
+    store_pending_prio(pool, &owner, &user_id, rule.runs).await?;
+    state.chat_client.send_message(&caller, &Replies::sub_prio_pending(&user.name.display, support.kind, rule.runs)).await?;
+    Ok(())
+}
+
+pub fn sub_prio_command() -> Arc<dyn CommandT> {
+    Arc::new(FnCommand::new(
+        |event, pool, state, client| {
+            Box::pin(async move {
+                let caller = ChannelId::new(event.platform, &event.channel);
+                let usage = || BotError::Chat("Usage: !subprio sub <1|2|3|any> <runs> | gift <count> <runs> | off (0 runs removes a rule)".to_string());
+                let args: Vec<String> = event.message.split_whitespace().skip(1).map(|a| a.to_lowercase()).collect();
+
+                let mut cfg = state.config.write().await;
+                let rules = &mut cfg.get_channel_config_mut(caller.clone()).sub_rules;
+
+                match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+                    [] => {}
+                    ["off"] => rules.clear(),
+                    [kind, key, runs] => {
+                        let runs: i32 = runs.parse().ok().filter(|r| *r >= 0).ok_or_else(usage)?;
+                        let rule = match *kind {
+                            "sub" => {
+                                let tier = if *key == "any" { 0 } else { key.parse::<u8>().ok().filter(|t| (1..=3).contains(t)).ok_or_else(usage)? };
+                                SubRule { kind: SupportKind::Sub, tier, min: 1, runs }
+                            }
+                            "gift" | "gifts" => {
+                                let min = key.parse::<u32>().ok().filter(|m| *m > 0).ok_or_else(usage)?;
+                                SubRule { kind: SupportKind::Gift, tier: 0, min, runs }
+                            }
+                            _ => return Err(usage()),
+                        };
+                        rules.retain(|r| !(r.kind == rule.kind && r.tier == rule.tier && r.min == rule.min));
+                        if rule.runs > 0 {
+                            rules.push(rule);
+                        }
+                    }
+                    _ => return Err(usage()),
+                }
+
+                let reply = Replies::sub_prio_rules(&rules.iter().map(SubRule::summary).collect::<Vec<_>>());
+                if !args.is_empty() {
+                    save_channel_config(&pool, &caller, &cfg).await?;
+                }
+                drop(cfg);
+
+                client.send_message(&caller, &reply).await?;
+                Ok(())
+            })
+        },
+        "Show or change how many prio runs subs and gifted subs grant",
+        "!subprio [sub <1|2|3|any> <runs>] [gift <count> <runs>] [off]",
+        "subprio",
+        PermissionLevel::Moderator,
+    ))
+}
@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+/// Tagy, které jde připsat k `!join`. První je kanonický název, zbytek aliasy.
+const KNOWN_TAGS: &[&[&str]] = &[
+    &["titan"],
+    &["hunter"],
+    &["warlock", "lock"],
+    &["sherpa", "teacher"],
+    &["first", "firsttime", "firsttimer", "new"],
+];
+
+pub fn canonical_tag(word: &str) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    KNOWN_TAGS.iter().find(|aliases| aliases.contains(&word.as_str())).map(|aliases| aliases[0])
+}
+
+pub fn known_tags() -> String {
+    KNOWN_TAGS.iter().map(|aliases| aliases[0]).collect::<Vec<_>>().join(", ")
+}
+
+/// Odtrhne tagy z konce argumentů (`Name#1234 titan sherpa`), vrací (zbytek, tagy)
+pub fn split_tags(args: &str) -> (String, Vec<String>) {
+    let mut words: Vec<&str> = args.split_whitespace().collect();
+    let mut tags = Vec::new();
+
+    while let Some(tag) = words.last().and_then(|w| canonical_tag(w)) {
+        if !tags.contains(&tag.to_string()) {
+            tags.insert(0, tag.to_string());
+        }
+        words.pop();
+    }
+    (words.join(" "), tags)
+}
+
+/// Tagy do chatu, např. ` [titan, sherpa]`
+pub fn format_tags(tags: &[String]) -> String {
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", tags.join(", "))
+    }
+}
+
+/// Limity tagů na jednu skupinu při `!next`, např. nejvýš 2 first-timeři. Prázdné = vypnuto.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct GroupBalance {
+    #[serde(default)]
+    pub max_per_group: BTreeMap<String, usize>,
+}
+
+impl GroupBalance {
+    pub fn is_enabled(&self) -> bool {
+        !self.max_per_group.is_empty()
+    }
+
+    /// Vejde se blok hráčů s těmito tagy do skupiny, kde už jsou `counts`?
+    pub fn allows(&self, counts: &HashMap<String, usize>, tags: &[String]) -> bool {
+        self.max_per_group.iter().all(|(tag, max)| {
+            let adding = tags.iter().filter(|t| *t == tag).count();
+            adding == 0 || counts.get(tag).copied().unwrap_or(0) + adding <= *max
+        })
+    }
+
+    pub fn summary(&self) -> String {
+        if !self.is_enabled() {
+            return "off".to_string();
+        }
+        self.max_per_group.iter().map(|(tag, max)| format!("max {max} {tag}")).collect::<Vec<_>>().join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn balance(limits: &[(&str, usize)]) -> GroupBalance {
+        GroupBalance { max_per_group: limits.iter().map(|(tag, max)| (tag.to_string(), *max)).collect() }
+    }
+
+    #[test]
+    fn balance_off_allows_everything() {
+        let counts = HashMap::from([("first".to_string(), 10)]);
+        assert!(GroupBalance::default().allows(&counts, &tags(&["first", "first"])));
+    }
+
+    #[test]
+    fn balance_counts_the_whole_block() {
+        let balance = balance(&[("first", 2)]);
+        let counts = HashMap::from([("first".to_string(), 1)]);
+        assert!(balance.allows(&counts, &tags(&["first"])));
+        // Party se dvěma first-timery by limit překročila
+        assert!(!balance.allows(&counts, &tags(&["first", "first"])));
+        assert!(balance.allows(&HashMap::new(), &tags(&["first", "first"])));
+    }
+
+    #[test]
+    fn balance_ignores_tags_without_a_limit() {
+        let balance = balance(&[("first", 1)]);
+        let counts = HashMap::from([("first".to_string(), 1), ("titan".to_string(), 5)]);
+        assert!(balance.allows(&counts, &tags(&["titan", "sherpa"])));
+        assert!(!balance.allows(&counts, &tags(&["titan", "first"])));
+    }
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use sqlx::PgPool;
+
+use crate::{api::twitch_api::resolve_twitch_user_id, bot::{chat_event::chat_event::Platform, commands::commands::BotResult, db::{ChannelId, UserId, users::{ViewerMatch, find_queue_users_by_name, find_users_by_name}}, replies::Replies, state::{def::{AppState, BotError}, state::get_twitch_access_token}}};
+
+/// Divák, na kterého míří moderátorský příkaz
+#[derive(Debug, Clone)]
+pub struct Viewer {
+    pub user_id: UserId,
+    pub login: String,
+    pub display_name: String,
+}
+
+/// Kde hledat dřív než mezi všemi známými diváky
+pub enum ViewerScope<'a> {
+    Anywhere,
+    /// Jedna fronta vlastníka
+    Queue(&'a ChannelId, &'a str),
+    /// Všechny fronty vlastníka
+    Channel(&'a ChannelId),
+}
+
+/// Převede `@name`, login, zobrazované jméno nebo `kick:login` na diváka.
+/// Hledá postupně ve frontě (podle `scope`), ve `streamusers` a nakonec přes Twitch API.
+/// Víc shod je chyba do chatu se seznamem, ať moderátor upřesní platformu.
+pub async fn resolve_viewer(pool: &PgPool, state: &AppState, caller_platform: Platform, scope: ViewerScope<'_>, input: &str) -> BotResult<Viewer> {
+    let input = input.trim().trim_start_matches('@');
+    let (platform, name) = match input.split_once(':') {
+        Some((platform, name)) => match Platform::from_str(&platform.to_lowercase()) {
+            Ok(platform) => (Some(platform), name.trim_start_matches('@')),
+            Err(_) => (None, input),
+        },
+        None => (None, input),
+    };
+    if name.is_empty() {
+        return Err(BotError::Chat(Replies::viewer_not_found(input)));
+    }
+
+    let in_queue = match scope {
+        ViewerScope::Anywhere => Vec::new(),
+        ViewerScope::Queue(owner, queue) => find_queue_users_by_name(pool, owner, Some(queue), platform, name).await?,
+        ViewerScope::Channel(owner) => find_queue_users_by_name(pool, owner, None, platform, name).await?,
+    };
+    if let Some(viewer) = pick_viewer(name, in_queue)? {
+        return Ok(viewer);
+    }
+
+    // Login je na platformě unikátní, zobrazované jméno ne
+    let mut known = find_users_by_name(pool, platform, name).await?;
+    if known.iter().any(|m| m.login_name.eq_ignore_ascii_case(name)) {
+        known.retain(|m| m.login_name.eq_ignore_ascii_case(name));
+    }
+    if let Some(viewer) = pick_viewer(name, known)? {
+        return Ok(viewer);
+    }
+
+    // Přejmenovaní nebo ještě neznámí diváci na Twitchi
+    if platform.unwrap_or(caller_platform) == Platform::Twitch {
+        let token = get_twitch_access_token(state).await?;
+        if let Ok((platform_user_id, display_name)) = resolve_twitch_user_id(name, &state.secrets, &token).await {
+            return Ok(Viewer {
+                user_id: UserId::new(Platform::Twitch, platform_user_id),
+                login: name.to_lowercase(),
+                display_name,
+            });
+        }
+    }
+
+    Err(BotError::Chat(Replies::viewer_not_found(name)))
+}
+
+fn pick_viewer(name: &str, matches: Vec<ViewerMatch>) -> BotResult<Option<Viewer>> {
+    match matches.as_slice() {
+        [] => Ok(None),
+        [only] => Ok(Some(Viewer {
+            user_id: UserId::from_str(&only.id).map_err(|e| BotError::Custom(e.to_string()))?,
+            login: only.login_name.clone(),
+            display_name: only.display_name.clone(),
+        })),
+        many => {
+            let options: Vec<String> = many
+                .iter()
+                .map(|m| format!("{}:{} ({})", m.id.split_once(':').map(|(p, _)| p).unwrap_or_default(), m.login_name, m.display_name))
+                .collect();
+            Err(BotError::Chat(Replies::viewer_ambiguous(name, &options)))
+        }
+    }
+}
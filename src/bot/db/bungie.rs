@@ -1,6 +1,35 @@
 use sqlx::PgPool;
 
-use crate::{api::bungie::{MemberShip, get_membershipid}, bot::{chat_event::chat_event::{ChatUser, Platform}, commands::commands::BotResult, db::{UserId, users::{User, upsert_stream_user}}, state::def::BotError}};
+use crate::{api::bungie::{BungieAccount, BungieClient, BungieError, MemberShip}, bot::{chat_event::chat_event::{ChatUser, Platform}, commands::commands::BotResult, db::{ChannelId, UserId, queue::begin_queue_tx, users::{User, upsert_stream_user}}, replies::Replies, state::def::BotError}};
+
+/// Všechny propojené (cross-save) membershipy uživatele, `is_primary` je ta s postavami
+pub const BUNGIE_MEMBERSHIPS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.bungie_memberships (
+        user_id TEXT NOT NULL,
+        membership_id TEXT NOT NULL,
+        membership_type INTEGER NOT NULL,
+        is_primary BOOLEAN NOT NULL DEFAULT FALSE,
+        PRIMARY KEY (user_id, membership_id)
+    );
+"#;
+
+/// Přepíše uložené membershipy uživatele podle aktuálního účtu
+async fn save_linked_memberships(pool: &PgPool, user_id: &UserId, account: &BungieAccount) -> BotResult<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM krapbott_v2.bungie_memberships WHERE user_id = $1")
+        .bind(user_id.as_str()).execute(&mut *tx).await?;
+    for membership in &account.linked {
+        sqlx::query(
+            r#"
+            INSERT INTO krapbott_v2.bungie_memberships (user_id, membership_id, membership_type, is_primary)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, membership_id) DO NOTHING
+            "#,
+        ).bind(user_id.as_str()).bind(&membership.id).bind(membership.type_m).bind(membership.id == account.primary.id).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
 
 pub async fn register_bungie_name(
     pool: &PgPool,
@@ -8,20 +37,26 @@ pub async fn register_bungie_name(
     platform_user_id: &str,     // ID uživatele na platformě
     login_name: &str,           // login name (např. twitch login)
     display_name: &str,         // zobrazované jméno
-    bungie_name: &str, x_api_key: &str) -> BotResult<String> {
+    bungie_name: &str, bungie: &BungieClient) -> BotResult<String> {
     // Zavoláme Bungie API pro získání membership info
-    let membership_info = get_membershipid(bungie_name, x_api_key).await
-        .map_err(|_| BotError::Custom("Problem with Bungie API".to_string()))?;
-
-    if membership_info.type_m == -1 {
-        return Ok(format!(
-            "{} doesn't exist, check if your Bungie name is correct",
-            bungie_name
-        ));
-    }
+    let account = match bungie.find_account(bungie_name).await {
+        Ok(account) => account,
+        Err(BungieError::NotFound | BungieError::InvalidName(_)) => {
+            return Ok(format!(
+                "{} doesn't exist, check if your Bungie name is correct",
+                bungie_name
+            ));
+        }
+        Err(err) if err.is_unavailable() => {
+            tracing::warn!("Bungie lookup for {} failed: {}", bungie_name, err);
+            return Err(BotError::Chat(Replies::bungie_unavailable()));
+        }
+        Err(err) => return Err(err.into()),
+    };
 
+    let membership_info = &account.primary;
     let user = User {
-        id: format!("{}:{}", platform, platform_user_id),
+        id: platform_user_id.to_string(),
         platform: platform,
         platform_user_id: platform_user_id.to_string(),
         login_name: login_name.to_string(),
@@ -32,6 +67,7 @@ pub async fn register_bungie_name(
     };
 
     upsert_stream_user(pool, &user).await?;
+    save_linked_memberships(pool, &UserId::new(platform, platform_user_id), &account).await?;
 
     Ok(format!(
         "{} has been registered to the database as {}",
@@ -47,11 +83,14 @@ pub async fn load_membership(
 ) -> Option<MemberShip> {
     let user_id = UserId::new(platform, platform_user_id);
 
+    // Primární cross-save membership, starší záznamy bez propojení mají jen `streamusers`
     let result = sqlx::query!(
         r#"
-        SELECT membership_id, membership_type
-        FROM krapbott_v2.streamusers
-        WHERE id = $1
+        SELECT COALESCE(m.membership_id, s.membership_id) AS "membership_id!",
+               COALESCE(m.membership_type, s.membership_type) AS "membership_type!"
+        FROM krapbott_v2.streamusers s
+        LEFT JOIN krapbott_v2.bungie_memberships m ON m.user_id = s.id AND m.is_primary
+        WHERE s.id = $1
         "#,
         user_id.as_str()
     ).fetch_optional(pool).await.ok()?;
@@ -68,10 +107,11 @@ pub async fn load_membership(
 }
 
 /// Kontrola, zda existuje Bungie jméno pro daného uživatele
-/// Vrací true pokud existuje a uloží informace do DB
-pub async fn is_bungiename(pool: &PgPool, user: &ChatUser, bungie_name: &str, x_api_key: &str) -> bool {
-    match get_membershipid(bungie_name, x_api_key).await {
-        Ok(info) if info.type_m != -1 => {
+/// Vrací true pokud existuje a uloží informace do DB, `Err` když Bungie neodpovídá
+pub async fn is_bungiename(pool: &PgPool, user: &ChatUser, bungie_name: &str, bungie: &BungieClient) -> Result<bool, BungieError> {
+    match bungie.find_account(bungie_name).await {
+        Ok(account) => {
+            let info = &account.primary;
 
             let id = UserId::new(user.identity.platform, user.identity.platform_user_id.clone());
             let _ = sqlx::query(
@@ -83,11 +123,15 @@ pub async fn is_bungiename(pool: &PgPool, user: &ChatUser, bungie_name: &str, x_
                     membership_id = EXCLUDED.membership_id,
                     membership_type = EXCLUDED.membership_type
                 "#,
-            ).bind(id).bind(user.identity.platform.as_str()).bind(user.identity.platform_user_id.clone()).bind(user.name.login.clone()).bind(user.name.display.clone()).bind(bungie_name).bind(info.id.to_string()).bind(info.type_m).execute(pool).await;
+            ).bind(id.as_str()).bind(user.identity.platform.as_str()).bind(user.identity.platform_user_id.clone()).bind(user.name.login.clone()).bind(user.name.display.clone()).bind(bungie_name).bind(info.id.to_string()).bind(info.type_m).execute(pool).await;
+            if let Err(err) = save_linked_memberships(pool, &id, &account).await {
+                tracing::warn!("Saving linked memberships for {} failed: {}", bungie_name, err);
+            }
 
-            true
+            Ok(true)
         }
-        _ => false,
+        Err(BungieError::NotFound | BungieError::InvalidName(_)) => Ok(false),
+        Err(err) => Err(err),
     }
 }
 
@@ -104,4 +148,52 @@ pub async fn get_membership_id_by_user_id(pool: &PgPool, user_id: &UserId) -> Re
     .await?;
 
     Ok(record)
-}
\ No newline at end of file
+}
+/// Uživatel s uloženým Bungie jménem, který se nejdéle nekontroloval (nikdy kontrolovaní první)
+pub async fn next_bungie_name_check(pool: &PgPool, older_than_hours: i32) -> BotResult<Option<(String, String, String, MemberShip)>> {
+    let row: Option<(String, String, String, String, i32)> = sqlx::query_as(
+        r#"
+        SELECT s.id, s.display_name, s.bungie_name,
+               COALESCE(m.membership_id, s.membership_id), COALESCE(m.membership_type, s.membership_type)
+        FROM krapbott_v2.streamusers s
+        LEFT JOIN krapbott_v2.bungie_memberships m ON m.user_id = s.id AND m.is_primary
+        WHERE s.bungie_checked_at IS NULL OR s.bungie_checked_at < now() - make_interval(hours => $1)
+        ORDER BY s.bungie_checked_at NULLS FIRST
+        LIMIT 1
+        "#,
+    ).bind(older_than_hours).fetch_optional(pool).await?;
+
+    Ok(row.map(|(id, display_name, bungie_name, membership_id, membership_type)| {
+        (id, display_name, bungie_name, MemberShip { id: membership_id, type_m: membership_type })
+    }))
+}
+
+pub async fn mark_bungie_name_checked(pool: &PgPool, user_id: &str) -> BotResult<()> {
+    sqlx::query("UPDATE krapbott_v2.streamusers SET bungie_checked_at = now() WHERE id = $1")
+        .bind(user_id).execute(pool).await?;
+    Ok(())
+}
+
+/// Přepíše Bungie jméno uživatele i v aktivních frontách, vrací kanály, kde ve frontě stojí.
+/// Staré i nové jméno zmizí z cache klienta, ať se staré dál nepřekládá.
+pub async fn update_bungie_name(pool: &PgPool, bungie: &BungieClient, user_id: &str, old_name: &str, bungie_name: &str) -> BotResult<Vec<ChannelId>> {
+    sqlx::query("UPDATE krapbott_v2.streamusers SET bungie_name = $2, bungie_checked_at = now() WHERE id = $1")
+        .bind(user_id).bind(bungie_name).execute(pool).await?;
+    bungie.forget_name(old_name);
+    bungie.forget_name(bungie_name);
+
+    let owners: Vec<ChannelId> = sqlx::query_scalar("SELECT DISTINCT channel_id FROM krapbott_v2.queue WHERE user_id = $1")
+        .bind(user_id).fetch_all(pool).await?;
+    let mut channels = Vec::new();
+    for owner in owners {
+        // Pod zámkem fronty, ať se to nepotká s !next nebo snapshotem pro undo
+        let mut tx = begin_queue_tx(pool, &owner).await?;
+        let updated = sqlx::query("UPDATE krapbott_v2.queue SET bungie_name = $3 WHERE user_id = $1 AND channel_id = $2")
+            .bind(user_id).bind(owner.as_str()).bind(bungie_name).execute(&mut *tx).await?.rows_affected();
+        tx.commit().await?;
+        if updated > 0 {
+            channels.push(owner);
+        }
+    }
+    Ok(channels)
+}
@@ -0,0 +1,56 @@
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, UserId}};
+
+pub const COOLDOWN_CLEARS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.cooldown_clears (
+        channel_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        cleared_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        PRIMARY KEY (channel_id, user_id)
+    );
+"#;
+
+/// Poslední run diváka v kanálu (po případném ručním smazání cooldownu)
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LastRun {
+    /// Kolik runů kanál odehrál od té doby
+    pub runs_since: i64,
+    pub seconds_since: i64,
+}
+
+pub async fn fetch_last_run(pool: &PgPool, channel_id: &ChannelId, user_id: &UserId) -> BotResult<Option<LastRun>> {
+    let last = sqlx::query_as::<_, LastRun>(
+        r#"
+        WITH last AS (
+            SELECT h.id, h.played_at
+            FROM krapbott_v2.run_history h
+            JOIN krapbott_v2.run_history_members m ON m.run_id = h.id
+            LEFT JOIN krapbott_v2.cooldown_clears c
+                   ON c.channel_id = h.channel_id AND c.user_id = m.user_id
+            WHERE h.channel_id = $1 AND m.user_id = $2
+              AND h.played_at > COALESCE(c.cleared_at, '-infinity'::timestamptz)
+            ORDER BY h.played_at DESC
+            LIMIT 1
+        )
+        SELECT
+            (SELECT COUNT(*) FROM krapbott_v2.run_history h WHERE h.channel_id = $1 AND h.id > last.id) AS runs_since,
+            EXTRACT(EPOCH FROM now() - last.played_at)::BIGINT AS seconds_since
+        FROM last
+        "#,
+    ).bind(channel_id.as_str()).bind(user_id.as_str()).fetch_optional(pool).await?;
+
+    Ok(last)
+}
+
+/// Runy odehrané před tímhle okamžikem se do cooldownu nepočítají
+pub async fn clear_cooldown(pool: &PgPool, channel_id: &ChannelId, user_id: &str) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.cooldown_clears (channel_id, user_id, cleared_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT (channel_id, user_id) DO UPDATE SET cleared_at = now()
+        "#,
+    ).bind(channel_id.as_str()).bind(user_id).execute(pool).await?;
+    Ok(())
+}
@@ -0,0 +1,308 @@
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool};
+
+use crate::bot::{commands::commands::BotResult, db::ChannelId};
+
+pub const RUN_HISTORY_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.run_history (
+        id BIGSERIAL PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        queue_name TEXT NOT NULL DEFAULT 'main',
+        played_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        raffle BOOLEAN NOT NULL DEFAULT FALSE,
+        group_size INTEGER -- teamsize v době runu, pro zaplněnost skupin
+    );
+"#;
+
+pub const RUN_HISTORY_MEMBERS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.run_history_members (
+        run_id BIGINT NOT NULL REFERENCES krapbott_v2.run_history(id) ON DELETE CASCADE,
+        user_id TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        bungie_name TEXT NOT NULL,
+        had_prio BOOLEAN NOT NULL DEFAULT FALSE,
+        joined_at TIMESTAMPTZ,
+        PRIMARY KEY (run_id, user_id)
+    );
+"#;
+
+/// How far back `!history <name>` looks when answering "did they play tonight?"
+pub const HISTORY_WINDOW_HOURS: i64 = 12;
+
+/// Kolik posledních runů se bere do průměrné délky runu
+pub const RUN_PACE_SAMPLE: i64 = 10;
+/// Delší mezera mezi runy = pauza nebo konec streamu, do průměru se nepočítá
+pub const RUN_PACE_MAX_GAP_MINUTES: i64 = 90;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunMember {
+    pub user_id: String,
+    pub display_name: String,
+    pub bungie_name: String,
+    pub had_prio: bool,
+    /// Unix timestamp (seconds) of the original `!join`
+    pub joined_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunRecord {
+    pub id: i64,
+    /// Unix timestamp (seconds)
+    pub played_at: i64,
+    pub queue_name: String,
+    pub raffle: bool,
+    pub members: Vec<RunMember>,
+}
+
+#[derive(sqlx::FromRow)]
+struct RunMemberRow {
+    run_id: i64,
+    played_at: i64,
+    queue_name: String,
+    raffle: bool,
+    user_id: String,
+    display_name: String,
+    bungie_name: String,
+    had_prio: bool,
+    joined_at: Option<i64>,
+}
+
+/// Zapíše odehranou skupinu. Volá se uvnitř transakce `!next` / raffle losování.
+pub async fn record_run(conn: &mut PgConnection, channel_id: &ChannelId, queue: &str, members: &[RunMember], raffle: bool, group_size: i64) -> BotResult<Option<i64>> {
+    if members.is_empty() {
+        return Ok(None);
+    }
+
+    let run_id: i64 = sqlx::query_scalar(
+        "INSERT INTO krapbott_v2.run_history (channel_id, queue_name, raffle, group_size) VALUES ($1, $2, $3, $4) RETURNING id",
+    ).bind(channel_id.as_str()).bind(queue).bind(raffle).bind(group_size as i32).fetch_one(&mut *conn).await?;
+
+    let user_ids: Vec<String> = members.iter().map(|m| m.user_id.clone()).collect();
+    let display_names: Vec<String> = members.iter().map(|m| m.display_name.clone()).collect();
+    let bungie_names: Vec<String> = members.iter().map(|m| m.bungie_name.clone()).collect();
+    let prio: Vec<bool> = members.iter().map(|m| m.had_prio).collect();
+    let joined: Vec<Option<i64>> = members.iter().map(|m| m.joined_at).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.run_history_members (run_id, user_id, display_name, bungie_name, had_prio, joined_at)
+        SELECT $1, u, d, b, p, to_timestamp(j)
+        FROM UNNEST($2::text[], $3::text[], $4::text[], $5::bool[], $6::bigint[]) AS t(u, d, b, p, j)
+        ON CONFLICT DO NOTHING
+        "#,
+    ).bind(run_id).bind(&user_ids).bind(&display_names).bind(&bungie_names).bind(&prio).bind(&joined).execute(&mut *conn).await?;
+
+    Ok(Some(run_id))
+}
+
+fn group_runs(rows: Vec<RunMemberRow>) -> Vec<RunRecord> {
+    let mut runs: Vec<RunRecord> = Vec::new();
+    for row in rows {
+        let member = RunMember {
+            user_id: row.user_id,
+            display_name: row.display_name,
+            bungie_name: row.bungie_name,
+            had_prio: row.had_prio,
+            joined_at: row.joined_at,
+        };
+        match runs.last_mut() {
+            Some(run) if run.id == row.run_id => run.members.push(member),
+            _ => runs.push(RunRecord {
+                id: row.run_id,
+                played_at: row.played_at,
+                queue_name: row.queue_name,
+                raffle: row.raffle,
+                members: vec![member],
+            }),
+        }
+    }
+    runs
+}
+
+/// Kanály, kde hráč někdy hrál (kam hlásit změnu jeho Bungie jména)
+pub async fn fetch_played_channels(pool: &PgPool, user_id: &str) -> BotResult<Vec<ChannelId>> {
+    let channels = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT r.channel_id
+        FROM krapbott_v2.run_history r
+        JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+        WHERE m.user_id = $1
+        "#,
+    ).bind(user_id).fetch_all(pool).await?;
+    Ok(channels)
+}
+
+/// Posledních `limit` odehraných skupin, nejnovější první
+pub async fn fetch_recent_runs(pool: &PgPool, channel_id: &ChannelId, limit: i64) -> BotResult<Vec<RunRecord>> {
+    let rows = sqlx::query_as::<_, RunMemberRow>(
+        r#"
+        WITH runs AS (
+            SELECT id, played_at, queue_name, raffle
+            FROM krapbott_v2.run_history
+            WHERE channel_id = $1
+            ORDER BY played_at DESC, id DESC
+            LIMIT $2
+        )
+        SELECT r.id AS run_id,
+               EXTRACT(EPOCH FROM r.played_at)::BIGINT AS played_at,
+               r.queue_name,
+               r.raffle,
+               m.user_id, m.display_name, m.bungie_name, m.had_prio,
+               EXTRACT(EPOCH FROM m.joined_at)::BIGINT AS joined_at
+        FROM runs r
+        JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+        ORDER BY r.played_at DESC, r.id DESC, m.display_name ASC
+        "#,
+    ).bind(channel_id.as_str()).bind(limit).fetch_all(pool).await?;
+
+    Ok(group_runs(rows))
+}
+
+/// Runs a viewer took part in during the last `hours`, matched by user id, display name or Bungie name.
+/// Every run comes back with its whole group, not just the viewer.
+pub async fn fetch_viewer_runs(pool: &PgPool, channel_id: &ChannelId, viewer: &str, hours: i64) -> BotResult<Vec<RunRecord>> {
+    let rows = sqlx::query_as::<_, RunMemberRow>(
+        r#"
+        WITH runs AS (
+            SELECT DISTINCT r.id, r.played_at, r.queue_name, r.raffle
+            FROM krapbott_v2.run_history r
+            JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+            WHERE r.channel_id = $1
+              AND r.played_at > NOW() - make_interval(hours => $3::int)
+              AND (m.user_id = $2 OR LOWER(m.display_name) = LOWER($2) OR LOWER(m.bungie_name) = LOWER($2))
+        )
+        SELECT r.id AS run_id,
+               EXTRACT(EPOCH FROM r.played_at)::BIGINT AS played_at,
+               r.queue_name,
+               r.raffle,
+               m.user_id, m.display_name, m.bungie_name, m.had_prio,
+               EXTRACT(EPOCH FROM m.joined_at)::BIGINT AS joined_at
+        FROM runs r
+        JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+        ORDER BY r.played_at DESC, r.id DESC, m.display_name ASC
+        "#,
+    ).bind(channel_id.as_str()).bind(viewer).bind(hours as i32).fetch_all(pool).await?;
+
+    Ok(group_runs(rows))
+}
+
+/// Tempo runů: průměrná délka runu a kolik uběhlo od posledního `!next`
+#[derive(Debug, Clone, Default, sqlx::FromRow)]
+pub struct RunPace {
+    pub avg_seconds: Option<i64>,
+    pub since_last: Option<i64>,
+}
+
+impl RunPace {
+    /// Seconds until the group at 1-based `position` goes live. `None` for the live group
+    /// or when there is not enough history yet.
+    pub fn eta_seconds(&self, position: i64, teamsize: i64) -> Option<i64> {
+        let group = (position - 1) / teamsize.max(1) + 1;
+        if group <= 1 {
+            return None;
+        }
+        let avg = self.avg_seconds?;
+        let elapsed = self.since_last.filter(|s| *s < RUN_PACE_MAX_GAP_MINUTES * 60).unwrap_or(0);
+        Some(((group - 1) * avg - elapsed).max(0))
+    }
+}
+
+/// Rolling average over the last `RUN_PACE_SAMPLE` runs, ignoring breaks between streams
+pub async fn fetch_run_pace(pool: &PgPool, channel_id: &ChannelId, queue: &str) -> BotResult<RunPace> {
+    let pace = sqlx::query_as::<_, RunPace>(
+        r#"
+        WITH last AS (
+            SELECT played_at
+            FROM krapbott_v2.run_history
+            WHERE channel_id = $1 AND queue_name = $4
+            ORDER BY played_at DESC
+            LIMIT $2 + 1
+        ), gaps AS (
+            SELECT played_at, played_at - LAG(played_at) OVER (ORDER BY played_at) AS gap
+            FROM last
+        )
+        SELECT EXTRACT(EPOCH FROM AVG(gap) FILTER (WHERE gap < make_interval(mins => $3::int)))::BIGINT AS avg_seconds,
+               EXTRACT(EPOCH FROM NOW() - MAX(played_at))::BIGINT AS since_last
+        FROM gaps
+        "#,
+    ).bind(channel_id.as_str()).bind(RUN_PACE_SAMPLE).bind(RUN_PACE_MAX_GAP_MINUTES as i32).bind(queue).fetch_one(pool).await?;
+
+    Ok(pace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, db::initialize_database, state::def::MAIN_QUEUE};
+
+    fn member(name: &str, had_prio: bool) -> RunMember {
+        RunMember {
+            user_id: format!("twitch:{name}"),
+            display_name: name.to_string(),
+            bungie_name: format!("{name}#0001"),
+            had_prio,
+            joined_at: Some(1_700_000_000),
+        }
+    }
+
+    #[sqlx::test]
+    async fn recorded_runs_come_back_newest_first(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "history_test");
+        let mut conn = pool.acquire().await.unwrap();
+
+        assert_eq!(record_run(&mut conn, &owner, MAIN_QUEUE, &[], false, 2).await.unwrap(), None);
+        let first = record_run(&mut conn, &owner, MAIN_QUEUE, &[member("b", false), member("a", true)], false, 2).await.unwrap();
+        let second = record_run(&mut conn, &owner, "raid", &[member("c", false)], true, 2).await.unwrap();
+
+        let runs = fetch_recent_runs(&pool, &owner, 10).await.unwrap();
+        assert_eq!(runs.iter().map(|r| Some(r.id)).collect::<Vec<_>>(), [second, first]);
+        assert!(runs[0].raffle && runs[0].queue_name == "raid");
+        let names: Vec<_> = runs[1].members.iter().map(|m| (m.display_name.as_str(), m.had_prio, m.joined_at)).collect();
+        assert_eq!(names, [("a", true, Some(1_700_000_000)), ("b", false, Some(1_700_000_000))]);
+
+        // Hledání podle jednoho hráče vrátí celou jeho skupinu
+        let played = fetch_viewer_runs(&pool, &owner, "B", 24).await.unwrap();
+        assert_eq!(played.len(), 1);
+        assert_eq!(played[0].members.iter().map(|m| m.display_name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+    }
+
+    #[test]
+    fn eta_skips_the_live_group() {
+        let pace = RunPace { avg_seconds: Some(600), since_last: Some(0) };
+        assert_eq!(pace.eta_seconds(1, 3), None);
+        assert_eq!(pace.eta_seconds(3, 3), None);
+        assert_eq!(pace.eta_seconds(4, 3), Some(600));
+        assert_eq!(pace.eta_seconds(7, 3), Some(1200));
+    }
+
+    #[test]
+    fn eta_subtracts_time_since_the_last_run() {
+        let pace = RunPace { avg_seconds: Some(600), since_last: Some(900) };
+        assert_eq!(pace.eta_seconds(4, 3), Some(0));
+        assert_eq!(pace.eta_seconds(7, 3), Some(300));
+        // Po pauze delší než RUN_PACE_MAX_GAP_MINUTES se čas od posledního runu nepočítá
+        let after_break = RunPace { since_last: Some(RUN_PACE_MAX_GAP_MINUTES * 60), ..pace };
+        assert_eq!(after_break.eta_seconds(4, 3), Some(600));
+    }
+
+    #[test]
+    fn eta_needs_history() {
+        assert_eq!(RunPace::default().eta_seconds(10, 2), None);
+    }
+
+    #[sqlx::test]
+    async fn run_pace_ignores_breaks_and_other_queues(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "pace_test");
+        for (queue, minutes_ago) in [(MAIN_QUEUE, 300), (MAIN_QUEUE, 50), (MAIN_QUEUE, 30), (MAIN_QUEUE, 10), ("raid", 5)] {
+            sqlx::query("INSERT INTO krapbott_v2.run_history (channel_id, queue_name, played_at) VALUES ($1, $2, NOW() - make_interval(mins => $3))")
+                .bind(owner.as_str()).bind(queue).bind(minutes_ago).execute(&pool).await.unwrap();
+        }
+
+        let pace = fetch_run_pace(&pool, &owner, MAIN_QUEUE).await.unwrap();
+        assert_eq!(pace.avg_seconds, Some(1200));
+        assert!(pace.since_last.is_some_and(|s| (600..605).contains(&s)));
+        assert!(pace.eta_seconds(5, 2).is_some_and(|s| (1795..=1800).contains(&s)));
+    }
+}
@@ -0,0 +1,41 @@
+use sqlx::PgPool;
+
+use crate::bot::commands::commands::BotResult;
+
+/// Zmenšené tabulky z Destiny manifestu (jen co používáme), jeden řádek na tabulku
+pub const DESTINY_MANIFEST_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.destiny_manifest (
+        component TEXT PRIMARY KEY,
+        version TEXT NOT NULL,
+        content TEXT NOT NULL,
+        fetched_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    );
+"#;
+
+/// Tabulka pro danou verzi manifestu, `None` pokud je uložená jiná verze nebo žádná
+pub async fn load_manifest_component(pool: &PgPool, component: &str, version: &str) -> BotResult<Option<String>> {
+    let content = sqlx::query_scalar(
+        "SELECT content FROM krapbott_v2.destiny_manifest WHERE component = $1 AND version = $2",
+    ).bind(component).bind(version).fetch_optional(pool).await?;
+    Ok(content)
+}
+
+/// Poslední uložená verze bez ohledu na aktuální manifest, když Bungie neodpovídá
+pub async fn load_latest_manifest_component(pool: &PgPool, component: &str) -> BotResult<Option<(String, String)>> {
+    let row = sqlx::query_as(
+        "SELECT version, content FROM krapbott_v2.destiny_manifest WHERE component = $1",
+    ).bind(component).fetch_optional(pool).await?;
+    Ok(row)
+}
+
+pub async fn save_manifest_component(pool: &PgPool, component: &str, version: &str, content: &str) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.destiny_manifest (component, version, content, fetched_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (component) DO UPDATE
+        SET version = EXCLUDED.version, content = EXCLUDED.content, fetched_at = EXCLUDED.fetched_at
+        "#,
+    ).bind(component).bind(version).bind(content).execute(pool).await?;
+    Ok(())
+}
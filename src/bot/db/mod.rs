@@ -4,13 +4,24 @@ use std::{error::Error, str::FromStr};
 use serde::{Deserialize, Serialize};
 use sqlx::{Decode, Encode, PgPool, Postgres, Type, encode::IsNull, postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef}};
 
-use crate::bot::{chat_event::chat_event::Platform};
+use crate::bot::{chat_event::chat_event::Platform, db::{aliases::{COMMAND_ALIASES, COMMAND_ALIASES_REMOVALS, COMMAND_DISABLED}, config::CONFIG_TABLE, users::{SESSIONS_TABLE, USERS_TABLE}, history::{RUN_HISTORY_MEMBERS_TABLE, RUN_HISTORY_TABLE}, raffle::RAFFLE_ENTRANTS_TABLE, snapshots::QUEUE_SNAPSHOTS_TABLE, cooldown::COOLDOWN_CLEARS_TABLE, queue::{BAN_TABLE, LEGACY_BAN_MIGRATION, QUEUE_TABLE}, points::POINTS_TABLE, sub_prio::PENDING_PRIO_TABLE, twitch_tokens::TWITCH_TOKENS_TABLE, manifest::DESTINY_MANIFEST_TABLE, bungie::BUNGIE_MEMBERSHIPS_TABLE}};
 
 pub mod users;
 pub mod queue;
 pub mod aliases;
 pub mod bungie;
 pub mod config;
+pub mod history;
+pub mod raffle;
+pub mod snapshots;
+pub mod cooldown;
+pub mod transfer;
+pub mod party;
+pub mod points;
+pub mod sub_prio;
+pub mod twitch_tokens;
+pub mod stats;
+pub mod manifest;
 
 
         
@@ -19,6 +30,11 @@ pub async fn initialize_database(pool: &PgPool) -> Result<(), sqlx::Error> {
     sqlx::query("CREATE SCHEMA IF NOT EXISTS  krapbott_v2;").execute(pool).await?;
     sqlx::query("SET search_path TO krapbott_v2;").execute(pool).await?;
 
+    // Základní tabulky, v produkci už existují, čistá databáze (testy) je potřebuje
+    for table in [QUEUE_TABLE, USERS_TABLE, SESSIONS_TABLE, COMMAND_ALIASES, COMMAND_DISABLED, COMMAND_ALIASES_REMOVALS, CONFIG_TABLE] {
+        sqlx::query(table).execute(pool).await?;
+    }
+
     sqlx::query(
         r#"
         ALTER TABLE IF EXISTS krapbott_v2.queue
@@ -53,6 +69,85 @@ pub async fn initialize_database(pool: &PgPool) -> Result<(), sqlx::Error> {
         ON krapbott_v2.sessions (platform, platform_user_id);
         "#
     ).execute(pool).await?;
+
+    sqlx::query(RUN_HISTORY_TABLE).execute(pool).await?;
+    sqlx::query(RUN_HISTORY_MEMBERS_TABLE).execute(pool).await?;
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_run_history_channel_played
+        ON krapbott_v2.run_history (channel_id, played_at DESC);
+        "#
+    ).execute(pool).await?;
+    sqlx::query("ALTER TABLE krapbott_v2.run_history ADD COLUMN IF NOT EXISTS group_size INTEGER;").execute(pool).await?;
+    // Kdy se naposledy kontrolovalo přejmenování na Bungie
+    sqlx::query("ALTER TABLE krapbott_v2.streamusers ADD COLUMN IF NOT EXISTS bungie_checked_at TIMESTAMPTZ;").execute(pool).await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE krapbott_v2.queue
+        ADD COLUMN IF NOT EXISTS subscriber BOOLEAN NOT NULL DEFAULT FALSE,
+        ADD COLUMN IF NOT EXISTS vip BOOLEAN NOT NULL DEFAULT FALSE,
+        ADD COLUMN IF NOT EXISTS joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        ADD COLUMN IF NOT EXISTS queue_name TEXT NOT NULL DEFAULT 'main',
+        ADD COLUMN IF NOT EXISTS party_id TEXT,
+        ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
+        "#
+    ).execute(pool).await?;
+
+    // Pojmenované fronty: primární klíč musí obsahovat i queue_name
+    sqlx::query(
+        r#"
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM information_schema.key_column_usage
+                WHERE table_schema = 'krapbott_v2' AND table_name = 'queue'
+                  AND constraint_name = 'queue_pkey' AND column_name = 'queue_name'
+            ) THEN
+                ALTER TABLE krapbott_v2.queue DROP CONSTRAINT IF EXISTS queue_pkey;
+                ALTER TABLE krapbott_v2.queue ADD CONSTRAINT queue_pkey PRIMARY KEY (channel_id, queue_name, position);
+            END IF;
+        END $$;
+        "#
+    ).execute(pool).await?;
+    sqlx::query(RAFFLE_ENTRANTS_TABLE).execute(pool).await?;
+    // Prohry a bonusy platí jen pro frontu, ve které se losovalo
+    sqlx::query("ALTER TABLE krapbott_v2.raffle_entrants ADD COLUMN IF NOT EXISTS queue_name TEXT NOT NULL DEFAULT 'main';").execute(pool).await?;
+    sqlx::query(
+        r#"
+        DO $$
+        BEGIN
+            IF NOT EXISTS (
+                SELECT 1 FROM information_schema.key_column_usage
+                WHERE table_schema = 'krapbott_v2' AND table_name = 'raffle_entrants'
+                  AND constraint_name = 'raffle_entrants_pkey' AND column_name = 'queue_name'
+            ) THEN
+                ALTER TABLE krapbott_v2.raffle_entrants DROP CONSTRAINT IF EXISTS raffle_entrants_pkey;
+                ALTER TABLE krapbott_v2.raffle_entrants ADD CONSTRAINT raffle_entrants_pkey PRIMARY KEY (channel_id, queue_name, user_id);
+            END IF;
+        END $$;
+        "#
+    ).execute(pool).await?;
+    sqlx::query(QUEUE_SNAPSHOTS_TABLE).execute(pool).await?;
+    sqlx::query(
+        r#"
+        ALTER TABLE krapbott_v2.queue_snapshots
+        ADD COLUMN IF NOT EXISTS run_id BIGINT,
+        ADD COLUMN IF NOT EXISTS run_counted BOOLEAN NOT NULL DEFAULT FALSE,
+        ADD COLUMN IF NOT EXISTS draw_users TEXT[],
+        ADD COLUMN IF NOT EXISTS draw_before JSONB,
+        ADD COLUMN IF NOT EXISTS queue_settings TEXT,
+        ADD COLUMN IF NOT EXISTS entries_after JSONB;
+        "#
+    ).execute(pool).await?;
+    sqlx::query(COOLDOWN_CLEARS_TABLE).execute(pool).await?;
+    sqlx::query(BAN_TABLE).execute(pool).await?;
+    sqlx::query(POINTS_TABLE).execute(pool).await?;
+    sqlx::query(PENDING_PRIO_TABLE).execute(pool).await?;
+    sqlx::query(TWITCH_TOKENS_TABLE).execute(pool).await?;
+    sqlx::query(DESTINY_MANIFEST_TABLE).execute(pool).await?;
+    sqlx::query(BUNGIE_MEMBERSHIPS_TABLE).execute(pool).await?;
+    sqlx::query(LEGACY_BAN_MIGRATION).execute(pool).await?;
     Ok(())
 }
 impl Type<Postgres> for Platform {
@@ -0,0 +1,288 @@
+use std::collections::{HashMap, VecDeque};
+
+use sqlx::{PgConnection, PgExecutor, PgPool};
+
+use crate::bot::{commands::{commands::BotResult, queue::{logic::QueueEntry, tags::GroupBalance}}, db::{ChannelId, queue::{begin_queue_tx, bungie_name_exists_in_queue}, snapshots::{seal_snapshot, snapshot_queue}}, replies::Replies, state::def::QueueSettings};
+
+/// Označí hráče ve frontě jako člena party (party_id = user_id zakladatele)
+pub async fn set_party<'e>(executor: impl PgExecutor<'e>, owner: &ChannelId, queue: &str, user_id: &str, party_id: Option<&str>) -> BotResult<()> {
+    sqlx::query("UPDATE krapbott_v2.queue SET party_id = $4 WHERE channel_id = $1 AND queue_name = $2 AND user_id = $3")
+        .bind(owner.as_str()).bind(queue).bind(user_id).bind(party_id).execute(executor).await?;
+    Ok(())
+}
+
+/// Přidá přijatého člena hned za party. Vrací odpověď do chatu.
+pub async fn add_party_member(pool: &PgPool, owner: &ChannelId, queue: &str, user: &QueueEntry, party_id: &str, leader: &str, settings: &QueueSettings) -> BotResult<String> {
+    let teamsize = settings.teamsize;
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "party").await?;
+
+    let members: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND party_id = $3",
+    ).bind(owner.as_str()).bind(queue).bind(party_id).fetch_one(&mut *tx).await?;
+    if members == 0 {
+        return Ok(Replies::party_gone(&user.display_name));
+    }
+    if members as usize >= teamsize {
+        return Ok(Replies::party_full(&user.display_name, teamsize));
+    }
+
+    // Aktuální skupina už hraje: kdo v ní je s party, jen se označí, rozdělit party do dvou skupin nejde
+    let position: Option<i32> = sqlx::query_scalar(
+        "SELECT position FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND user_id = $3",
+    ).bind(owner.as_str()).bind(queue).bind(user.user_id.as_str()).fetch_optional(&mut *tx).await?;
+    let party_live: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND party_id = $3 AND position <= $4)",
+    ).bind(owner.as_str()).bind(queue).bind(party_id).bind(teamsize as i32).fetch_one(&mut *tx).await?;
+    let user_live = position.is_some_and(|p| p <= teamsize as i32);
+    if party_live != user_live {
+        return Ok(Replies::party_playing(&user.display_name, leader));
+    }
+    if user_live {
+        set_party(&mut *tx, owner, queue, user.user_id.as_str(), Some(party_id)).await?;
+        seal_snapshot(&mut tx, snapshot).await?;
+        tx.commit().await?;
+        return Ok(Replies::party_joined(&user.display_name, leader));
+    }
+
+    // Kdo už ve frontě je, se jen přesune k party
+    if position.is_some() {
+        sqlx::query("DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND user_id = $3")
+            .bind(owner.as_str()).bind(queue).bind(user.user_id.as_str()).execute(&mut *tx).await?;
+    } else {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+            .bind(owner.as_str()).bind(queue).fetch_one(&mut *tx).await?;
+        if count >= settings.size as i64 {
+            return Ok(format!("❌ {}, you can't enter the queue, it is full", user.display_name));
+        }
+        if bungie_name_exists_in_queue(&mut tx, &user.bungie_name, owner, queue).await? {
+            return Ok(format!("❌ {}, wishes for some jail time ⛓", user.display_name));
+        }
+    }
+
+    let last: i32 = sqlx::query_scalar(
+        "SELECT MAX(position) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND party_id = $3",
+    ).bind(owner.as_str()).bind(queue).bind(party_id).fetch_one(&mut *tx).await?;
+
+    // Uvolní místo za party, nejdřív za aktuální skupinou (přes záporné pozice kvůli primárnímu klíči)
+    let insert_at = (last + 1).max(teamsize as i32 + 1);
+    sqlx::query("UPDATE krapbott_v2.queue SET position = -(position + 1) WHERE channel_id = $1 AND queue_name = $2 AND position >= $3")
+        .bind(owner.as_str()).bind(queue).bind(insert_at).execute(&mut *tx).await?;
+    sqlx::query("UPDATE krapbott_v2.queue SET position = -position WHERE channel_id = $1 AND queue_name = $2 AND position < 0")
+        .bind(owner.as_str()).bind(queue).execute(&mut *tx).await?;
+
+    sqlx::query(
+        "INSERT INTO krapbott_v2.queue (position, user_id, bungie_name, display_name, channel_id, queue_name, party_id, tags) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    ).bind(insert_at).bind(user.user_id.as_str()).bind(&user.bungie_name).bind(&user.display_name).bind(owner.as_str()).bind(queue).bind(party_id).bind(&user.tags).execute(&mut *tx).await?;
+
+    // Aktuální skupina už hraje, skládá se jen zbytek fronty
+    pack_groups(&mut tx, owner, queue, teamsize, teamsize, &settings.balance).await?;
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
+
+    Ok(Replies::party_joined(&user.display_name, leader))
+}
+
+/// Přeskládá frontu za prvními `fixed` hráči tak, aby žádná party nepřesahovala hranici skupiny
+/// a skupiny dodržely limity tagů z `balance`.
+/// Volné místo ve skupině zaplní další hráči, kteří se tam vejdou. Prio a zamčení hráči zůstávají na svém místě.
+pub async fn pack_groups(conn: &mut PgConnection, owner: &ChannelId, queue: &str, fixed: usize, teamsize: usize, balance: &GroupBalance) -> BotResult<()> {
+    let rows: Vec<PackRow> = sqlx::query_as(
+        r#"
+        SELECT user_id, party_id, tags,
+               (COALESCE(locked_first, FALSE) OR COALESCE(group_priority, 2) = 1 OR COALESCE(priority_runs_left, 0) > 0) AS pinned
+        FROM krapbott_v2.queue
+        WHERE channel_id = $1 AND queue_name = $2
+        ORDER BY position ASC
+        "#,
+    ).bind(owner.as_str()).bind(queue).fetch_all(&mut *conn).await?;
+
+    let fixed = fixed.min(rows.len());
+    let mut rest = rows;
+    let live: Vec<String> = rest.drain(..fixed).map(|row| row.user_id).collect();
+    if !balance.is_enabled() && rest.iter().all(|row| row.party_id.is_none()) {
+        return Ok(());
+    }
+
+    let mut order = live;
+    order.extend(pack_order(rest, teamsize, balance));
+    sqlx::query("UPDATE krapbott_v2.queue SET position = -position WHERE channel_id = $1 AND queue_name = $2")
+        .bind(owner.as_str()).bind(queue).execute(&mut *conn).await?;
+    sqlx::query(
+        "UPDATE krapbott_v2.queue q
+         SET position = o.new_position
+         FROM UNNEST($3::text[]) WITH ORDINALITY AS o(user_id, new_position)
+         WHERE q.channel_id = $1 AND q.queue_name = $2 AND q.user_id = o.user_id",
+    ).bind(owner.as_str()).bind(queue).bind(&order).execute(&mut *conn).await?;
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct PackRow {
+    user_id: String,
+    party_id: Option<String>,
+    tags: Vec<String>,
+    /// Prio nebo zamčený hráč, se kterým se nehýbe
+    pinned: bool,
+}
+
+/// Blok hráčů, který jde do skupiny celý (party nebo jeden hráč)
+#[derive(Default)]
+struct Unit {
+    members: Vec<String>,
+    tags: Vec<String>,
+}
+
+/// Party drží pohromadě jako jeden blok, bloky se skládají do skupin po `teamsize` kolem připnutých hráčů.
+/// Když už nikdo další limity tagů nesplní, skupina se doplní i tak, ať nezůstane poloprázdná,
+/// a když se do zbylých míst nevejde žádná celá party, doplní se jejími prvními členy.
+fn pack_order(rows: Vec<PackRow>, teamsize: usize, balance: &GroupBalance) -> Vec<String> {
+    let teamsize = teamsize.max(1);
+    let mut slots: Vec<Option<String>> = vec![None; rows.len()];
+    let mut pinned_tags: HashMap<usize, Vec<String>> = HashMap::new();
+    let mut units: Vec<(Option<String>, Unit)> = Vec::new();
+
+    for (i, row) in rows.into_iter().enumerate() {
+        if row.pinned {
+            slots[i] = Some(row.user_id);
+            pinned_tags.entry(i / teamsize).or_default().extend(row.tags);
+            continue;
+        }
+        match units.iter_mut().find(|(p, _)| row.party_id.is_some() && *p == row.party_id) {
+            Some((_, unit)) => {
+                unit.members.push(row.user_id);
+                unit.tags.extend(row.tags);
+            }
+            None => units.push((row.party_id, Unit { members: vec![row.user_id], tags: row.tags })),
+        }
+    }
+
+    let mut pending: VecDeque<Unit> = units.into_iter().map(|(_, unit)| unit).collect();
+    let mut group = 0;
+    while !pending.is_empty() {
+        let range = group * teamsize..((group + 1) * teamsize).min(slots.len());
+        let mut free: VecDeque<usize> = range.filter(|i| slots[*i].is_none()).collect();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for tag in pinned_tags.remove(&group).unwrap_or_default() {
+            *counts.entry(tag).or_default() += 1;
+        }
+
+        while let Some(i) = pending.iter().position(|u| u.members.len() <= free.len() && balance.allows(&counts, &u.tags)) {
+            place(pending.remove(i).unwrap_or_default(), &mut counts, &mut slots, &mut free);
+        }
+        while let Some(i) = pending.iter().position(|u| u.members.len() <= free.len()) {
+            place(pending.remove(i).unwrap_or_default(), &mut counts, &mut slots, &mut free);
+        }
+        // Větší party než zbylé místo: skupina se doplní jejími prvními členy, zbytek jde do další
+        if !free.is_empty() {
+            if let Some(mut unit) = pending.pop_front() {
+                let rest = unit.members.split_off(free.len().min(unit.members.len()));
+                place(Unit { members: unit.members, tags: Vec::new() }, &mut counts, &mut slots, &mut free);
+                if !rest.is_empty() {
+                    pending.push_front(Unit { members: rest, tags: unit.tags });
+                }
+            }
+        }
+        group += 1;
+    }
+    slots.into_iter().flatten().collect()
+}
+
+/// Zařadí blok na volná místa skupiny
+fn place(unit: Unit, counts: &mut HashMap<String, usize>, slots: &mut [Option<String>], free: &mut VecDeque<usize>) {
+    for tag in unit.tags {
+        *counts.entry(tag).or_default() += 1;
+    }
+    for member in unit.members {
+        if let Some(i) = free.pop_front() {
+            slots[i] = Some(member);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, commands::queue::logic::Queue, db::{UserId, initialize_database, queue::add_to_queue, snapshots::restore_last_snapshot}, state::def::MAIN_QUEUE};
+
+    fn row(user_id: &str, party_id: Option<&str>, pinned: bool) -> PackRow {
+        PackRow { user_id: user_id.to_string(), party_id: party_id.map(String::from), tags: Vec::new(), pinned }
+    }
+
+    fn entry(name: &str) -> QueueEntry {
+        QueueEntry {
+            user_id: UserId::new(Platform::Twitch, name),
+            bungie_name: format!("{name}#0001"),
+            display_name: name.to_string(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn pinned_rows_keep_their_place() {
+        let rows = vec![
+            row("prio", None, true),
+            row("a", None, false),
+            row("leader", Some("leader"), false),
+            row("b", None, false),
+            row("member", Some("leader"), false),
+        ];
+        let order = pack_order(rows, 2, &GroupBalance::default());
+        assert_eq!(order, ["prio", "a", "leader", "member", "b"]);
+    }
+
+    #[test]
+    fn oversized_party_fills_the_group_without_gaps() {
+        let rows = vec![
+            row("a", None, false),
+            row("p1", Some("p1"), false),
+            row("p2", Some("p1"), false),
+            row("p3", Some("p1"), false),
+        ];
+        let order = pack_order(rows, 2, &GroupBalance::default());
+        assert_eq!(order, ["a", "p1", "p2", "p3"]);
+    }
+
+    async fn setup_party(pool: &PgPool, owner: &ChannelId, names: &[&str]) -> UserId {
+        initialize_database(pool).await.unwrap();
+        for name in names {
+            add_to_queue(100, pool, &entry(name), owner, MAIN_QUEUE, Queue::Join, false).await.unwrap();
+        }
+        let leader = UserId::new(Platform::Twitch, "leader");
+        set_party(pool, owner, MAIN_QUEUE, leader.as_str(), Some(leader.as_str())).await.unwrap();
+        leader
+    }
+
+    async fn order(pool: &PgPool, owner: &ChannelId) -> Vec<String> {
+        sqlx::query_scalar(
+            "SELECT display_name FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 ORDER BY position",
+        ).bind(owner.as_str()).bind(MAIN_QUEUE).fetch_all(pool).await.unwrap()
+    }
+
+    fn teamsize_3() -> QueueSettings {
+        serde_json::from_value(serde_json::json!({ "size": 100, "teamsize": 3 })).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn accept_waits_while_the_party_plays(pool: PgPool) {
+        let owner = ChannelId::new(Platform::Twitch, "party_live");
+        let leader = setup_party(&pool, &owner, &["v1", "leader", "v3", "v4", "v5"]).await;
+
+        let reply = add_party_member(&pool, &owner, MAIN_QUEUE, &entry("friend"), leader.as_str(), "leader", &teamsize_3()).await.unwrap();
+        assert_eq!(reply, Replies::party_playing("friend", "leader"));
+        assert_eq!(order(&pool, &owner).await, ["v1", "leader", "v3", "v4", "v5"]);
+    }
+
+    #[sqlx::test]
+    async fn accept_joins_the_party_group(pool: PgPool) {
+        let owner = ChannelId::new(Platform::Twitch, "party_waiting");
+        let leader = setup_party(&pool, &owner, &["v1", "v2", "v3", "leader", "v5"]).await;
+
+        add_party_member(&pool, &owner, MAIN_QUEUE, &entry("friend"), leader.as_str(), "leader", &teamsize_3()).await.unwrap();
+        assert_eq!(order(&pool, &owner).await, ["v1", "v2", "v3", "leader", "friend", "v5"]);
+
+        // Přijetí jde vrátit přes `!undo`
+        restore_last_snapshot(&pool, &owner, Some(MAIN_QUEUE)).await.unwrap();
+        assert_eq!(order(&pool, &owner).await, ["v1", "v2", "v3", "leader", "v5"]);
+    }
+}
@@ -0,0 +1,86 @@
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, UserId}};
+
+pub const POINTS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.points (
+        channel_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        points BIGINT NOT NULL DEFAULT 0,
+        watch_minutes BIGINT NOT NULL DEFAULT 0,
+        PRIMARY KEY (channel_id, user_id)
+    );
+"#;
+
+/// Jedno přičtení bodů za tick
+pub struct PointsAward {
+    pub user_id: String,
+    pub display_name: String,
+    pub points: i64,
+}
+
+/// Přičte body všem najednou, `minutes` se připíše do sledovaného času
+pub async fn award_points(pool: &PgPool, channel: &ChannelId, awards: &[PointsAward], minutes: i64) -> BotResult<()> {
+    if awards.is_empty() {
+        return Ok(());
+    }
+    let user_ids: Vec<&str> = awards.iter().map(|a| a.user_id.as_str()).collect();
+    let names: Vec<&str> = awards.iter().map(|a| a.display_name.as_str()).collect();
+    let points: Vec<i64> = awards.iter().map(|a| a.points).collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.points (channel_id, user_id, display_name, points, watch_minutes)
+        SELECT $1, u, d, p, $5 FROM UNNEST($2::text[], $3::text[], $4::bigint[]) AS t(u, d, p)
+        ON CONFLICT (channel_id, user_id) DO UPDATE
+        SET points = krapbott_v2.points.points + EXCLUDED.points,
+            watch_minutes = krapbott_v2.points.watch_minutes + EXCLUDED.watch_minutes,
+            display_name = EXCLUDED.display_name
+        "#,
+    ).bind(channel.as_str()).bind(&user_ids).bind(&names).bind(&points).bind(minutes).execute(pool).await?;
+    Ok(())
+}
+
+/// Body a pořadí diváka v kanálu
+pub async fn fetch_points(pool: &PgPool, channel: &ChannelId, user_id: &UserId) -> BotResult<Option<(i64, i64)>> {
+    let row: Option<(i64, i64)> = sqlx::query_as(
+        r#"
+        SELECT points, rank FROM (
+            SELECT user_id, points, RANK() OVER (ORDER BY points DESC) AS rank
+            FROM krapbott_v2.points WHERE channel_id = $1
+        ) r WHERE user_id = $2
+        "#,
+    ).bind(channel.as_str()).bind(user_id.as_str()).fetch_optional(pool).await?;
+    Ok(row)
+}
+
+/// Přičte (nebo odečte) body, zůstatek nejde pod nulu. Vrací nový zůstatek.
+pub async fn add_points(pool: &PgPool, channel: &ChannelId, user_id: &UserId, display_name: &str, amount: i64) -> BotResult<i64> {
+    let balance: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO krapbott_v2.points (channel_id, user_id, display_name, points)
+        VALUES ($1, $2, $3, GREATEST($4, 0))
+        ON CONFLICT (channel_id, user_id) DO UPDATE
+        SET points = GREATEST(krapbott_v2.points.points + $4, 0)
+        RETURNING points
+        "#,
+    ).bind(channel.as_str()).bind(user_id.as_str()).bind(display_name).bind(amount).fetch_one(pool).await?;
+    Ok(balance)
+}
+
+/// Strhne `cost` bodů, jen pokud na ně divák má. Vrací zbytek, nebo None.
+pub async fn spend_points(pool: &PgPool, channel: &ChannelId, user_id: &UserId, cost: i64) -> BotResult<Option<i64>> {
+    let left: Option<i64> = sqlx::query_scalar(
+        "UPDATE krapbott_v2.points SET points = points - $3 WHERE channel_id = $1 AND user_id = $2 AND points >= $3 RETURNING points",
+    ).bind(channel.as_str()).bind(user_id.as_str()).bind(cost).fetch_optional(pool).await?;
+    Ok(left)
+}
+
+/// Nejlepší diváci kanálu jako (display_name, points)
+pub async fn fetch_leaderboard(pool: &PgPool, channel: &ChannelId, limit: i64) -> BotResult<Vec<(String, i64)>> {
+    let rows = sqlx::query_as::<_, (String, i64)>(
+        "SELECT display_name, points FROM krapbott_v2.points WHERE channel_id = $1 AND points > 0 ORDER BY points DESC LIMIT $2",
+    ).bind(channel.as_str()).bind(limit).fetch_all(pool).await?;
+    Ok(rows)
+}
@@ -1,6 +1,6 @@
-use sqlx::{PgPool, types::time::OffsetDateTime};
+use sqlx::{PgConnection, PgExecutor, PgPool, Postgres, Transaction, types::time::OffsetDateTime};
 
-use crate::bot::{commands::{commands::BotResult, queue::logic::{Queue, QueueEntry}}, db::{ChannelId, UserId, bungie::get_membership_id_by_user_id}, replies::Replies, state::def::ObsQueueEntry};
+use crate::bot::{commands::{commands::BotResult, queue::{logic::{Queue, QueueEntry}, tags::format_tags}}, db::{ChannelId, UserId, snapshots::{seal_snapshot, snapshot_queue}}, replies::Replies, state::def::ObsQueueEntry};
 
 pub const QUEUE_TABLE: &str = r#"
     CREATE TABLE IF NOT EXISTS krapbott_v2.queue (
@@ -9,21 +9,54 @@ pub const QUEUE_TABLE: &str = r#"
         display_name TEXT NOT NULL,
         bungie_name TEXT NOT NULL,
         channel_id TEXT NOT NULL,
+        queue_name TEXT NOT NULL DEFAULT 'main',
         group_priority INTEGER DEFAULT 2,
         locked_first BOOLEAN DEFAULT FALSE,
         priority_runs_left INTEGER DEFAULT 0,
-        PRIMARY KEY(position, channel_id)
+        subscriber BOOLEAN NOT NULL DEFAULT FALSE,
+        vip BOOLEAN NOT NULL DEFAULT FALSE,
+        joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        party_id TEXT, -- user_id zakladatele party
+        tags TEXT[] NOT NULL DEFAULT '{}', -- role / class (titan, sherpa, ...)
+        PRIMARY KEY(channel_id, queue_name, position)
     );
 "#;
 
+/// Bany do fronty, každý kanál má vlastní (starý globální `banlist` podle membership_id se převede při startu)
 pub const BAN_TABLE: &str = r#"
-    CREATE TABLE IF NOT EXISTS krapbott_v2.banlist (
-        membership_id TEXT PRIMARY KEY,
-        banned_until TIMESTAMP NULL, -- Null = permanent ban
-        reason TEXT
+    CREATE TABLE IF NOT EXISTS krapbott_v2.queue_bans (
+        channel_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        display_name TEXT NOT NULL,
+        banned_until TIMESTAMPTZ NULL, -- Null = permanent ban
+        reason TEXT,
+        banned_by TEXT NOT NULL,
+        banned_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        PRIMARY KEY (channel_id, user_id)
     );
 "#;
 
+/// Jednorázově převede starý globální `banlist` (podle membership_id) na bany v každém kanálu.
+/// Prošlé timeouty se zahodí, převedená tabulka se přejmenuje na `banlist_migrated`.
+pub const LEGACY_BAN_MIGRATION: &str = r#"
+    DO $$
+    BEGIN
+        IF to_regclass('krapbott_v2.banlist') IS NOT NULL THEN
+            INSERT INTO krapbott_v2.queue_bans (channel_id, user_id, display_name, banned_until, reason, banned_by)
+            SELECT c.channel_id, s.id, s.display_name, b.banned_until::timestamptz, b.reason, 'banlist'
+            FROM krapbott_v2.banlist b
+            JOIN krapbott_v2.streamusers s
+              ON s.membership_id = b.membership_id
+              OR s.id IN (SELECT m.user_id FROM krapbott_v2.bungie_memberships m WHERE m.membership_id = b.membership_id)
+            CROSS JOIN krapbott_v2.channel_config c
+            WHERE b.banned_until IS NULL OR b.banned_until::timestamptz > now()
+            ON CONFLICT (channel_id, user_id) DO NOTHING;
+
+            ALTER TABLE krapbott_v2.banlist RENAME TO banlist_migrated;
+        END IF;
+    END $$;
+"#;
+
 #[derive(Debug, Clone)]
 pub enum BanStatus {
     NotBanned,
@@ -36,49 +69,114 @@ pub enum BanStatus {
     },
 }
 
-/// Funkce pro kontrolu, zda je uživatel zabanovaný
-pub async fn is_banned_from_queue(pool: &PgPool, user_id: &UserId) -> BotResult<BanStatus> {
-    let membership_id = get_membership_id_by_user_id(pool, user_id).await?;
-    let record = sqlx::query!(
+/// Funkce pro kontrolu, zda je uživatel zabanovaný v daném kanálu
+pub async fn is_banned_from_queue(pool: &PgPool, channel_id: &ChannelId, user_id: &UserId) -> BotResult<BanStatus> {
+    let record: Option<(Option<String>, Option<OffsetDateTime>)> = sqlx::query_as(
         r#"
         SELECT reason, banned_until
-        FROM krapbott_v2.banlist
-        WHERE membership_id = $1
+        FROM krapbott_v2.queue_bans
+        WHERE channel_id = $1 AND user_id = $2
           AND (banned_until IS NULL OR banned_until > NOW())
         "#,
-        membership_id
-    )
-    .fetch_optional(pool)
-    .await?;
+    ).bind(channel_id.as_str()).bind(user_id.as_str()).fetch_optional(pool).await?;
 
-    let Some(record) = record else {
+    let Some((reason, banned_until)) = record else {
         return Ok(BanStatus::NotBanned);
     };
 
-    match record.banned_until {
-        None => Ok(BanStatus::Permanent {
-            reason: record.reason,
-        }),
+    Ok(match banned_until {
+        None => BanStatus::Permanent { reason },
+        Some(banned_until) => BanStatus::Timed { reason, banned_until },
+    })
+}
 
-        Some(naive) => {
-            let utc = naive.assume_utc();
+/// `duration_secs = None` je permanentní ban, jinak timeout
+pub async fn ban_from_queue(pool: &PgPool, channel_id: &ChannelId, user_id: &UserId, display_name: &str, duration_secs: Option<i64>, reason: Option<&str>, banned_by: &str) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.queue_bans (channel_id, user_id, display_name, banned_until, reason, banned_by)
+        VALUES ($1, $2, $3, NOW() + make_interval(secs => $4), $5, $6)
+        ON CONFLICT (channel_id, user_id) DO UPDATE
+        SET display_name = EXCLUDED.display_name,
+            banned_until = EXCLUDED.banned_until,
+            reason = EXCLUDED.reason,
+            banned_by = EXCLUDED.banned_by,
+            banned_at = NOW()
+        "#,
+    ).bind(channel_id.as_str()).bind(user_id.as_str()).bind(display_name).bind(duration_secs.map(|s| s as f64)).bind(reason).bind(banned_by).execute(pool).await?;
+    Ok(())
+}
 
-            Ok(BanStatus::Timed {
-                reason: record.reason,
-                banned_until: utc,
-            })
-        }
-    }
+/// Vrací true, pokud nějaký ban existoval
+pub async fn unban_from_queue(pool: &PgPool, channel_id: &ChannelId, user_id: &str) -> BotResult<bool> {
+    let res = sqlx::query("DELETE FROM krapbott_v2.queue_bans WHERE channel_id = $1 AND user_id = $2")
+        .bind(channel_id.as_str()).bind(user_id).execute(pool).await?;
+    Ok(res.rows_affected() > 0)
+}
+
+#[derive(Debug, Clone, serde::Serialize, sqlx::FromRow)]
+pub struct QueueBan {
+    pub user_id: String,
+    pub display_name: String,
+    pub reason: Option<String>,
+    /// Unix timestamp (seconds), None = permanent
+    pub banned_until: Option<i64>,
+    pub banned_by: String,
+    pub banned_at: i64,
+}
+
+/// Aktivní bany kanálu, prošlé timeouty se nevrací
+pub async fn list_queue_bans(pool: &PgPool, channel_id: &ChannelId) -> BotResult<Vec<QueueBan>> {
+    let bans = sqlx::query_as::<_, QueueBan>(
+        r#"
+        SELECT user_id, display_name, reason,
+               EXTRACT(EPOCH FROM banned_until)::BIGINT AS banned_until,
+               banned_by,
+               EXTRACT(EPOCH FROM banned_at)::BIGINT AS banned_at
+        FROM krapbott_v2.queue_bans
+        WHERE channel_id = $1 AND (banned_until IS NULL OR banned_until > NOW())
+        ORDER BY banned_at DESC
+        "#,
+    ).bind(channel_id.as_str()).fetch_all(pool).await?;
+    Ok(bans)
+}
+
+/// Transakce se zámkem fronty vlastníka. Všechny změny fronty jdou přes ni,
+/// jinak se souběžné !join / !leave / !next perou o stejné pozice.
+pub async fn begin_queue_tx(pool: &PgPool, owner: &ChannelId) -> BotResult<Transaction<'static, Postgres>> {
+    let mut tx = pool.begin().await?;
+    lock_queue(&mut tx, owner).await?;
+    Ok(tx)
 }
 
-//TODO PŘEDĚLAT FUNKCE NA USER_ID PODPORU a CHANNEL_ID Podporu
-pub async fn add_to_queue(queue_len: usize, pool: &PgPool, user: &QueueEntry, channel_id: &ChannelId, join_type: Queue, raffle: bool) -> BotResult<String> {
+/// Advisory zámek na frontu vlastníka, uvolní se s koncem transakce
+pub async fn lock_queue(conn: &mut PgConnection, owner: &ChannelId) -> BotResult<()> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('krapbott_v2.queue'), hashtext($1))")
+        .bind(owner.as_str()).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Přidá uživatele na konec fronty, kdo už ve frontě je, si jen aktualizuje Bungie jméno
+pub async fn add_to_queue(queue_len: usize, pool: &PgPool, user: &QueueEntry, channel_id: &ChannelId, queue: &str, join_type: Queue, raffle: bool) -> BotResult<String> {
+    let mut tx = begin_queue_tx(pool, channel_id).await?;
+    let reply = insert_into_queue(&mut tx, queue_len, user, channel_id, queue, join_type, raffle).await?;
+    tx.commit().await?;
+    Ok(reply)
+}
+
+/// `add_to_queue` uvnitř už zamčené transakce, aby šlo hráče hned dál upravit
+pub async fn insert_into_queue(tx: &mut PgConnection, queue_len: usize, user: &QueueEntry, channel_id: &ChannelId, queue: &str, join_type: Queue, raffle: bool) -> BotResult<String> {
+    if user_exists_in_queue(&mut *tx, &user.user_id, channel_id, queue).await? {
+        update_queue(tx, user, channel_id, queue).await?;
+        return Ok(format!("✅ {} has updated their bungie name to {}{}", user.display_name, user.bungie_name, format_tags(&user.tags)));
+    }
+
     match join_type {
         Queue::Join => {
             let count: i64 = sqlx::query_scalar!(
-                "SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1",
-                channel_id.as_str()
-            ).fetch_one(pool).await?.unwrap_or(0);
+                "SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2",
+                channel_id.as_str(), queue
+            ).fetch_one(&mut *tx).await?.unwrap_or(0);
 
             if count >= queue_len as i64 {
                 return Ok(if !raffle {
@@ -88,7 +186,7 @@ pub async fn add_to_queue(queue_len: usize, pool: &PgPool, user: &QueueEntry, ch
                 });
             }
 
-            if bungie_name_exists_in_queue(pool, &user.bungie_name, channel_id).await? {
+            if bungie_name_exists_in_queue(tx, &user.bungie_name, channel_id, queue).await? {
                 return Ok(format!("❌ {}, wishes for some jail time ⛓", user.display_name));
             }
         },
@@ -96,63 +194,66 @@ pub async fn add_to_queue(queue_len: usize, pool: &PgPool, user: &QueueEntry, ch
     }
 
     let next_position: i32 = sqlx::query_scalar!(
-        "SELECT COALESCE(MAX(position), 0) + 1 FROM krapbott_v2.queue WHERE channel_id = $1",
-        channel_id.as_str()
-    ).fetch_one(pool).await?.unwrap_or(1);
-
-    let result = sqlx::query(
-        "INSERT INTO krapbott_v2.queue (position, user_id, bungie_name, display_name, channel_id) VALUES ($1, $2, $3, $4, $5)",
-    ).bind(next_position).bind(user.user_id.clone()).bind(user.bungie_name.clone()).bind(user.display_name.clone()).bind(channel_id.as_str()).execute(pool).await;
-
-    match result {
-        Ok(_) => Ok(if !raffle {
-            Replies::join_added(&user.display_name, &next_position.to_string())
-        } else {
-            format!("✅ {} entered the raffle", user.display_name)
-        }),
-        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
-            Ok(format!("❌ Error Occured entering! {}", user.bungie_name))
-        }
-        Err(e) => Err(e.into())
-    }
+        "SELECT COALESCE(MAX(position), 0) + 1 FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2",
+        channel_id.as_str(), queue
+    ).fetch_one(&mut *tx).await?.unwrap_or(1);
+
+    sqlx::query(
+        "INSERT INTO krapbott_v2.queue (position, user_id, bungie_name, display_name, channel_id, queue_name, tags) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    ).bind(next_position).bind(user.user_id.clone()).bind(user.bungie_name.clone()).bind(user.display_name.clone()).bind(channel_id.as_str()).bind(queue).bind(&user.tags).execute(&mut *tx).await?;
+
+    Ok(if !raffle {
+        Replies::join_added(&user.display_name, &next_position.to_string())
+    } else {
+        format!("✅ {} entered the raffle", user.display_name)
+    })
 }
 
-pub async fn user_exists_in_queue(pool: &PgPool, user_id: &UserId, channel_id: &ChannelId) -> BotResult<bool> {
+pub async fn user_exists_in_queue<'e>(executor: impl PgExecutor<'e>, user_id: &UserId, channel_id: &ChannelId, queue: &str) -> BotResult<bool> {
     let exists = sqlx::query_scalar!(
-        "SELECT EXISTS(SELECT 1 FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2)",
-        user_id.as_str(), channel_id.as_str()
-    ).fetch_one(pool).await?.unwrap_or(false);
+        "SELECT EXISTS(SELECT 1 FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3)",
+        user_id.as_str(), channel_id.as_str(), queue
+    ).fetch_one(executor).await?.unwrap_or(false);
 
     Ok(exists)
 }
 
+/// Pozice uživatele ve frontě, `None` když ve frontě není
+pub async fn queue_position<'e>(executor: impl PgExecutor<'e>, user_id: &UserId, channel_id: &ChannelId, queue: &str) -> BotResult<Option<i32>> {
+    let position = sqlx::query_scalar(
+        "SELECT position FROM krapbott_v2.queue WHERE user_id = $1 AND channel_id = $2 AND queue_name = $3",
+    ).bind(user_id.as_str()).bind(channel_id.as_str()).bind(queue).fetch_optional(executor).await?;
+    Ok(position)
+}
+
 /// Zkontroluje, zda ve frontě existuje uživatel se stejným Bungie jménem
-pub async fn bungie_name_exists_in_queue(pool: &PgPool, bungie_name: &str, channel_id: &ChannelId) -> BotResult<bool> {
+pub async fn bungie_name_exists_in_queue(conn: &mut PgConnection, bungie_name: &str, channel_id: &ChannelId, queue: &str) -> BotResult<bool> {
     let exists: Option<bool> = sqlx::query_scalar!(
-        "SELECT EXISTS(SELECT 1 FROM krapbott_v2.queue WHERE bungie_name = $1 AND channel_id = $2)",
-        bungie_name, channel_id.as_str()
-    ).fetch_one(pool).await?;
+        "SELECT EXISTS(SELECT 1 FROM krapbott_v2.queue WHERE bungie_name = $1 AND channel_id = $2 AND queue_name = $3)",
+        bungie_name, channel_id.as_str(), queue
+    ).fetch_one(&mut *conn).await?;
 
     Ok(exists.unwrap_or(false))
 }
 
-/// Aktualizuje Bungie jméno uživatele ve frontě pro daný kanál
-pub async fn update_queue(pool: &PgPool, user: &QueueEntry, channel_id: &ChannelId) -> BotResult<()> {
+/// Aktualizuje Bungie jméno uživatele ve frontě pro daný kanál, nové tagy přepíšou staré
+pub async fn update_queue(conn: &mut PgConnection, user: &QueueEntry, channel_id: &ChannelId, queue: &str) -> BotResult<()> {
     sqlx::query(
-        "UPDATE krapbott_v2.queue SET bungie_name = $1, display_name = $2 WHERE user_id = $3 AND channel_id = $4",
-    ).bind(user.bungie_name.clone()).bind(user.display_name.clone()).bind(user.user_id.clone()).bind(channel_id.as_str()).execute(pool).await?;
+        "UPDATE krapbott_v2.queue SET bungie_name = $1, display_name = $2, tags = CASE WHEN cardinality($6::text[]) > 0 THEN $6 ELSE tags END WHERE user_id = $3 AND channel_id = $4 AND queue_name = $5",
+    ).bind(user.bungie_name.clone()).bind(user.display_name.clone()).bind(user.user_id.clone()).bind(channel_id.as_str()).bind(queue).bind(&user.tags).execute(&mut *conn).await?;
     Ok(())
 }
 
-pub async fn fetch_queue_for_owner(pool: &PgPool, owner: &ChannelId, teamsize: usize) -> BotResult<Vec<ObsQueueEntry>> {
+pub async fn fetch_queue_for_owner(pool: &PgPool, owner: &ChannelId, queue: &str) -> BotResult<Vec<ObsQueueEntry>> {
     let rows = sqlx::query!(
         r#"
-        SELECT position, display_name, bungie_name, user_id
+        SELECT position, display_name, bungie_name, user_id, party_id, tags
         FROM krapbott_v2.queue
-        WHERE channel_id = $1
+        WHERE channel_id = $1 AND queue_name = $2
         ORDER BY position ASC
         "#,
-        owner.as_str()
+        owner.as_str(),
+        queue
     ).fetch_all(pool).await?;
 
     Ok(rows
@@ -161,6 +262,122 @@ pub async fn fetch_queue_for_owner(pool: &PgPool, owner: &ChannelId, teamsize: u
             position: r.position,
             display_name: r.display_name,
             bungie_name: r.bungie_name,
-            user_id: r.user_id
+            user_id: r.user_id,
+            odds: None,
+            eta_minutes: None,
+            ready: None,
+            presence: None,
+            party: r.party_id,
+            tags: r.tags,
         }).collect())
 }
+
+/// Aktuální skupina (prvních `teamsize` lidí) jako (user_id, display_name)
+pub async fn fetch_group_members(pool: &PgPool, owner: &ChannelId, queue: &str, teamsize: i64) -> BotResult<Vec<(String, String)>> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT user_id, display_name FROM krapbott_v2.queue
+         WHERE channel_id = $1 AND queue_name = $2
+         ORDER BY position ASC
+         LIMIT $3",
+    ).bind(owner.as_str()).bind(queue).bind(teamsize).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// Přečísluje frontu 1..n, uživatelé z `to_back` skončí na konci (ve stávajícím pořadí)
+pub async fn renumber_queue(conn: &mut PgConnection, owner: &ChannelId, queue: &str, to_back: &[String]) -> BotResult<()> {
+    sqlx::query(
+        "UPDATE krapbott_v2.queue SET position = -position WHERE channel_id = $1 AND queue_name = $2",
+    ).bind(owner.as_str()).bind(queue).execute(&mut *conn).await?;
+
+    sqlx::query(
+        "UPDATE krapbott_v2.queue q
+         SET position = o.rn
+         FROM (
+             SELECT user_id, ROW_NUMBER() OVER (ORDER BY (user_id = ANY($3)), position DESC)::INT AS rn
+             FROM krapbott_v2.queue
+             WHERE channel_id = $1 AND queue_name = $2
+         ) o
+         WHERE q.channel_id = $1 AND q.queue_name = $2 AND q.user_id = o.user_id",
+    ).bind(owner.as_str()).bind(queue).bind(to_back).execute(&mut *conn).await?;
+    Ok(())
+}
+
+pub async fn move_users_to_back(pool: &PgPool, owner: &ChannelId, queue: &str, user_ids: &[String], reason: &str) -> BotResult<()> {
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    let snapshot = snapshot_queue(&mut tx, owner, queue, reason).await?;
+    sqlx::query(
+        "UPDATE krapbott_v2.queue SET locked_first = FALSE WHERE channel_id = $1 AND queue_name = $2 AND user_id = ANY($3)",
+    ).bind(owner.as_str()).bind(queue).bind(user_ids).execute(&mut *tx).await?;
+    renumber_queue(&mut tx, owner, queue, user_ids).await?;
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn remove_users_from_queue(pool: &PgPool, owner: &ChannelId, queue: &str, user_ids: &[String]) -> BotResult<()> {
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "ready check").await?;
+    sqlx::query(
+        "DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 AND user_id = ANY($3)",
+    ).bind(owner.as_str()).bind(queue).bind(user_ids).execute(&mut *tx).await?;
+    renumber_queue(&mut tx, owner, queue, &[]).await?;
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::task::JoinSet;
+
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, commands::queue::{logic::next_handler, tags::GroupBalance}, db::initialize_database, state::def::{BotError, MAIN_QUEUE}};
+
+    fn entry(i: usize) -> QueueEntry {
+        QueueEntry {
+            user_id: UserId::new(Platform::Twitch, format!("{i}")),
+            bungie_name: format!("Guardian#{i:04}"),
+            display_name: format!("viewer{i}"),
+            tags: Vec::new(),
+        }
+    }
+
+    /// `sqlx::test` si pro test založí vlastní databázi na serveru z DATABASE_URL
+    #[sqlx::test]
+    async fn concurrent_join_leave_next_keep_positions_dense(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "concurrency_test");
+
+        let mut tasks = JoinSet::new();
+        for i in 0..80 {
+            let (p, o) = (pool.clone(), owner.clone());
+            tasks.spawn(async move {
+                let reply = add_to_queue(1000, &p, &entry(i), &o, MAIN_QUEUE, Queue::Join, false).await?;
+                assert!(!reply.contains("Error"), "join failed: {reply}");
+                Ok::<_, BotError>(())
+            });
+            if i % 4 == 0 {
+                let (p, o) = (pool.clone(), owner.clone());
+                tasks.spawn(async move {
+                    remove_users_from_queue(&p, &o, MAIN_QUEUE, &[entry(i / 2).user_id.as_str().to_string()]).await
+                });
+            }
+            if i % 10 == 0 {
+                let (p, o) = (pool.clone(), owner.clone());
+                tasks.spawn(async move { next_handler(&o, MAIN_QUEUE, &p, 3, &GroupBalance::default()).await.map(|_| ()) });
+            }
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let rows: Vec<(i32, String)> = sqlx::query_as(
+            "SELECT position, user_id FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 ORDER BY position",
+        ).bind(owner.as_str()).bind(MAIN_QUEUE).fetch_all(&pool).await.unwrap();
+
+        let positions: Vec<i32> = rows.iter().map(|(p, _)| *p).collect();
+        assert_eq!(positions, (1..=rows.len() as i32).collect::<Vec<_>>());
+        let users: std::collections::HashSet<&String> = rows.iter().map(|(_, u)| u).collect();
+        assert_eq!(users.len(), rows.len());
+    }
+}
@@ -0,0 +1,106 @@
+use sqlx::{PgConnection, PgExecutor, PgPool};
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, UserId}};
+
+pub const RAFFLE_ENTRANTS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.raffle_entrants (
+        channel_id TEXT NOT NULL,
+        queue_name TEXT NOT NULL DEFAULT 'main',
+        user_id TEXT NOT NULL,
+        losses INTEGER NOT NULL DEFAULT 0,
+        bonus DOUBLE PRECISION NOT NULL DEFAULT 0,
+        PRIMARY KEY (channel_id, queue_name, user_id)
+    );
+"#;
+
+/// Účastník losování — řádek z fronty doplněný o historii losování
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RaffleEntrant {
+    pub user_id: String,
+    pub subscriber: bool,
+    pub vip: bool,
+    pub losses: i32,
+    pub bonus: f64,
+}
+
+const ENTRANTS_QUERY: &str = r#"
+    SELECT q.user_id, q.subscriber, q.vip,
+           COALESCE(r.losses, 0) AS losses,
+           COALESCE(r.bonus, 0) AS bonus
+    FROM krapbott_v2.queue q
+    LEFT JOIN krapbott_v2.raffle_entrants r
+           ON r.channel_id = q.channel_id AND r.queue_name = q.queue_name AND r.user_id = q.user_id
+    WHERE q.channel_id = $1 AND q.queue_name = $2
+    ORDER BY q.position ASC
+"#;
+
+pub async fn fetch_raffle_entrants(conn: &mut PgConnection, channel_id: &ChannelId, queue: &str) -> BotResult<Vec<RaffleEntrant>> {
+    let entrants = sqlx::query_as::<_, RaffleEntrant>(ENTRANTS_QUERY).bind(channel_id.as_str()).bind(queue).fetch_all(&mut *conn).await?;
+    Ok(entrants)
+}
+
+pub async fn fetch_raffle_entrants_pool(pool: &PgPool, channel_id: &ChannelId, queue: &str) -> BotResult<Vec<RaffleEntrant>> {
+    let entrants = sqlx::query_as::<_, RaffleEntrant>(ENTRANTS_QUERY).bind(channel_id.as_str()).bind(queue).fetch_all(pool).await?;
+    Ok(entrants)
+}
+
+/// Uloží, jestli byl uživatel při joinu sub / VIP (váha se počítá až při losování)
+pub async fn set_queue_status<'e>(executor: impl PgExecutor<'e>, channel_id: &ChannelId, queue: &str, user_id: &UserId, subscriber: bool, vip: bool) -> BotResult<()> {
+    sqlx::query(
+        "UPDATE krapbott_v2.queue SET subscriber = $1, vip = $2 WHERE channel_id = $3 AND queue_name = $4 AND user_id = $5",
+    ).bind(subscriber).bind(vip).bind(channel_id.as_str()).bind(queue).bind(user_id.as_str()).execute(executor).await?;
+    Ok(())
+}
+
+/// Winners start from zero again, everyone left behind gets one more loss
+pub async fn record_draw(conn: &mut PgConnection, channel_id: &ChannelId, queue: &str, winners: &[String], losers: &[String]) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.raffle_entrants (channel_id, queue_name, user_id, losses)
+        SELECT $1, $2, u, 0 FROM UNNEST($3::text[]) AS u
+        ON CONFLICT (channel_id, queue_name, user_id) DO UPDATE SET losses = 0
+        "#,
+    ).bind(channel_id.as_str()).bind(queue).bind(winners).execute(&mut *conn).await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.raffle_entrants (channel_id, queue_name, user_id, losses)
+        SELECT $1, $2, u, 1 FROM UNNEST($3::text[]) AS u
+        ON CONFLICT (channel_id, queue_name, user_id) DO UPDATE SET losses = krapbott_v2.raffle_entrants.losses + 1
+        "#,
+    ).bind(channel_id.as_str()).bind(queue).bind(losers).execute(&mut *conn).await?;
+
+    Ok(())
+}
+
+pub async fn set_raffle_bonus(pool: &PgPool, channel_id: &ChannelId, queue: &str, user_id: &str, bonus: f64) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.raffle_entrants (channel_id, queue_name, user_id, bonus)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (channel_id, queue_name, user_id) DO UPDATE SET bonus = EXCLUDED.bonus
+        "#,
+    ).bind(channel_id.as_str()).bind(queue).bind(user_id).bind(bonus).execute(pool).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, db::initialize_database};
+
+    #[sqlx::test]
+    async fn losses_are_counted_per_queue(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "raffle_test");
+        let mut conn = pool.acquire().await.unwrap();
+        record_draw(&mut conn, &owner, "raid", &["w".to_string()], &["l".to_string()]).await.unwrap();
+        record_draw(&mut conn, &owner, "raid", &[], &["l".to_string()]).await.unwrap();
+        record_draw(&mut conn, &owner, "dungeon", &["l".to_string()], &[]).await.unwrap();
+
+        let losses: Vec<(String, i32)> = sqlx::query_as(
+            "SELECT queue_name, losses FROM krapbott_v2.raffle_entrants WHERE channel_id = $1 AND user_id = 'l' ORDER BY queue_name",
+        ).bind(owner.as_str()).fetch_all(&pool).await.unwrap();
+        assert_eq!(losses, [("dungeon".to_string(), 0), ("raid".to_string(), 2)]);
+    }
+}
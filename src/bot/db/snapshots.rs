@@ -0,0 +1,310 @@
+use sqlx::{PgConnection, PgPool};
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, queue::{begin_queue_tx, renumber_queue}}};
+
+/// Kolik snapshotů si pamatujeme na kanál
+pub const UNDO_HISTORY: i64 = 10;
+
+pub const QUEUE_SNAPSHOTS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.queue_snapshots (
+        id BIGSERIAL PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        queue_name TEXT NOT NULL,
+        action TEXT NOT NULL,
+        taken_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        entries JSONB NOT NULL,
+        run_id BIGINT, -- run z `!next` / losování, `!undo` ho smaže z historie
+        run_counted BOOLEAN NOT NULL DEFAULT FALSE, -- změna zvedla počítadlo runů kanálu
+        draw_users TEXT[], -- hráči, kterým losování změnilo prohry
+        draw_before JSONB, -- jejich řádky z `raffle_entrants` před losováním
+        queue_settings TEXT, -- nastavení smazané pojmenované fronty (JSON), `!undo` ji vrátí
+        entries_after JSONB -- fronta po změně, `!undo` podle ní pozná pozdější joiny a odchody
+    );
+"#;
+
+/// id, queue_name, action, run_id, run_counted, draw_users, queue_settings
+type SnapshotRow = (i64, String, String, Option<i64>, bool, Option<Vec<String>>, Option<String>);
+
+/// Obnovený snapshot — která fronta a co se vracelo
+pub struct RestoredSnapshot {
+    pub queue_name: String,
+    pub action: String,
+    /// Vrácený `!next` / losování, počítadlo runů kanálu se má snížit
+    pub run_counted: bool,
+    /// Nastavení smazané fronty (JSON), má se vrátit do konfigurace
+    pub queue_settings: Option<String>,
+}
+
+/// Výsledek `!undo`
+pub enum UndoOutcome {
+    /// Kanál nemá žádný snapshot
+    Nothing,
+    Restored(RestoredSnapshot),
+}
+
+/// Uloží celý stav fronty před změnou. Volat uvnitř transakce té změny, vrací id snapshotu.
+pub async fn snapshot_queue(conn: &mut PgConnection, channel: &ChannelId, queue: &str, action: &str) -> BotResult<i64> {
+    let id: i64 = sqlx::query_scalar(
+        r#"
+        INSERT INTO krapbott_v2.queue_snapshots (channel_id, queue_name, action, entries)
+        SELECT $1, $2, $3, COALESCE(jsonb_agg(to_jsonb(q) ORDER BY q.position), '[]'::jsonb)
+        FROM krapbott_v2.queue q
+        WHERE q.channel_id = $1 AND q.queue_name = $2
+        RETURNING id
+        "#,
+    ).bind(channel.as_str()).bind(queue).bind(action).fetch_one(&mut *conn).await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM krapbott_v2.queue_snapshots
+        WHERE channel_id = $1 AND id NOT IN (
+            SELECT id FROM krapbott_v2.queue_snapshots
+            WHERE channel_id = $1
+            ORDER BY id DESC
+            LIMIT $2
+        )
+        "#,
+    ).bind(channel.as_str()).bind(UNDO_HISTORY).execute(&mut *conn).await?;
+
+    Ok(id)
+}
+
+/// Uloží stav fronty po změně. Volat na konci transakce té změny, těsně před commitem.
+pub async fn seal_snapshot(conn: &mut PgConnection, snapshot_id: i64) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE krapbott_v2.queue_snapshots s
+        SET entries_after = (
+            SELECT COALESCE(jsonb_agg(to_jsonb(q) ORDER BY q.position), '[]'::jsonb)
+            FROM krapbott_v2.queue q
+            WHERE q.channel_id = s.channel_id AND q.queue_name = s.queue_name
+        )
+        WHERE s.id = $1
+        "#,
+    ).bind(snapshot_id).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Poznamená ke snapshotu zapsaný run, `!undo` ho smaže z historie a sníží počítadlo runů
+pub async fn attach_run(conn: &mut PgConnection, snapshot_id: i64, run_id: Option<i64>) -> BotResult<()> {
+    sqlx::query("UPDATE krapbott_v2.queue_snapshots SET run_id = $2, run_counted = TRUE WHERE id = $1")
+        .bind(snapshot_id).bind(run_id).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Uloží nastavení mazané pojmenované fronty, `!undo` ji vrátí i s hráči
+pub async fn attach_queue_settings(conn: &mut PgConnection, snapshot_id: i64, settings: &str) -> BotResult<()> {
+    sqlx::query("UPDATE krapbott_v2.queue_snapshots SET queue_settings = $2 WHERE id = $1")
+        .bind(snapshot_id).bind(settings).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Uloží prohry a bonusy hráčů před `record_draw`, `!undo` je vrátí. Volat před losováním.
+pub async fn attach_draw(conn: &mut PgConnection, snapshot_id: i64, channel: &ChannelId, queue: &str, users: &[String]) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE krapbott_v2.queue_snapshots
+        SET draw_users = $4,
+            draw_before = (
+                SELECT COALESCE(jsonb_agg(to_jsonb(r)), '[]'::jsonb)
+                FROM krapbott_v2.raffle_entrants r
+                WHERE r.channel_id = $2 AND r.queue_name = $3 AND r.user_id = ANY($4)
+            )
+        WHERE id = $1
+        "#,
+    ).bind(snapshot_id).bind(channel.as_str()).bind(queue).bind(users).execute(&mut *conn).await?;
+    Ok(())
+}
+
+/// Vrátí frontu do stavu posledního snapshotu a snapshot zahodí. Bez `queue` bere poslední snapshot kanálu.
+/// Kdo se od snapshotu přidal, zůstane na konci fronty, kdo mezitím odešel, se nevrací.
+pub async fn restore_last_snapshot(pool: &PgPool, channel: &ChannelId, queue: Option<&str>) -> BotResult<UndoOutcome> {
+    let mut tx = begin_queue_tx(pool, channel).await?;
+
+    let snapshot: Option<SnapshotRow> = sqlx::query_as(
+        r#"
+        SELECT id, queue_name, action, run_id, run_counted, draw_users, queue_settings
+        FROM krapbott_v2.queue_snapshots
+        WHERE channel_id = $1 AND ($2::text IS NULL OR queue_name = $2)
+        ORDER BY id DESC
+        LIMIT 1
+        FOR UPDATE
+        "#,
+    ).bind(channel.as_str()).bind(queue).fetch_optional(&mut *tx).await?;
+
+    let Some((id, queue_name, action, run_id, run_counted, draw_users, queue_settings)) = snapshot else {
+        return Ok(UndoOutcome::Nothing);
+    };
+
+    // Join, `!leave` ani změna Bungie name snapshot nedělají — porovnají se se stavem po změně
+    let current: String = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(jsonb_agg(to_jsonb(q) ORDER BY q.position), '[]'::jsonb)::text
+        FROM krapbott_v2.queue q
+        WHERE q.channel_id = $1 AND q.queue_name = $2
+        "#,
+    ).bind(channel.as_str()).bind(&queue_name).fetch_one(&mut *tx).await?;
+
+    sqlx::query("DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+        .bind(channel.as_str()).bind(&queue_name).execute(&mut *tx).await?;
+
+    // jsonb_populate_recordset vezme sloupce podle názvu, takže přežije i nové sloupce ve frontě.
+    // Kdo byl ve frontě po změně a teď v ní není, mezitím odešel.
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.queue
+        SELECT r.* FROM krapbott_v2.queue_snapshots s
+        CROSS JOIN LATERAL jsonb_populate_recordset(NULL::krapbott_v2.queue, s.entries) r
+        WHERE s.id = $1 AND NOT (
+            EXISTS (
+                SELECT 1 FROM jsonb_populate_recordset(NULL::krapbott_v2.queue, s.entries_after) a
+                WHERE a.user_id = r.user_id
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM jsonb_populate_recordset(NULL::krapbott_v2.queue, $2::jsonb) c
+                WHERE c.user_id = r.user_id
+            )
+        )
+        "#,
+    ).bind(id).bind(&current).execute(&mut *tx).await?;
+
+    // Vrácení hráči si nechají aktuální jména
+    sqlx::query(
+        r#"
+        UPDATE krapbott_v2.queue q
+        SET bungie_name = c.bungie_name, display_name = c.display_name
+        FROM jsonb_populate_recordset(NULL::krapbott_v2.queue, $3::jsonb) c
+        WHERE q.channel_id = $1 AND q.queue_name = $2 AND q.user_id = c.user_id
+        "#,
+    ).bind(channel.as_str()).bind(&queue_name).bind(&current).execute(&mut *tx).await?;
+
+    // Kdo se přidal po změně, jde za vrácenou frontu ve svém pořadí
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.queue
+        SELECT (jsonb_populate_record(j.c, jsonb_build_object('position', j.pos))).*
+        FROM (
+            SELECT c, m.top + ROW_NUMBER() OVER (ORDER BY c.position) AS pos
+            FROM jsonb_populate_recordset(NULL::krapbott_v2.queue, $2::jsonb) c
+            CROSS JOIN krapbott_v2.queue_snapshots s
+            CROSS JOIN (
+                SELECT COALESCE(MAX(position), 0) AS top FROM krapbott_v2.queue
+                WHERE channel_id = $3 AND queue_name = $4
+            ) m
+            WHERE s.id = $1
+              AND NOT EXISTS (
+                  SELECT 1 FROM jsonb_populate_recordset(NULL::krapbott_v2.queue, COALESCE(s.entries_after, s.entries)) a
+                  WHERE a.user_id = c.user_id
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM krapbott_v2.queue q
+                  WHERE q.channel_id = $3 AND q.queue_name = $4 AND q.user_id = c.user_id
+              )
+        ) j
+        "#,
+    ).bind(id).bind(&current).bind(channel.as_str()).bind(&queue_name).execute(&mut *tx).await?;
+
+    renumber_queue(&mut tx, channel, &queue_name, &[]).await?;
+
+    // Vrácený run se nemá počítat do historie, cooldownů ani statistik
+    if let Some(run_id) = run_id {
+        sqlx::query("DELETE FROM krapbott_v2.run_history WHERE id = $1").bind(run_id).execute(&mut *tx).await?;
+    }
+    if let Some(users) = draw_users {
+        sqlx::query("DELETE FROM krapbott_v2.raffle_entrants WHERE channel_id = $1 AND queue_name = $2 AND user_id = ANY($3)")
+            .bind(channel.as_str()).bind(&queue_name).bind(&users).execute(&mut *tx).await?;
+        // Fronta se bere ze snapshotu, starší snapshoty queue_name v řádcích nemají
+        sqlx::query(
+            r#"
+            INSERT INTO krapbott_v2.raffle_entrants (channel_id, queue_name, user_id, losses, bonus)
+            SELECT r.channel_id, s.queue_name, r.user_id, r.losses, r.bonus FROM krapbott_v2.queue_snapshots s
+            CROSS JOIN LATERAL jsonb_populate_recordset(NULL::krapbott_v2.raffle_entrants, s.draw_before) r
+            WHERE s.id = $1
+            "#,
+        ).bind(id).execute(&mut *tx).await?;
+    }
+
+    sqlx::query("DELETE FROM krapbott_v2.queue_snapshots WHERE id = $1").bind(id).execute(&mut *tx).await?;
+
+    tx.commit().await?;
+    Ok(UndoOutcome::Restored(RestoredSnapshot { queue_name, action, run_counted, queue_settings }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, commands::queue::{logic::{Queue, QueueEntry, next_handler}, tags::GroupBalance}, db::{UserId, initialize_database, queue::add_to_queue}, state::def::MAIN_QUEUE};
+
+    async fn join(pool: &PgPool, owner: &ChannelId, queue: &str, name: &str) {
+        let entry = QueueEntry {
+            user_id: UserId::new(Platform::Twitch, name),
+            bungie_name: format!("{name}#0001"),
+            display_name: name.to_string(),
+            tags: Vec::new(),
+        };
+        add_to_queue(100, pool, &entry, owner, queue, Queue::Join, false).await.unwrap();
+    }
+
+    async fn queued(pool: &PgPool, owner: &ChannelId, queue: &str) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+            .bind(owner.as_str()).bind(queue).fetch_one(pool).await.unwrap()
+    }
+
+    #[sqlx::test]
+    async fn undo_next_removes_recorded_run(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "undo_test");
+        for name in ["a", "b", "c"] {
+            join(&pool, &owner, MAIN_QUEUE, name).await;
+        }
+
+        next_handler(&owner, MAIN_QUEUE, &pool, 2, &GroupBalance::default()).await.unwrap();
+        let UndoOutcome::Restored(restored) = restore_last_snapshot(&pool, &owner, None).await.unwrap() else {
+            panic!("undo should restore the queue");
+        };
+        assert!(restored.run_counted);
+
+        let runs: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM krapbott_v2.run_history WHERE channel_id = $1")
+            .bind(owner.as_str()).fetch_one(&pool).await.unwrap();
+        assert_eq!((runs, queued(&pool, &owner, MAIN_QUEUE).await), (0, 3));
+    }
+
+    #[sqlx::test]
+    async fn undo_keeps_a_later_join(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "undo_late_join");
+        for name in ["a", "b", "c"] {
+            join(&pool, &owner, MAIN_QUEUE, name).await;
+        }
+
+        next_handler(&owner, MAIN_QUEUE, &pool, 2, &GroupBalance::default()).await.unwrap();
+        join(&pool, &owner, MAIN_QUEUE, "d").await;
+
+        let outcome = restore_last_snapshot(&pool, &owner, None).await.unwrap();
+        assert!(matches!(outcome, UndoOutcome::Restored(ref restored) if restored.action == "next"));
+        // Vrácená skupina je zpátky a nový hráč zůstal na konci
+        let names: Vec<String> = sqlx::query_scalar(
+            "SELECT display_name FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2 ORDER BY position",
+        ).bind(owner.as_str()).bind(MAIN_QUEUE).fetch_all(&pool).await.unwrap();
+        assert_eq!(names, ["a", "b", "c", "d"]);
+    }
+
+    #[sqlx::test]
+    async fn undo_picks_the_requested_queue(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "undo_queues");
+        for name in ["a", "b", "c"] {
+            join(&pool, &owner, MAIN_QUEUE, name).await;
+            join(&pool, &owner, "raid", name).await;
+        }
+
+        next_handler(&owner, MAIN_QUEUE, &pool, 2, &GroupBalance::default()).await.unwrap();
+        next_handler(&owner, "raid", &pool, 2, &GroupBalance::default()).await.unwrap();
+
+        let UndoOutcome::Restored(restored) = restore_last_snapshot(&pool, &owner, Some(MAIN_QUEUE)).await.unwrap() else {
+            panic!("undo should restore the main queue");
+        };
+        assert_eq!(restored.queue_name, MAIN_QUEUE);
+        assert_eq!((queued(&pool, &owner, MAIN_QUEUE).await, queued(&pool, &owner, "raid").await), (3, 1));
+    }
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::ChannelId};
+
+/// Mezera mezi runy, po které se počítá nový stream
+pub const STREAM_GAP_HOURS: i32 = 4;
+/// Výchozí okno pro `!stats` a dock
+pub const STATS_DEFAULT_DAYS: i32 = 30;
+
+/// Souhrn z `run_history` za posledních `days` dní
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChannelStats {
+    pub days: i32,
+    pub runs: i64,
+    pub raffle_runs: i64,
+    pub streams: i64,
+    pub unique_participants: i64,
+    pub avg_wait_seconds: Option<i64>,
+    /// 0.0 - 1.0, jen runy se zapsanou velikostí skupiny
+    pub avg_fill: Option<f64>,
+    pub per_day: Vec<DayStats>,
+    pub per_stream: Vec<StreamStats>,
+    pub top_viewers: Vec<ReturningViewer>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DayStats {
+    /// YYYY-MM-DD v časové zóně kanálu
+    pub day: String,
+    pub runs: i64,
+    pub participants: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct StreamStats {
+    /// Unix timestamp (seconds) of the first and last run
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub runs: i64,
+    pub participants: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ReturningViewer {
+    pub display_name: String,
+    pub streams: i64,
+    pub runs: i64,
+}
+
+#[derive(sqlx::FromRow)]
+struct Totals {
+    runs: i64,
+    raffle_runs: i64,
+    unique_participants: i64,
+    avg_wait_seconds: Option<i64>,
+    avg_fill: Option<f64>,
+}
+
+/// Runy očíslované podle streamu, sdílené CTE pro statistiky po streamech ($3 = STREAM_GAP_HOURS)
+const STREAMS_CTE: &str = r#"
+    WITH gaps AS (
+        SELECT id, played_at,
+               CASE WHEN played_at - LAG(played_at) OVER (ORDER BY played_at, id) < make_interval(hours => $3::int) THEN 0 ELSE 1 END AS new_stream
+        FROM krapbott_v2.run_history
+        WHERE channel_id = $1 AND played_at > NOW() - make_interval(days => $2::int)
+    ), streams AS (
+        SELECT id, played_at, SUM(new_stream) OVER (ORDER BY played_at, id) AS stream
+        FROM gaps
+    )
+"#;
+
+/// Časová zóna kanálu pro rozdělení po dnech, bere se z `auto_queue`
+pub async fn fetch_channel_stats(pool: &PgPool, channel: &ChannelId, days: i32, timezone: &str, top: i64) -> BotResult<ChannelStats> {
+    let totals = sqlx::query_as::<_, Totals>(
+        r#"
+        WITH runs AS (
+            SELECT r.id, r.raffle, r.group_size, r.played_at,
+                   COUNT(m.user_id) AS members,
+                   SUM(r.played_at - m.joined_at) AS wait,
+                   COUNT(m.joined_at) AS waited
+            FROM krapbott_v2.run_history r
+            LEFT JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+            WHERE r.channel_id = $1 AND r.played_at > NOW() - make_interval(days => $2::int)
+            GROUP BY r.id
+        )
+        SELECT COUNT(*) AS runs,
+               COUNT(*) FILTER (WHERE raffle) AS raffle_runs,
+               (SELECT COUNT(DISTINCT m.user_id) FROM krapbott_v2.run_history_members m WHERE m.run_id IN (SELECT id FROM runs)) AS unique_participants,
+               EXTRACT(EPOCH FROM SUM(wait) / NULLIF(SUM(waited), 0)::float8)::BIGINT AS avg_wait_seconds,
+               AVG(LEAST(members::float8 / group_size, 1.0)) FILTER (WHERE group_size > 0) AS avg_fill
+        FROM runs
+        "#,
+    ).bind(channel.as_str()).bind(days).fetch_one(pool).await?;
+
+    let per_day = sqlx::query_as::<_, DayStats>(
+        r#"
+        SELECT to_char(r.played_at AT TIME ZONE $3, 'YYYY-MM-DD') AS day,
+               COUNT(DISTINCT r.id) AS runs,
+               COUNT(DISTINCT m.user_id) AS participants
+        FROM krapbott_v2.run_history r
+        LEFT JOIN krapbott_v2.run_history_members m ON m.run_id = r.id
+        WHERE r.channel_id = $1 AND r.played_at > NOW() - make_interval(days => $2::int)
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+    ).bind(channel.as_str()).bind(days).bind(timezone).fetch_all(pool).await?;
+
+    let per_stream = sqlx::query_as::<_, StreamStats>(&format!(
+        r#"{STREAMS_CTE}
+        SELECT EXTRACT(EPOCH FROM MIN(s.played_at))::BIGINT AS started_at,
+               EXTRACT(EPOCH FROM MAX(s.played_at))::BIGINT AS ended_at,
+               COUNT(DISTINCT s.id) AS runs,
+               COUNT(DISTINCT m.user_id) AS participants
+        FROM streams s
+        LEFT JOIN krapbott_v2.run_history_members m ON m.run_id = s.id
+        GROUP BY s.stream
+        ORDER BY started_at ASC
+        "#
+    )).bind(channel.as_str()).bind(days).bind(STREAM_GAP_HOURS).fetch_all(pool).await?;
+
+    // Vracející se = hráli aspoň ve dvou streamech
+    let top_viewers = sqlx::query_as::<_, ReturningViewer>(&format!(
+        r#"{STREAMS_CTE}
+        SELECT (ARRAY_AGG(m.display_name ORDER BY s.played_at DESC))[1] AS display_name,
+               COUNT(DISTINCT s.stream) AS streams,
+               COUNT(*) AS runs
+        FROM streams s
+        JOIN krapbott_v2.run_history_members m ON m.run_id = s.id
+        GROUP BY m.user_id
+        HAVING COUNT(DISTINCT s.stream) > 1
+        ORDER BY streams DESC, runs DESC
+        LIMIT $4
+        "#
+    )).bind(channel.as_str()).bind(days).bind(STREAM_GAP_HOURS).bind(top).fetch_all(pool).await?;
+
+    Ok(ChannelStats {
+        days,
+        runs: totals.runs,
+        raffle_runs: totals.raffle_runs,
+        streams: per_stream.len() as i64,
+        unique_participants: totals.unique_participants,
+        avg_wait_seconds: totals.avg_wait_seconds,
+        avg_fill: totals.avg_fill,
+        per_day,
+        per_stream,
+        top_viewers,
+    })
+}
@@ -0,0 +1,59 @@
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, UserId}};
+
+/// Jak dlouho čeká prio za sub na join, pak propadne
+pub const PENDING_PRIO_HOURS: i32 = 24;
+
+pub const PENDING_PRIO_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.pending_sub_prio (
+        channel_id TEXT NOT NULL,
+        user_id TEXT NOT NULL,
+        runs INTEGER NOT NULL,
+        granted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+        PRIMARY KEY (channel_id, user_id)
+    );
+"#;
+
+/// Uloží prio za sub diváka, který zrovna nečeká ve frontě. Víc subů se nesčítá, platí to štědřejší.
+pub async fn store_pending_prio(pool: &PgPool, owner: &ChannelId, user_id: &UserId, runs: i32) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.pending_sub_prio (channel_id, user_id, runs)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (channel_id, user_id) DO UPDATE
+        SET runs = GREATEST(krapbott_v2.pending_sub_prio.runs, EXCLUDED.runs), granted_at = now()
+        "#,
+    ).bind(owner.as_str()).bind(user_id.as_str()).bind(runs).execute(pool).await?;
+    Ok(())
+}
+
+/// Vybere čekající prio diváka (propadlé zahodí), volá se po joinu
+pub async fn take_pending_prio(pool: &PgPool, owner: &ChannelId, user_id: &UserId) -> BotResult<Option<i32>> {
+    let runs: Option<Option<i32>> = sqlx::query_scalar(
+        r#"
+        DELETE FROM krapbott_v2.pending_sub_prio
+        WHERE channel_id = $1 AND user_id = $2
+        RETURNING CASE WHEN granted_at > now() - make_interval(hours => $3) THEN runs END
+        "#,
+    ).bind(owner.as_str()).bind(user_id.as_str()).bind(PENDING_PRIO_HOURS).fetch_optional(pool).await?;
+    Ok(runs.flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, db::initialize_database};
+
+    #[sqlx::test]
+    async fn pending_prio_is_taken_once(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "sub_prio");
+        let user = UserId::new(Platform::Twitch, "viewer");
+
+        store_pending_prio(&pool, &owner, &user, 2).await.unwrap();
+        store_pending_prio(&pool, &owner, &user, 1).await.unwrap();
+        assert_eq!(take_pending_prio(&pool, &owner, &user).await.unwrap(), Some(2));
+        assert_eq!(take_pending_prio(&pool, &owner, &user).await.unwrap(), None);
+    }
+}
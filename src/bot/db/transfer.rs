@@ -0,0 +1,326 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::{ChannelId, UserId, queue::{BanStatus, begin_queue_tx, is_banned_from_queue}, snapshots::{seal_snapshot, snapshot_queue}}, state::def::BotError};
+
+/// Jeden řádek exportu, odpovídá sloupcům `krapbott_v2.queue`
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct QueueExportRow {
+    pub position: i32,
+    pub user_id: String,
+    pub display_name: String,
+    pub bungie_name: String,
+    #[serde(default = "default_group_priority")]
+    pub group_priority: i32,
+    #[serde(default)]
+    pub locked_first: bool,
+    #[serde(default)]
+    pub priority_runs_left: i32,
+    #[serde(default)]
+    pub subscriber: bool,
+    #[serde(default)]
+    pub vip: bool,
+    /// Unix timestamp (seconds)
+    #[serde(default)]
+    pub joined_at: Option<i64>,
+    #[serde(default)]
+    pub party_id: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn default_group_priority() -> i32 {
+    2
+}
+
+/// Celý export jedné fronty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueExport {
+    pub channel: String,
+    pub queue: String,
+    pub exported_at: i64,
+    pub entries: Vec<QueueExportRow>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Fronta se nahradí obsahem importu
+    #[default]
+    Replace,
+    /// Import se přidá na konec, kdo už ve frontě je, se přeskočí
+    Append,
+}
+
+/// Výsledek importu pro dock
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Zabanovaní nebo v timeoutu, do fronty se nedostali
+    pub banned: Vec<String>,
+}
+
+const CSV_HEADER: &str = "position,user_id,display_name,bungie_name,group_priority,locked_first,priority_runs_left,subscriber,vip,joined_at,party_id,tags";
+
+pub async fn export_queue(pool: &PgPool, owner: &ChannelId, queue: &str) -> BotResult<QueueExport> {
+    let entries = sqlx::query_as::<_, QueueExportRow>(
+        r#"
+        SELECT position, user_id, display_name, bungie_name,
+               COALESCE(group_priority, 2) AS group_priority,
+               COALESCE(locked_first, FALSE) AS locked_first,
+               COALESCE(priority_runs_left, 0) AS priority_runs_left,
+               subscriber, vip,
+               EXTRACT(EPOCH FROM joined_at)::BIGINT AS joined_at,
+               party_id, tags
+        FROM krapbott_v2.queue
+        WHERE channel_id = $1 AND queue_name = $2
+        ORDER BY position ASC
+        "#,
+    ).bind(owner.as_str()).bind(queue).fetch_all(pool).await?;
+
+    Ok(QueueExport {
+        channel: owner.as_str().to_string(),
+        queue: queue.to_string(),
+        exported_at: chrono::Utc::now().timestamp(),
+        entries,
+    })
+}
+
+/// Nahraje řádky do fronty v pořadí podle `position`, zabanované v `owner` přeskočí.
+/// Předchozí stav jde vrátit přes `!undo`.
+pub async fn import_queue(pool: &PgPool, owner: &ChannelId, queue: &str, mut rows: Vec<QueueExportRow>, mode: ImportMode) -> BotResult<ImportReport> {
+    // UserId::new panikaří na prázdné id, tak to zkontrolujeme dřív
+    let valid = |id: &str| id.split_once(':').is_some_and(|(_, rest)| !rest.is_empty() && !rest.contains(':')) && UserId::from_str(id).is_ok();
+    if let Some(bad) = rows.iter().find(|r| !valid(&r.user_id)) {
+        return Err(BotError::Chat(format!("Invalid user_id {} for {}", bad.user_id, bad.display_name)));
+    }
+    rows.sort_by_key(|r| r.position);
+
+    // Export z jiného kanálu nesmí obejít bany tohoto
+    let mut banned = Vec::new();
+    let mut allowed = Vec::with_capacity(rows.len());
+    for row in rows {
+        let user_id = UserId::from_str(&row.user_id).map_err(|e| BotError::Chat(e.to_string()))?;
+        match is_banned_from_queue(pool, owner, &user_id).await? {
+            BanStatus::NotBanned => allowed.push(row),
+            _ => banned.push(row.display_name),
+        }
+    }
+
+    let mut tx = begin_queue_tx(pool, owner).await?;
+    let snapshot = snapshot_queue(&mut tx, owner, queue, "import").await?;
+
+    let mut existing: Vec<String> = Vec::new();
+    match mode {
+        ImportMode::Replace => {
+            sqlx::query("DELETE FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+                .bind(owner.as_str()).bind(queue).execute(&mut *tx).await?;
+        }
+        ImportMode::Append => {
+            existing = sqlx::query_scalar("SELECT user_id FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+                .bind(owner.as_str()).bind(queue).fetch_all(&mut *tx).await?;
+        }
+    }
+
+    let mut position: i32 = sqlx::query_scalar("SELECT COALESCE(MAX(position), 0) FROM krapbott_v2.queue WHERE channel_id = $1 AND queue_name = $2")
+        .bind(owner.as_str()).bind(queue).fetch_one(&mut *tx).await?;
+
+    let mut inserted = 0;
+    for row in allowed {
+        if existing.contains(&row.user_id) {
+            continue;
+        }
+        existing.push(row.user_id.clone());
+        position += 1;
+
+        sqlx::query(
+            r#"
+            INSERT INTO krapbott_v2.queue
+                (position, user_id, display_name, bungie_name, channel_id, queue_name,
+                 group_priority, locked_first, priority_runs_left, subscriber, vip, joined_at, party_id, tags)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, COALESCE(to_timestamp($12), now()), $13, $14)
+            "#,
+        )
+        .bind(position).bind(&row.user_id).bind(&row.display_name).bind(&row.bungie_name)
+        .bind(owner.as_str()).bind(queue)
+        .bind(row.group_priority).bind(row.locked_first).bind(row.priority_runs_left)
+        .bind(row.subscriber).bind(row.vip).bind(row.joined_at.map(|t| t as f64)).bind(&row.party_id).bind(&row.tags)
+        .execute(&mut *tx).await?;
+        inserted += 1;
+    }
+
+    seal_snapshot(&mut tx, snapshot).await?;
+    tx.commit().await?;
+    Ok(ImportReport { imported: inserted, banned })
+}
+
+impl QueueExport {
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from(CSV_HEADER);
+        out.push('\n');
+        for r in &self.entries {
+            let fields = [
+                r.position.to_string(),
+                csv_field(&r.user_id),
+                csv_field(&r.display_name),
+                csv_field(&r.bungie_name),
+                r.group_priority.to_string(),
+                r.locked_first.to_string(),
+                r.priority_runs_left.to_string(),
+                r.subscriber.to_string(),
+                r.vip.to_string(),
+                r.joined_at.map(|t| t.to_string()).unwrap_or_default(),
+                r.party_id.as_deref().map(csv_field).unwrap_or_default(),
+                csv_field(&r.tags.join(" ")),
+            ];
+            out.push_str(&fields.join(","));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Rozdělí jeden CSV řádek, uvozovky se zdvojují jako v exportu
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+/// Načte CSV z exportu. Stačí sloupce user_id, display_name a bungie_name, zbytek je volitelný.
+pub fn parse_queue_csv(input: &str) -> BotResult<Vec<QueueExportRow>> {
+    let mut lines = input.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<String> = lines
+        .next()
+        .map(split_csv_line)
+        .ok_or_else(|| BotError::Chat("CSV is empty".to_string()))?
+        .into_iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+
+    let column = |name: &str| header.iter().position(|h| h == name);
+    let (Some(user_col), Some(name_col), Some(bungie_col)) = (column("user_id"), column("display_name"), column("bungie_name")) else {
+        return Err(BotError::Chat("CSV needs user_id, display_name and bungie_name columns".to_string()));
+    };
+
+    let mut rows = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let fields = split_csv_line(line);
+        let get = |col: Option<usize>| col.and_then(|c| fields.get(c)).map(|f| f.trim()).filter(|f| !f.is_empty());
+        let flag = |name: &str| get(column(name)).is_some_and(|f| f.eq_ignore_ascii_case("true") || f == "1");
+
+        let (Some(user_id), Some(display_name), Some(bungie_name)) = (get(Some(user_col)), get(Some(name_col)), get(Some(bungie_col))) else {
+            return Err(BotError::Chat(format!("CSV row {} is missing a user_id, display_name or bungie_name", i + 2)));
+        };
+
+        rows.push(QueueExportRow {
+            position: get(column("position")).and_then(|p| p.parse().ok()).unwrap_or(i as i32 + 1),
+            user_id: user_id.to_string(),
+            display_name: display_name.to_string(),
+            bungie_name: bungie_name.to_string(),
+            group_priority: get(column("group_priority")).and_then(|p| p.parse().ok()).unwrap_or_else(default_group_priority),
+            locked_first: flag("locked_first"),
+            priority_runs_left: get(column("priority_runs_left")).and_then(|p| p.parse().ok()).unwrap_or(0),
+            subscriber: flag("subscriber"),
+            vip: flag("vip"),
+            joined_at: get(column("joined_at")).and_then(|t| t.parse().ok()),
+            party_id: get(column("party_id")).map(String::from),
+            tags: get(column("tags")).map(|t| t.split_whitespace().map(String::from).collect()).unwrap_or_default(),
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{chat_event::chat_event::Platform, db::{initialize_database, queue::ban_from_queue}};
+
+    fn row(position: i32, user_id: &str, display_name: &str) -> QueueExportRow {
+        QueueExportRow {
+            position,
+            user_id: user_id.to_string(),
+            display_name: display_name.to_string(),
+            bungie_name: format!("{display_name}#0001"),
+            group_priority: default_group_priority(),
+            locked_first: false,
+            priority_runs_left: 0,
+            subscriber: false,
+            vip: false,
+            joined_at: None,
+            party_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn csv_round_trip_keeps_every_column() {
+        let mut leader = row(1, "twitch:1", "Comma, \"Quoted\" Guy");
+        leader.group_priority = 1;
+        leader.locked_first = true;
+        leader.priority_runs_left = 2;
+        leader.subscriber = true;
+        leader.joined_at = Some(1_700_000_000);
+        leader.party_id = Some("twitch:1".to_string());
+        leader.tags = vec!["titan".to_string(), "sherpa".to_string()];
+        let mut member = row(2, "kick:2", "plain");
+        member.vip = true;
+        member.party_id = Some("twitch:1".to_string());
+
+        let export = QueueExport { channel: "twitch:test".to_string(), queue: "main".to_string(), exported_at: 0, entries: vec![leader, member] };
+        let parsed = parse_queue_csv(&export.to_csv()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), serde_json::to_value(&export.entries).unwrap());
+    }
+
+    #[test]
+    fn csv_needs_only_the_identity_columns() {
+        let rows = parse_queue_csv("Display_Name,user_id,bungie_name\nfirst,twitch:1,First#0001\n\nsecond,twitch:2,Second#0002\n").unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[1].position, rows[1].group_priority, rows[1].tags.len()), (2, 2, 0));
+        assert_eq!(rows[1].display_name, "second");
+    }
+
+    #[test]
+    fn csv_rejects_missing_columns_and_fields() {
+        assert!(parse_queue_csv("").is_err());
+        assert!(parse_queue_csv("user_id,display_name\ntwitch:1,first").is_err());
+        assert!(parse_queue_csv("user_id,display_name,bungie_name\ntwitch:1,,First#0001").is_err());
+    }
+
+    #[sqlx::test]
+    async fn import_skips_banned_users(pool: PgPool) {
+        initialize_database(&pool).await.unwrap();
+        let owner = ChannelId::new(Platform::Twitch, "import_bans");
+        let banned = UserId::from_str("twitch:2").unwrap();
+        ban_from_queue(&pool, &owner, &banned, "banned", None, None, "mod").await.unwrap();
+
+        let rows = vec![row(1, "twitch:1", "first"), row(2, "twitch:2", "banned"), row(3, "twitch:3", "third")];
+        let report = import_queue(&pool, &owner, "main", rows, ImportMode::Replace).await.unwrap();
+        assert_eq!((report.imported, report.banned), (2, vec!["banned".to_string()]));
+    }
+}
@@ -0,0 +1,72 @@
+use sqlx::PgPool;
+
+use crate::bot::{commands::commands::BotResult, db::ChannelId};
+
+/// Tokeny streamerů z přihlášení do docku, potřeba pro EventSub a odměny za kanálové body
+pub const TWITCH_TOKENS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS krapbott_v2.twitch_tokens (
+        channel_id TEXT PRIMARY KEY,
+        broadcaster_id TEXT NOT NULL,
+        access_token TEXT NOT NULL,
+        refresh_token TEXT NOT NULL,
+        expires_at TIMESTAMPTZ NOT NULL,
+        scopes TEXT NOT NULL DEFAULT ''
+    );
+"#;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TwitchUserToken {
+    pub broadcaster_id: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp (seconds)
+    pub expires_at: i64,
+}
+
+pub async fn save_twitch_token(pool: &PgPool, channel: &ChannelId, broadcaster_id: &str, access_token: &str, refresh_token: &str, expires_in: i64, scopes: &str) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO krapbott_v2.twitch_tokens (channel_id, broadcaster_id, access_token, refresh_token, expires_at, scopes)
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5), $6)
+        ON CONFLICT (channel_id) DO UPDATE
+        SET broadcaster_id = EXCLUDED.broadcaster_id,
+            access_token = EXCLUDED.access_token,
+            refresh_token = EXCLUDED.refresh_token,
+            expires_at = EXCLUDED.expires_at,
+            scopes = EXCLUDED.scopes
+        "#,
+    ).bind(channel.as_str()).bind(broadcaster_id).bind(access_token).bind(refresh_token).bind(expires_in as f64).bind(scopes).execute(pool).await?;
+    Ok(())
+}
+
+/// Po obnovení tokenu se mění jen token a platnost
+pub async fn update_twitch_token(pool: &PgPool, channel: &ChannelId, access_token: &str, refresh_token: &str, expires_in: i64) -> BotResult<()> {
+    sqlx::query(
+        r#"
+        UPDATE krapbott_v2.twitch_tokens
+        SET access_token = $2, refresh_token = $3, expires_at = NOW() + make_interval(secs => $4)
+        WHERE channel_id = $1
+        "#,
+    ).bind(channel.as_str()).bind(access_token).bind(refresh_token).bind(expires_in as f64).execute(pool).await?;
+    Ok(())
+}
+
+pub async fn load_twitch_token(pool: &PgPool, channel: &ChannelId) -> BotResult<Option<TwitchUserToken>> {
+    let token = sqlx::query_as::<_, TwitchUserToken>(
+        r#"
+        SELECT broadcaster_id, access_token, refresh_token,
+               EXTRACT(EPOCH FROM expires_at)::BIGINT AS expires_at
+        FROM krapbott_v2.twitch_tokens
+        WHERE channel_id = $1
+        "#,
+    ).bind(channel.as_str()).fetch_optional(pool).await?;
+    Ok(token)
+}
+
+/// Kanály, jejichž streamer povolil správu odměn
+pub async fn channels_with_redemption_scope(pool: &PgPool) -> BotResult<Vec<String>> {
+    let channels = sqlx::query_scalar(
+        "SELECT channel_id FROM krapbott_v2.twitch_tokens WHERE scopes LIKE '%channel:manage:redemptions%'",
+    ).fetch_all(pool).await?;
+    Ok(channels)
+}
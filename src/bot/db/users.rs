@@ -41,7 +41,7 @@ pub struct User {
 /// Přidání / aktualizace uživatele
 pub async fn upsert_stream_user(pool: &PgPool, user: &User) -> BotResult<()> {
     // Vytvoříme unikátní ID jako platform:platform_user_id
-    let user_id = UserId::new(user.platform, user.id.clone());
+    let user_id = UserId::new(user.platform, &user.platform_user_id);
 
     sqlx::query(
         r#"
@@ -87,3 +87,47 @@ pub async fn get_queue_user_by_id(pool: &PgPool, user_id: &UserId) -> BotResult<
     }))
 }
 
+
+/// Divák nalezený podle jména
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ViewerMatch {
+    pub id: String,
+    pub login_name: String,
+    pub display_name: String,
+}
+
+/// Známí diváci, jejichž login nebo zobrazované jméno odpovídá `name`. Shody loginu jsou první.
+/// `platform = None` hledá na všech platformách.
+pub async fn find_users_by_name(pool: &PgPool, platform: Option<Platform>, name: &str) -> BotResult<Vec<ViewerMatch>> {
+    let rows = sqlx::query_as::<_, ViewerMatch>(
+        r#"
+        SELECT id, login_name, display_name FROM krapbott_v2.streamusers
+        WHERE ($1::text IS NULL OR platform = $1)
+          AND (LOWER(login_name) = LOWER($2) OR LOWER(display_name) = LOWER($2))
+        ORDER BY (LOWER(login_name) = LOWER($2)) DESC, id
+        LIMIT 10
+        "#,
+    ).bind(platform.map(|p| p.as_str().to_string())).bind(name).fetch_all(pool).await?;
+    Ok(rows)
+}
+
+/// Hráči ve frontách vlastníka podle jména ve frontě nebo v `streamusers`.
+/// `queue = None` prohledá všechny fronty kanálu.
+pub async fn find_queue_users_by_name(pool: &PgPool, owner: &ChannelId, queue: Option<&str>, platform: Option<Platform>, name: &str) -> BotResult<Vec<ViewerMatch>> {
+    let rows = sqlx::query_as::<_, ViewerMatch>(
+        r#"
+        SELECT DISTINCT ON (q.user_id)
+               q.user_id AS id,
+               COALESCE(s.login_name, LOWER(q.display_name)) AS login_name,
+               q.display_name
+        FROM krapbott_v2.queue q
+        LEFT JOIN krapbott_v2.streamusers s ON s.id = q.user_id
+        WHERE q.channel_id = $1
+          AND ($2::text IS NULL OR q.queue_name = $2)
+          AND ($3::text IS NULL OR q.user_id LIKE $3 || ':%')
+          AND (LOWER(q.display_name) = LOWER($4) OR LOWER(s.login_name) = LOWER($4) OR LOWER(s.display_name) = LOWER($4))
+        ORDER BY q.user_id
+        "#,
+    ).bind(owner.as_str()).bind(queue).bind(platform.map(|p| p.as_str().to_string())).bind(name).fetch_all(pool).await?;
+    Ok(rows)
+}
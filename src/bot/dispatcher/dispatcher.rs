@@ -120,14 +120,16 @@ pub async fn refresh_channel_dispatcher(channel: &ChannelId, state: Arc<AppState
         let registry = state.registry.clone();
         let dispatcher = registry.build_for_channel(channel, &config, alias_cfg.clone()).await;
         let mut runtime = state.runtime.dispatchers.write().await;
-        runtime.insert(
-            channel.clone(),
-            ChannelRuntime {
-                dispatcher,
-                tasks: Vec::new(),
-                alias_config: alias_cfg,
-            },
-        );
+        // Jen vyměníme dispatcher, běžící tasky kanálu zůstávají
+        match runtime.get_mut(channel) {
+            Some(existing) => {
+                existing.dispatcher = dispatcher;
+                existing.alias_config = alias_cfg;
+            }
+            None => {
+                runtime.insert(channel.clone(), ChannelRuntime::new(dispatcher, alias_cfg));
+            }
+        }
     }
     let _ = state.sse_bus.send(crate::bot::web::sse::SseEvent::AliasesUpdated { channel: channel.to_owned() });
 
@@ -8,6 +8,8 @@ use crate::bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::{Comma
 use tracing::warn;
 use kick_rust::KickClient;
 use crate::bot::runtime::channel_lifecycle::start_channel;
+use crate::bot::runtime::points::record_chat_activity;
+use crate::bot::commands::queue::subs::handle_support_event;
 
 pub trait ChatClient: Send + Sync {
     async fn send_message(&self, channel: &ChannelId, message: &str) -> BotResult<()>;
@@ -72,6 +74,10 @@ impl UnifiedChatClient {
 
 pub async fn handle_event(event: &mut ChatEvent, pool: PgPool, state: Arc<AppState>) -> BotResult<()> {
     let channel_id = ChannelId::new(event.platform.clone(), &event.channel);
+    if event.support.is_some() {
+        return handle_support_event(event, &pool, &state).await;
+    }
+    record_chat_activity(&state, event);
      let dispatcher = {
         let cache = state.runtime.dispatchers.read().await;
 
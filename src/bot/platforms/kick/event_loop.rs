@@ -10,7 +10,7 @@ use tracing::{info, warn};
 use crate::bot::{
     chat_event::chat_event::{ChatEvent, Platform},
     commands::commands::BotResult,
-    platforms::kick::kick::map_kick_msg,
+    platforms::kick::kick::{map_kick_msg, map_kick_support},
     state::def::{AppState, BotError},
 };
 
@@ -92,6 +92,11 @@ async fn run_kick_ws_reader(channel: String, chatroom_id: u64, tx: UnboundedSend
                         continue;
                     }
 
+                    if let Some(event) = map_kick_support(&raw, &channel) {
+                        let _ = tx.send(event);
+                        continue;
+                    }
+
                     match MessageParser::parse_message(&raw) {
                         Ok(Some(parsed)) => {
                             if let KickEventData::ChatMessage(chat_msg) = parsed.data {
@@ -3,15 +3,19 @@ use serde_json::Value;
 use tracing::info;
 
 use crate::bot::{
-    chat_event::chat_event::{ChatEvent, ChatUser, DisplayName, Platform, UserIdentity},
+    chat_event::chat_event::{ChatEvent, ChatUser, DisplayName, Platform, SupportEvent, SupportKind, UserIdentity},
     permissions::permissions::PermissionLevel,
 };
 
 pub fn map_kick_msg(msg: ChatMessageEvent, raw_json: Option<&str>) -> ChatEvent {
     info!("Received Kick message: {:?}", raw_json);
-    let permission = raw_json
-        .and_then(extract_permission_from_raw)
-        .unwrap_or(PermissionLevel::Follower);
+    let badges = raw_json.map(extract_badges_from_raw).unwrap_or_default();
+    let permission = if badges.is_empty() {
+        PermissionLevel::Follower
+    } else {
+        permission_from_badges(&badges)
+    };
+    let subscriber = badges.iter().any(|b| b == "subscriber");
     info!("Extracted permission: {:?}", permission);
     let display = msg
         .sender
@@ -40,20 +44,56 @@ pub fn map_kick_msg(msg: ChatMessageEvent, raw_json: Option<&str>) -> ChatEvent
                 display,
             },
             permission,
+            subscriber,
         }),
         follower: None,
+        support: None,
     }
 }
 
-fn extract_permission_from_raw(raw_json: &str) -> Option<PermissionLevel> {
+/// Sub a darované suby z Pusheru. Kick posílá jen username, user_id se dohledá až podle fronty.
+pub fn map_kick_support(raw_json: &str, channel: &str) -> Option<ChatEvent> {
     let mut value: Value = serde_json::from_str(raw_json).ok()?;
     decode_embedded_data_json(&mut value);
-    let badges = extract_badge_types(&value);
-    if badges.is_empty() {
-        return None;
-    }
+    let data = &value["data"];
+
+    let (login, support) = match value["event"].as_str()? {
+        "App\\Events\\SubscriptionEvent" => (data["username"].as_str()?, SupportEvent { kind: SupportKind::Sub, tier: 1, count: 1 }),
+        "App\\Events\\GiftedSubscriptionsEvent" => {
+            let count = data["gifted_usernames"].as_array().map(|g| g.len() as u32).unwrap_or(1);
+            (data["gifter_username"].as_str()?, SupportEvent { kind: SupportKind::Gift, tier: 1, count })
+        }
+        _ => return None,
+    };
 
-    Some(permission_from_badges(&badges))
+    Some(ChatEvent {
+        platform: Platform::Kick,
+        channel: channel.to_string(),
+        message: String::new(),
+        broadcaster_id: None,
+        user: Some(ChatUser {
+            identity: UserIdentity {
+                platform: Platform::Kick,
+                platform_user_id: String::new(),
+            },
+            name: DisplayName {
+                login: login.to_lowercase(),
+                display: login.to_string(),
+            },
+            permission: PermissionLevel::Subscriber,
+            subscriber: true,
+        }),
+        follower: None,
+        support: Some(support),
+    })
+}
+
+fn extract_badges_from_raw(raw_json: &str) -> Vec<String> {
+    let Ok(mut value) = serde_json::from_str::<Value>(raw_json) else {
+        return Vec::new();
+    };
+    decode_embedded_data_json(&mut value);
+    extract_badge_types(&value)
 }
 
 fn decode_embedded_data_json(root: &mut Value) {
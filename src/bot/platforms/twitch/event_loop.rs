@@ -4,7 +4,7 @@ use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tracing::info;
 use twitch_irc::message::ServerMessage;
 
-use crate::bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::commands::BotResult, platforms::twitch::twitch::{build_twitch_client, map_privmsg}, state::def::AppState};
+use crate::bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::commands::BotResult, platforms::twitch::twitch::{build_twitch_client, map_privmsg, map_usernotice}, state::def::AppState};
 
 
 
@@ -23,6 +23,13 @@ pub async fn run_twitch_loop(mut incoming: UnboundedReceiver<ServerMessage>, tx:
     }
 
     while let Some(msg) = incoming.recv().await {
+        if let ServerMessage::UserNotice(notice) = &msg {
+            if let Some(event) = map_usernotice(notice) {
+                info!("Twitch support event in channel {}: {}", notice.channel_login, notice.system_message);
+                let _ = tx.send(event);
+            }
+            continue;
+        }
         if let ServerMessage::Privmsg(privmsg) = msg {
             // Ignore forwarded shared-chat messages
             info!("Received Twitch message in channel {}: {}", privmsg.channel_login, privmsg.message_text);
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{info, warn};
+
+use crate::{api::twitch_api::subscribe_reward_redemptions, bot::{chat_event::chat_event::Platform, commands::{commands::BotResult, queue::rewards::{Redemption, handle_redemption}}, db::{ChannelId, twitch_tokens::channels_with_redemption_scope}, state::{def::{AppState, BotError}, state::get_broadcaster_token}}};
+
+const EVENTSUB_URL: &str = "wss://eventsub.wss.twitch.tv/ws";
+/// Twitch posílá keepalive po 10 s, po delším tichu je spojení mrtvé
+const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// EventSub pro odměny za kanálové body. Jedno websocket spojení na streamera,
+/// který se přihlásil do docku a má namapovanou aspoň jednu odměnu.
+pub async fn run_twitch_eventsub(pool: PgPool, state: Arc<AppState>) -> BotResult<()> {
+    let mut sessions: HashMap<ChannelId, JoinHandle<()>> = HashMap::new();
+
+    loop {
+        sessions.retain(|_, handle| !handle.is_finished());
+
+        match channels_with_redemption_scope(&pool).await {
+            Ok(channels) => {
+                let cfg = state.config.read().await;
+                for channel in channels {
+                    let channel = ChannelId::new(Platform::Twitch, &channel);
+                    let mapped = cfg.get_channel_config(&channel).is_some_and(|c| !c.rewards.is_empty());
+                    if mapped && !sessions.contains_key(&channel) {
+                        sessions.insert(channel.clone(), spawn_eventsub_session(channel, pool.clone(), state.clone()));
+                    }
+                }
+            }
+            Err(e) => warn!("EventSub: loading broadcaster tokens failed: {:?}", e),
+        }
+
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    }
+}
+
+fn spawn_eventsub_session(channel: ChannelId, pool: PgPool, state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = run_eventsub_session(&channel, &pool, &state).await {
+            warn!("EventSub [{}] stopped: {:?}", channel.as_str(), e);
+        }
+    })
+}
+
+async fn run_eventsub_session(channel: &ChannelId, pool: &PgPool, state: &Arc<AppState>) -> BotResult<()> {
+    let mut url = EVENTSUB_URL.to_string();
+    // Po session_reconnect zůstávají odběry platné, nový welcome se už nepřihlašuje
+    let mut subscribe = true;
+
+    loop {
+        let (mut ws, _response) = connect_async(url.as_str())
+            .await
+            .map_err(|e| BotError::Custom(format!("EventSub connect failed: {e}")))?;
+        info!("EventSub [{}] connected", channel.as_str());
+
+        let mut reconnect_url = None;
+        loop {
+            let frame = match tokio::time::timeout(KEEPALIVE_TIMEOUT, ws.next()).await {
+                Ok(Some(frame)) => frame,
+                Ok(None) | Err(_) => break,
+            };
+            let text = match frame {
+                Ok(Message::Text(text)) => text.to_string(),
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("EventSub [{}] websocket error: {}", channel.as_str(), e);
+                    break;
+                }
+            };
+
+            let msg: Value = match serde_json::from_str(&text) {
+                Ok(msg) => msg,
+                Err(_) => continue,
+            };
+            match msg["metadata"]["message_type"].as_str().unwrap_or_default() {
+                "session_welcome" if subscribe => {
+                    let session_id = msg["payload"]["session"]["id"].as_str().unwrap_or_default();
+                    let Some(token) = get_broadcaster_token(pool, state, channel).await? else {
+                        return Ok(());
+                    };
+                    subscribe_reward_redemptions(&state.secrets, &token.access_token, &token.broadcaster_id, session_id).await?;
+                    info!("EventSub [{}] subscribed to reward redemptions", channel.as_str());
+                }
+                "notification" => {
+                    let redemption: Redemption = match serde_json::from_value(msg["payload"]["event"].clone()) {
+                        Ok(redemption) => redemption,
+                        Err(e) => {
+                            warn!("EventSub [{}] unexpected event: {}", channel.as_str(), e);
+                            continue;
+                        }
+                    };
+                    let (pool, state, channel) = (pool.clone(), state.clone(), channel.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_redemption(&pool, &state, &channel, redemption).await {
+                            warn!("EventSub [{}] redemption failed: {:?}", channel.as_str(), e);
+                        }
+                    });
+                }
+                "session_reconnect" => {
+                    reconnect_url = msg["payload"]["session"]["reconnect_url"].as_str().map(String::from);
+                    break;
+                }
+                "revocation" => {
+                    warn!("EventSub [{}] subscription revoked: {}", channel.as_str(), msg["payload"]["subscription"]["status"]);
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        match reconnect_url {
+            Some(next) => {
+                url = next;
+                subscribe = false;
+            }
+            None => {
+                warn!("EventSub [{}] disconnected, reconnecting in 10s", channel.as_str());
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                url = EVENTSUB_URL.to_string();
+                subscribe = true;
+            }
+        }
+    }
+}
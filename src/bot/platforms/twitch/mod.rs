@@ -1,2 +1,3 @@
 pub mod event_loop;
+pub mod eventsub;
 pub mod twitch;
\ No newline at end of file
@@ -1,12 +1,12 @@
 use std::collections::HashSet;
 
 use tokio::sync::mpsc;
-use twitch_irc::message::ServerMessage;
+use twitch_irc::message::{ServerMessage, UserNoticeEvent, UserNoticeMessage};
 use twitch_irc::transport::websocket::{ConnectionUri, TLS, WSTransport};
 use twitch_irc::{SecureTCPTransport, login::StaticLoginCredentials, message::PrivmsgMessage};
 use twitch_irc::{ClientConfig, TwitchIRCClient};
 
-use crate::bot::chat_event::chat_event::{ChatEvent, ChatUser, DisplayName, Platform, UserIdentity};
+use crate::bot::chat_event::chat_event::{ChatEvent, ChatUser, DisplayName, Platform, SupportEvent, SupportKind, UserIdentity};
 use crate::bot::permissions::permissions::PermissionLevel;
 
 pub type TwitchClient = TwitchIRCClient<WSTransport<TLS>, StaticLoginCredentials>;
@@ -25,6 +25,8 @@ pub fn map_privmsg(msg: &PrivmsgMessage) -> ChatEvent {
     } else {
         PermissionLevel::Everyone
     };
+    // Zakládající subové mají místo subscriber badge founder
+    let subscriber = msg.badges.iter().any(|b| b.name == "subscriber" || b.name == "founder");
 
     ChatEvent {
         platform: Platform::Twitch,
@@ -41,9 +43,56 @@ pub fn map_privmsg(msg: &PrivmsgMessage) -> ChatEvent {
                 display: msg.sender.name.clone(),
             },
             permission,
-            
+            subscriber,
         }),
         follower: None,
+        support: None,
+    }
+}
+
+/// Sub, resub a darované suby z USERNOTICE. Anonymní dary nemá kdo dostat.
+pub fn map_usernotice(msg: &UserNoticeMessage) -> Option<ChatEvent> {
+    let support = match &msg.event {
+        UserNoticeEvent::SubOrResub { sub_plan, .. } => SupportEvent { kind: SupportKind::Sub, tier: sub_tier(sub_plan), count: 1 },
+        // Hromadný dar chodí i jako jednotlivé SubGift s community-gift-id, ty se nepočítají dvakrát
+        UserNoticeEvent::SubGift { is_sender_anonymous: false, sub_plan, .. }
+            if !msg.source.tags.0.contains_key("msg-param-community-gift-id") =>
+        {
+            SupportEvent { kind: SupportKind::Gift, tier: sub_tier(sub_plan), count: 1 }
+        }
+        UserNoticeEvent::SubMysteryGift { mass_gift_count, sub_plan, .. } => {
+            SupportEvent { kind: SupportKind::Gift, tier: sub_tier(sub_plan), count: *mass_gift_count as u32 }
+        }
+        _ => return None,
+    };
+
+    Some(ChatEvent {
+        platform: Platform::Twitch,
+        channel: msg.channel_login.clone(),
+        message: msg.system_message.clone(),
+        broadcaster_id: Some(msg.channel_id.clone()),
+        user: Some(ChatUser {
+            identity: UserIdentity {
+                platform: Platform::Twitch,
+                platform_user_id: msg.sender.id.clone(),
+            },
+            name: DisplayName {
+                login: msg.sender.login.clone(),
+                display: msg.sender.name.clone(),
+            },
+            permission: PermissionLevel::Subscriber,
+            subscriber: true,
+        }),
+        follower: None,
+        support: Some(support),
+    })
+}
+
+fn sub_tier(sub_plan: &str) -> u8 {
+    match sub_plan {
+        "2000" => 2,
+        "3000" => 3,
+        _ => 1,
     }
 }
 
@@ -1,4 +1,4 @@
-use crate::bot::db::ChannelId;
+use crate::bot::{chat_event::chat_event::SupportKind, commands::queue::requirements::UnmetRequirement, db::{ChannelId, stats::ChannelStats}, state::def::MAIN_QUEUE};
 
 pub struct Replies;
 
@@ -11,6 +11,33 @@ impl Replies {
         format!("❌ {} that was an invalid bungie name 😭please try again! Correct format would be: johnbungie#1234 💜", user)
     }
 
+    pub fn join_bungie_not_found(user: &str, name: &str) -> String {
+        format!("❌ {} Bungie couldn't find {} 😭 check the name and try again!", user, name)
+    }
+
+    pub fn join_bungie_unavailable(user: &str) -> String {
+        format!("⚠️ {} Bungie API is not responding right now, register your name later or try again in a bit", user)
+    }
+
+    pub fn join_requirement_unmet(user: &str, unmet: &UnmetRequirement) -> String {
+        match unmet {
+            UnmetRequirement::TooFew { label, min, have } => {
+                format!("❌ {user} this run needs at least {min} {label}, you have {have} 😭")
+            }
+            UnmetRequirement::TooMany { label, max, have } => {
+                format!("❌ {user} this run is for players with at most {max} {label}, you have {have}")
+            }
+        }
+    }
+
+    pub fn join_requirements_unavailable(user: &str) -> String {
+        format!("⚠️ {user} couldn't check your raid clears with Bungie right now, try again in a bit")
+    }
+
+    pub fn join_requirements(summary: &str) -> String {
+        format!("📋 Join requirements: {summary}")
+    }
+
     pub fn join_banned(user: &str, reason: Option<&str>) -> String {
         match reason {
             Some(r) => format!("❌ {} you are banned from queue for {} 😔", user, r),
@@ -18,8 +45,55 @@ impl Replies {
         }
     }
 
-    pub fn join_timed_out(user: &str) -> String {
-        format!("❌ {} you are timed out 😔 please try again later 💜", user)
+    pub fn join_timed_out(user: &str, reason: Option<&str>, seconds_left: i64) -> String {
+        let left = Replies::duration(seconds_left);
+        match reason {
+            Some(r) => format!("❌ {} you are timed out from queue for {} 😔 {} left 💜", user, r, left),
+            None => format!("❌ {} you are timed out 😔 please try again in {} 💜", user, left),
+        }
+    }
+
+    pub fn mod_banned(user: &str, reason: Option<&str>) -> String {
+        match reason {
+            Some(r) => format!("🔨 {user} has been banned from entering the queue for: {r}"),
+            None => format!("🔨 {user} has been banned from entering the queue."),
+        }
+    }
+
+    pub fn mod_timed_out(user: &str, seconds: i64, reason: Option<&str>) -> String {
+        let duration = Replies::duration(seconds);
+        match reason {
+            Some(r) => format!("⏱️ {user} has been timed out from entering the queue for {duration}: {r}"),
+            None => format!("⏱️ {user} has been timed out from entering the queue for {duration}."),
+        }
+    }
+
+    pub fn mod_unbanned(user: &str) -> String {
+        format!("User {user} has been unbanned from queue! They are free to enter again. 💜")
+    }
+
+    pub fn mod_not_banned(user: &str) -> String {
+        format!("User {user} was not found in the banlist.")
+    }
+
+    pub fn viewer_not_found(user: &str) -> String {
+        format!("User {user} not found FailFish")
+    }
+
+    pub fn viewer_ambiguous(user: &str, options: &[String]) -> String {
+        format!("{user} matches more viewers: {} — use platform:login", options.join(", "))
+    }
+
+    /// 90 -> "2m", 7200 -> "2h 0m", 2 dny -> "2d 0h"
+    pub fn duration(seconds: i64) -> String {
+        let minutes = (seconds.max(0) + 59) / 60;
+        if minutes < 60 {
+            format!("{minutes}m")
+        } else if minutes < 60 * 24 {
+            format!("{}h {}m", minutes / 60, minutes % 60)
+        } else {
+            format!("{}d {}h", minutes / (60 * 24), minutes / 60 % 24)
+        }
     }
 
     pub fn add_to_queue(user: &str) -> String {
@@ -34,6 +108,144 @@ impl Replies {
         format!("🎯{} have won the next run! 🥳 Please be ready for an invite! 💜", user)
     }
 
+    pub fn raffle_odds(user: &str, odds: f64) -> String {
+        format!("🎲 {user} you are entered in the raffle, your chance in the next draw is ~{:.0}%", odds * 100.0)
+    }
+
+    pub fn raffle_drawn(user: &str) -> String {
+        format!("🎯 {user} you were drawn for the current run! 💜")
+    }
+
+    pub fn raffle_policy(summary: &str) -> String {
+        format!("🎲 Raffle weights: {summary}")
+    }
+
+    pub fn group_balance(summary: &str) -> String {
+        format!("⚖️ Group balance: {summary}")
+    }
+
+    pub fn raffle_bonus_set(user: &str, bonus: f64) -> String {
+        format!("🎲 {user} now has a raffle bonus of {bonus:+}")
+    }
+
+    pub fn queue_unknown(queue: &str) -> String {
+        format!("❌ Queue {queue} does not exist, see !queues")
+    }
+
+    pub fn queue_created(queue: &str) -> String {
+        format!("✅ Queue {queue} created! Viewers can join with !join {queue}")
+    }
+
+    pub fn queue_deleted(queue: &str) -> String {
+        format!("🗑️ Queue {queue} was deleted")
+    }
+
+    pub fn queues_list(summary: &str) -> String {
+        format!("📋 Queues: {summary}")
+    }
+
+    pub fn party_usage() -> String {
+        "Usage: !join party @friend1 @friend2 — they then type !accept (not available in raffle mode)".to_string()
+    }
+
+    pub fn party_too_big(user: &str, teamsize: usize) -> String {
+        format!("❌ {user}, a party can have at most {teamsize} people")
+    }
+
+    pub fn party_invited(leader: &str, invitees: &[String]) -> String {
+        let mentions = invitees.iter().map(|i| format!("@{i}")).collect::<Vec<_>>().join(" ");
+        format!("🎉 {mentions} type !accept within 2 minutes to play with {leader}")
+    }
+
+    pub fn party_joined(user: &str, leader: &str) -> String {
+        format!("🎉 {user} joined {leader}'s party, you will play in the same group 💜")
+    }
+
+    pub fn party_gone(user: &str) -> String {
+        format!("❌ {user}, that party is no longer in the queue")
+    }
+
+    pub fn party_full(user: &str, teamsize: usize) -> String {
+        format!("❌ {user}, that party already has {teamsize} people")
+    }
+
+    pub fn party_playing(user: &str, leader: &str) -> String {
+        format!("❌ {user}, {leader}'s party is playing right now, ask for a new invite after the next !next")
+    }
+
+    pub fn party_no_invite(user: &str) -> String {
+        format!("{user}, you don't have any party invite waiting")
+    }
+
+    pub fn party_declined(user: &str, leader: &str) -> String {
+        format!("{user} declined {leader}'s party invite")
+    }
+
+    pub fn queue_exported(queue: &str, count: usize, link: &str) -> String {
+        format!("💾 Queue {queue} saved ({count} people): {link} (add ?format=csv for CSV, valid 30 min)")
+    }
+
+    pub fn ready_check_started(users: &str, seconds: u64) -> String {
+        format!("⏰ Ready check! {users} type !ready within {seconds}s or you lose your spot 💜")
+    }
+
+    pub fn ready_confirmed(user: &str, ready: usize, total: usize) -> String {
+        format!("✅ {user} is ready! ({ready}/{total})")
+    }
+
+    pub fn ready_all() -> String {
+        "🟢 Everyone is ready, let's go! 🥳".to_string()
+    }
+
+    pub fn ready_too_late(user: &str) -> String {
+        format!("⌛ {user} the ready check already ran out, sorry")
+    }
+
+    pub fn ready_missed_back(users: &str) -> String {
+        format!("⌛ {users} didn't ready up in time and got moved to the back of the queue 😴")
+    }
+
+    pub fn ready_missed_dropped(users: &str) -> String {
+        format!("⌛ {users} didn't ready up in time and got removed from the queue 😴")
+    }
+
+    pub fn ready_check_policy(summary: &str) -> String {
+        format!("⏰ Ready check: {summary}")
+    }
+
+    pub fn undo_done(action: &str, queue: &str) -> String {
+        if queue == MAIN_QUEUE {
+            format!("↩️ Undid the last {action}, the main queue is back to how it was")
+        } else {
+            format!("↩️ Undid the last {action} in queue {queue}")
+        }
+    }
+
+    pub fn undo_empty() -> String {
+        "↩️ Nothing to undo".to_string()
+    }
+
+    pub fn join_cooldown(user: &str, runs: i64, minutes: i64) -> String {
+        let wait = match (runs, minutes) {
+            (0, m) => format!("{m} min"),
+            (r, 0) => format!("{r} more run(s)"),
+            (r, m) => format!("{r} more run(s) and {m} min"),
+        };
+        format!("⏳ {user} you just played! Give others a chance, you can join again in {wait} 💜")
+    }
+
+    pub fn cooldown_policy(summary: &str) -> String {
+        format!("⏳ Join cooldown after a run: {summary}")
+    }
+
+    pub fn cooldown_cleared(user: &str) -> String {
+        format!("✅ {user}'s cooldown was cleared, they can join right away!")
+    }
+
+    pub fn auto_queue_policy(summary: &str) -> String {
+        format!("🤖 Auto open/close: {summary}")
+    }
+
     pub fn queue_empty(broadcaster: &str) -> String {
         format!("💀 {} the queue is empty..? 👁👄👁", broadcaster)
     }
@@ -70,13 +282,45 @@ impl Replies {
         format!("⭐💎{user} has been given {number} priority runs!  💎⭐")
     }
 
-    pub fn pos_reply(group: i64, index: &str, max_count: &str, user: &str) -> String {
+    pub fn pos_reply(group: i64, index: &str, max_count: &str, user: &str, eta_minutes: Option<i64>) -> String {
+        let eta = match eta_minutes {
+            Some(0) => " ⏳ up any moment now".to_string(),
+            Some(m) => format!(" ⏳ ~{m} min"),
+            None => String::new(),
+        };
         if group == 1 {
             format!("📋 {user} you are at position {}/{} and in LIVE group! DinoDance", index, max_count)
         } else if group == 2 {
-            format!("📋 {user} you are at position {}/{} and in NEXT group! GoldPLZ", index, max_count)
+            format!("📋 {user} you are at position {}/{} and in NEXT group! GoldPLZ{eta}", index, max_count)
         } else {
-            format!("📋 {user} you are at position {}/{} (Group {}) 💜", index, max_count, group)
+            format!("📋 {user} you are at position {}/{} (Group {}) 💜{eta}", index, max_count, group)
+        }
+    }
+
+    pub fn history_empty() -> String {
+        "📜 No runs have been played yet 👀".to_string()
+    }
+
+    pub fn history_recent(runs: &str) -> String {
+        format!("📜 Last runs: {runs}")
+    }
+
+    pub fn history_played(user: &str, count: usize, hours: i64, last: &str) -> String {
+        format!("✅ {user} played {count} run(s) in the last {hours}h, last one {last} 🫡")
+    }
+
+    pub fn history_not_played(user: &str, hours: i64) -> String {
+        format!("❌ {user} hasn't played in the last {hours}h 💜")
+    }
+
+    pub fn time_ago(seconds: i64) -> String {
+        let minutes = seconds.max(0) / 60;
+        if minutes < 1 {
+            "just now".to_string()
+        } else if minutes < 60 {
+            format!("{minutes}m ago")
+        } else {
+            format!("{}h {}m ago", minutes / 60, minutes % 60)
         }
     }
 
@@ -87,4 +331,203 @@ impl Replies {
     pub fn queue_runs_reset(channel: &ChannelId) -> String {
         format!("📋 Runs reset for {}", channel.as_str())
     }
-}
\ No newline at end of file
+
+    pub fn points_balance(user: &str, points: i64, rank: i64) -> String {
+        format!("💰 {user} has {points} points (#{rank})")
+    }
+
+    pub fn points_none(user: &str) -> String {
+        format!("💰 {user} has no points yet")
+    }
+
+    pub fn points_given(user: &str, amount: i64, balance: i64) -> String {
+        if amount >= 0 {
+            format!("💰 {user} got {amount} points, now has {balance}")
+        } else {
+            format!("💰 {user} lost {} points, now has {balance}", -amount)
+        }
+    }
+
+    pub fn points_leaderboard(entries: &[(String, i64)]) -> String {
+        if entries.is_empty() {
+            return "💰 Nobody has any points yet".to_string();
+        }
+        let list = entries.iter().enumerate().map(|(i, (name, points))| format!("{}. {} ({})", i + 1, name, points)).collect::<Vec<_>>().join(", ");
+        format!("🏆 {list}")
+    }
+
+    pub fn points_not_enough(user: &str, cost: i64) -> String {
+        format!("❌ {user}, you need {cost} points for that")
+    }
+
+    pub fn points_prio_bought(user: &str, cost: i64, left: i64) -> String {
+        format!("⭐ {user} spent {cost} points on a priority run, {left} left")
+    }
+
+    pub fn points_prio_not_queued(user: &str) -> String {
+        format!("❌ {user}, join the queue first, your points were refunded")
+    }
+
+    pub fn points_prio_disabled() -> String {
+        "❌ Buying priority with points is turned off".to_string()
+    }
+
+    pub fn points_policy(summary: &str) -> String {
+        format!("💰 Points: {summary}")
+    }
+
+    pub fn reward_prio(user: &str) -> String {
+        format!("⭐ {user} skipped the line and plays in the next group!")
+    }
+
+    pub fn reward_prio_in_group(user: &str) -> String {
+        format!("❌ {user} you are already in the current group, no need to skip the line")
+    }
+
+    pub fn reward_already_queued(user: &str) -> String {
+        format!("❌ {user} you are already in the queue")
+    }
+
+    pub fn reward_failed(user: &str) -> String {
+        format!("❌ {user} something went wrong with your reward")
+    }
+
+    pub fn reward_refunded(title: &str) -> String {
+        format!("Your points for \"{title}\" were refunded 💜")
+    }
+
+    pub fn reward_not_refunded(title: &str) -> String {
+        format!("Ask a mod to refund your points for \"{title}\"")
+    }
+
+    pub fn reward_list(mappings: &[String]) -> String {
+        if mappings.is_empty() {
+            return "🎁 No channel point rewards are mapped, see !reward add".to_string();
+        }
+        format!("🎁 Rewards: {}", mappings.join(", "))
+    }
+
+    pub fn reward_added(summary: &str) -> String {
+        format!("✅ Reward mapped: {summary}")
+    }
+
+    pub fn reward_removed(title: &str) -> String {
+        format!("✅ Reward \"{title}\" no longer does anything")
+    }
+
+    pub fn reward_unknown(title: &str) -> String {
+        format!("❌ No reward called \"{title}\" is mapped")
+    }
+
+    pub fn reward_needs_login() -> String {
+        "❌ The streamer has to log in to the dock first so the bot can manage rewards".to_string()
+    }
+
+    pub fn reward_twitch_only() -> String {
+        "❌ Channel point rewards only exist on Twitch".to_string()
+    }
+
+    pub fn sub_prio_granted(user: &str, kind: SupportKind, runs: i32) -> String {
+        let thanks = match kind {
+            SupportKind::Sub => "subscribing",
+            SupportKind::Gift => "the gifted subs",
+        };
+        if runs == 1 {
+            format!("💜 Thanks {user} for {thanks}! You play in the next group ⭐")
+        } else {
+            format!("💜 Thanks {user} for {thanks}! You play in the next {runs} groups ⭐")
+        }
+    }
+
+    pub fn sub_prio_pending(user: &str, kind: SupportKind, runs: i32) -> String {
+        let thanks = match kind {
+            SupportKind::Sub => "subscribing",
+            SupportKind::Gift => "the gifted subs",
+        };
+        let groups = if runs == 1 { "the next group".to_string() } else { format!("the next {runs} groups") };
+        format!("💜 Thanks {user} for {thanks}! !join within a day and you play in {groups} ⭐")
+    }
+
+    pub fn sub_prio_applied(runs: i32) -> String {
+        if runs == 1 {
+            "⭐ Your sub prio is in, you play in the next group".to_string()
+        } else {
+            format!("⭐ Your sub prio is in, you play in the next {runs} groups")
+        }
+    }
+
+    pub fn sub_prio_rules(rules: &[String]) -> String {
+        if rules.is_empty() {
+            return "💜 Subs don't grant priority, see !subprio sub <tier> <runs>".to_string();
+        }
+        format!("💜 Sub priority: {}", rules.join(", "))
+    }
+
+    pub fn stats_summary(stats: &ChannelStats) -> String {
+        if stats.runs == 0 {
+            return format!("📊 No runs in the last {} days", stats.days);
+        }
+        let mut parts = vec![
+            format!("{} runs in {} stream(s)", stats.runs, stats.streams),
+            format!("{} players", stats.unique_participants),
+        ];
+        if let Some(wait) = stats.avg_wait_seconds {
+            parts.push(format!("avg wait {}", Self::duration(wait)));
+        }
+        if let Some(fill) = stats.avg_fill {
+            parts.push(format!("groups {:.0}% full", fill * 100.0));
+        }
+        if stats.raffle_runs > 0 {
+            parts.push(format!("{} queue / {} raffle", stats.runs - stats.raffle_runs, stats.raffle_runs));
+        }
+        let mut reply = format!("📊 Last {} days: {}", stats.days, parts.join(", "));
+        if !stats.top_viewers.is_empty() {
+            let regulars = stats.top_viewers.iter().map(|v| format!("{} ({})", v.display_name, v.streams)).collect::<Vec<_>>().join(", ");
+            reply.push_str(&format!(" | Regulars: {regulars}"));
+        }
+        reply
+    }
+
+    pub fn bungie_total(name: &str, clears: i64) -> String {
+        format!("🏆 {name} has {clears} raid clears in total")
+    }
+
+    pub fn bungie_challenges(name: &str, activity: &str, challenges: &[String]) -> String {
+        if challenges.is_empty() {
+            return format!("❌ No master challenges found for {name} in {}", activity.to_uppercase());
+        }
+        format!("🏆 {name} {}: {}", activity.to_uppercase(), challenges.join(" || "))
+    }
+
+    pub fn bungie_not_registered(user: &str) -> String {
+        format!("❌ {user} has no Bungie name saved, use !register BungieName#1234")
+    }
+
+    pub fn bungie_name_not_found(name: &str) -> String {
+        format!("❌ {name} doesn't exist, check if the Bungie name is correct")
+    }
+
+    pub fn presence_offline(names: &str) -> String {
+        format!("🔴 Not in Destiny right now: {names}")
+    }
+
+    pub fn presence_skipped(names: &str) -> String {
+        format!("🔴 {names} not in Destiny, moved to the back of the queue")
+    }
+
+    pub fn presence_policy(summary: &str) -> String {
+        format!("🎮 In-game check: {summary}")
+    }
+
+    pub fn bungie_private(name: &str) -> String {
+        format!("🔒 {name} has a private Destiny profile")
+    }
+
+    pub fn bungie_name_changed(user: &str, old: &str, new: &str) -> String {
+        format!("✏️ Mods: {user} changed their Bungie name from {old} to {new}")
+    }
+
+    pub fn bungie_unavailable() -> String {
+        "⚠️ Bungie API is not responding right now, try again later".to_string()
+    }
+}
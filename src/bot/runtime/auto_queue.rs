@@ -0,0 +1,207 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use chrono::{Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{api::{kick_api::is_kick_channel_live, twitch_api::is_channel_live}, bot::{chat_event::chat_event::Platform, commands::{commands::BotResult, queue::logic::{queue_settings, resolve_queue_owner, set_queue_open}}, db::ChannelId, handler::handler::ChatClient, replies::Replies, state::{def::{AppState, BotError, MAIN_QUEUE}, state::get_twitch_access_token}, web::sse::SseEvent}};
+
+/// Jak často se kontroluje stream / rozvrh
+const AUTO_QUEUE_TICK: Duration = Duration::from_secs(60);
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoQueueMode {
+    #[default]
+    Off,
+    /// Otevřít po začátku streamu, zavřít po konci
+    Live,
+    /// Týdenní rozvrh v časové zóně kanálu
+    Schedule,
+}
+
+/// Jeden blok rozvrhu, např. `mon 18:00-22:00`. Když je `close` dřív než `open`, blok končí další den.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ScheduleSlot {
+    pub day: String,
+    pub open: String,
+    pub close: String,
+}
+
+/// Automatické otevírání / zavírání hlavní fronty
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct AutoQueue {
+    #[serde(default)]
+    pub mode: AutoQueueMode,
+    /// Minutes after going live before the queue opens
+    #[serde(default)]
+    pub open_delay_minutes: u32,
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default)]
+    pub schedule: Vec<ScheduleSlot>,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+impl Default for AutoQueue {
+    fn default() -> Self {
+        AutoQueue {
+            mode: AutoQueueMode::Off,
+            open_delay_minutes: 0,
+            timezone: default_timezone(),
+            schedule: Vec::new(),
+        }
+    }
+}
+
+impl ScheduleSlot {
+    pub fn parse(day: &str, open: &str, close: &str) -> Option<Self> {
+        day.parse::<Weekday>().ok()?;
+        NaiveTime::parse_from_str(open, "%H:%M").ok()?;
+        NaiveTime::parse_from_str(close, "%H:%M").ok()?;
+        Some(ScheduleSlot { day: day.to_lowercase(), open: open.to_string(), close: close.to_string() })
+    }
+
+    fn contains(&self, weekday: Weekday, time: NaiveTime) -> bool {
+        let (Ok(day), Ok(open), Ok(close)) = (
+            self.day.parse::<Weekday>(),
+            NaiveTime::parse_from_str(&self.open, "%H:%M"),
+            NaiveTime::parse_from_str(&self.close, "%H:%M"),
+        ) else {
+            return false;
+        };
+
+        if open < close {
+            weekday == day && time >= open && time < close
+        } else {
+            (weekday == day && time >= open) || (weekday == day.succ() && time < close)
+        }
+    }
+}
+
+impl AutoQueue {
+    /// Má být fronta podle rozvrhu právě otevřená?
+    pub fn scheduled_open(&self) -> BotResult<bool> {
+        let tz: Tz = self.timezone.parse().map_err(|_| BotError::Chat(format!("Unknown timezone {}", self.timezone)))?;
+        let now = Utc::now().with_timezone(&tz);
+        Ok(self.schedule.iter().any(|s| s.contains(now.weekday(), now.time())))
+    }
+
+    pub fn summary(&self) -> String {
+        match self.mode {
+            AutoQueueMode::Off => "off".to_string(),
+            AutoQueueMode::Live => format!("opens {} min after going live, closes when the stream ends", self.open_delay_minutes),
+            AutoQueueMode::Schedule if self.schedule.is_empty() => format!("schedule ({}) with no slots yet", self.timezone),
+            AutoQueueMode::Schedule => {
+                let slots = self.schedule.iter().map(|s| format!("{} {}-{}", s.day, s.open, s.close)).collect::<Vec<_>>().join(", ");
+                format!("schedule ({}): {}", self.timezone, slots)
+            }
+        }
+    }
+}
+
+pub async fn channel_is_live(state: &AppState, channel: &ChannelId) -> BotResult<bool> {
+    match channel.platform() {
+        Platform::Twitch => {
+            let token = get_twitch_access_token(state).await?;
+            Ok(is_channel_live(channel.channel(), &token, &state.secrets.bot_id).await?)
+        }
+        Platform::Kick => is_kick_channel_live(channel.channel()).await,
+        Platform::Obs => Ok(false),
+    }
+}
+
+async fn apply(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, open: bool) -> BotResult<()> {
+    if queue_settings(state, channel, MAIN_QUEUE).await?.open == open {
+        return Ok(());
+    }
+
+    info!("Auto queue: {} {}", if open { "opening" } else { "closing" }, channel.as_str());
+    set_queue_open(pool, state.clone(), channel, MAIN_QUEUE, open).await?;
+    let _ = state.sse_bus.send(SseEvent::QueueStateChanged { channel: channel.clone(), open });
+    let msg = if open { Replies::queue_opened() } else { Replies::queue_closed() };
+    state.chat_client.send_message(channel, &msg).await?;
+    Ok(())
+}
+
+/// Stav mezi ticky. Reaguje se jen na změny, takže ruční !open / !close mezi nimi platí.
+#[derive(Default)]
+struct AutoQueueTracker {
+    live_since: Option<Instant>,
+    opened_this_stream: bool,
+    scheduled: Option<bool>,
+}
+
+async fn tick(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, tracker: &mut AutoQueueTracker) -> BotResult<()> {
+    let auto = {
+        let cfg = state.config.read().await;
+        match cfg.get_channel_config(channel) {
+            Some(c) => c.auto_queue.clone(),
+            None => return Ok(()),
+        }
+    };
+
+    // Sdílená fronta patří jinému kanálu, ten si ji řídí sám
+    if resolve_queue_owner(state, channel).await? != *channel {
+        return Ok(());
+    }
+
+    match auto.mode {
+        AutoQueueMode::Off => {
+            *tracker = AutoQueueTracker::default();
+        }
+        AutoQueueMode::Live => {
+            tracker.scheduled = None;
+            let live = channel_is_live(state, channel).await?;
+            match (live, tracker.live_since) {
+                (true, None) => {
+                    tracker.live_since = Some(Instant::now());
+                    tracker.opened_this_stream = false;
+                }
+                (false, Some(_)) => {
+                    tracker.live_since = None;
+                    apply(pool, state, channel, false).await?;
+                }
+                _ => {}
+            }
+
+            if let Some(since) = tracker.live_since {
+                let delay = Duration::from_secs(auto.open_delay_minutes as u64 * 60);
+                if !tracker.opened_this_stream && since.elapsed() >= delay {
+                    tracker.opened_this_stream = true;
+                    apply(pool, state, channel, true).await?;
+                }
+            }
+        }
+        AutoQueueMode::Schedule => {
+            tracker.live_since = None;
+            let open = auto.scheduled_open()?;
+            if tracker.scheduled != Some(open) {
+                tracker.scheduled = Some(open);
+                apply(pool, state, channel, open).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Per-channel task, ukládá se do `ChannelRuntime.tasks`
+pub fn spawn_auto_queue(channel: ChannelId, state: Arc<AppState>, pool: PgPool) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut tracker = AutoQueueTracker::default();
+        let mut interval = tokio::time::interval(AUTO_QUEUE_TICK);
+        loop {
+            interval.tick().await;
+            if let Err(e) = tick(&pool, &state, &channel, &mut tracker).await {
+                warn!("Auto queue for {} failed: {:?}", channel.as_str(), e);
+            }
+        }
+    })
+}
@@ -0,0 +1,82 @@
+use std::{sync::Arc, time::Duration};
+
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{api::api::get_bungie_name, bot::{commands::{commands::BotResult, queue::logic::queue_channels}, db::{bungie::{mark_bungie_name_checked, next_bungie_name_check, update_bungie_name}, history::fetch_played_channels}, handler::handler::ChatClient, replies::Replies, state::def::AppState, web::sse::SseEvent}};
+
+/// Jak často se jedno jméno kontroluje znovu
+const RECHECK_AFTER_HOURS: i32 = 24;
+/// Pauza mezi dvěma hráči, ať refresher nebere Bungie limit joinům
+const CHECK_DELAY: Duration = Duration::from_secs(5);
+/// Když není koho kontrolovat, nebo Bungie neodpovídá
+const IDLE_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// Zkontroluje jednoho hráče, `false` když nikdo nečeká na kontrolu nebo Bungie neodpovídá
+async fn check_next(pool: &PgPool, state: &AppState) -> BotResult<bool> {
+    let Some((user_id, display_name, stored, membership)) = next_bungie_name_check(pool, RECHECK_AFTER_HOURS).await? else {
+        return Ok(false);
+    };
+
+    let current = match get_bungie_name(&state.bungie, &membership).await {
+        Ok(name) => name,
+        Err(err) if err.is_unavailable() => {
+            warn!("Bungie name refresh for {} failed: {}", user_id, err);
+            return Ok(false);
+        }
+        // Smazaný účet nebo membership bez jména, zkusíme zase za den
+        Err(_) => {
+            mark_bungie_name_checked(pool, &user_id).await?;
+            return Ok(true);
+        }
+    };
+
+    if current == stored {
+        mark_bungie_name_checked(pool, &user_id).await?;
+        return Ok(true);
+    }
+
+    info!("Bungie name of {} changed: {} -> {}", user_id, stored, current);
+    let queue_owners = update_bungie_name(pool, &state.bungie, &user_id, &stored, &current).await?;
+    for owner in &queue_owners {
+        let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner.clone() });
+    }
+
+    // Modům všech kanálů, které hráče znají, i když zrovna nečeká ve frontě
+    let mut owners = fetch_played_channels(pool, &user_id).await?;
+    owners.extend(queue_owners);
+    let mut channels = Vec::new();
+    for owner in owners {
+        for channel in queue_channels(state, &owner).await {
+            if !channels.contains(&channel) {
+                channels.push(channel);
+            }
+        }
+    }
+
+    let reply = Replies::bungie_name_changed(&display_name, &stored, &current);
+    for channel in channels {
+        if let Err(e) = state.chat_client.send_message(&channel, &reply).await {
+            warn!("Announcing Bungie name change in {} failed: {:?}", channel.as_str(), e);
+        }
+    }
+    Ok(true)
+}
+
+/// Globální task: uložená Bungie jména se postupně (nejstarší kontrola první) srovnávají s Bungie
+pub fn spawn_bungie_name_refresh(state: Arc<AppState>, pool: PgPool) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let delay = match check_next(&pool, &state).await {
+                Ok(true) => CHECK_DELAY,
+                Ok(false) => IDLE_DELAY,
+                Err(e) => {
+                    warn!("Bungie name refresh failed: {:?}", e);
+                    IDLE_DELAY
+                }
+            };
+            tokio::time::sleep(delay).await;
+        }
+    })
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use sqlx::PgPool;
 
-use crate::bot::{commands::{CommandRegistry, commands::BotResult}, db::{ChannelId, aliases::fetch_aliases_from_db, config::load_bot_config_from_db}, dispatcher::dispatcher::build_dispatcher_for_channel, runtime::channel_runtime::ChannelRuntime, state::def::AppState};
+use crate::bot::{commands::{CommandRegistry, commands::BotResult}, db::{ChannelId, aliases::fetch_aliases_from_db, config::load_bot_config_from_db}, dispatcher::dispatcher::build_dispatcher_for_channel, runtime::{auto_queue::spawn_auto_queue, channel_runtime::ChannelRuntime, points::spawn_points_loop}, state::def::AppState};
 
 pub async fn start_channel(channel_id: ChannelId, state: Arc<AppState>, pool: &PgPool) -> BotResult<()> {
     let aliases = fetch_aliases_from_db(&channel_id, pool).await?;
@@ -13,12 +13,19 @@ pub async fn start_channel(channel_id: ChannelId, state: Arc<AppState>, pool: &P
 
     let mut runtime = ChannelRuntime::new(dispatcher, aliases);
 
-    // (Later) attach per-channel tasks here
-    // Example:
-    // let task = start_points_loop(channel_id.clone(), state.clone());
-    // runtime.add_task(task);
+    // Per-channel tasks
+    runtime.add_task(spawn_auto_queue(channel_id.clone(), state.clone(), pool.clone()));
+    let points_enabled = state.config.read().await.get_channel_config(&channel_id).is_some_and(|c| c.packages.iter().any(|p| p == "points"));
+    if points_enabled {
+        runtime.add_task(spawn_points_loop(channel_id.clone(), state.clone(), pool.clone()));
+    } else {
+        state.runtime.chat_activity.remove(&channel_id);
+    }
 
-    state.runtime.dispatchers.write().await.insert(channel_id, runtime);
+    // Starý runtime (pokud nějaký je) nesmí nechat tasky běžet dál
+    if let Some(previous) = state.runtime.dispatchers.write().await.insert(channel_id, runtime) {
+        previous.shutdown();
+    }
 
     Ok(())
 }
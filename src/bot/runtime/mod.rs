@@ -1,2 +1,5 @@
 pub mod channel_runtime;
-pub mod channel_lifecycle;
\ No newline at end of file
+pub mod channel_lifecycle;
+pub mod auto_queue;
+pub mod points;
+pub mod bungie_names;
\ No newline at end of file
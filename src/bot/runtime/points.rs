@@ -0,0 +1,147 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::{api::twitch_api::{Chatter, fetch_lurkers, resolve_twitch_user_id}, bot::{chat_event::chat_event::{ChatEvent, Platform}, commands::commands::BotResult, db::{ChannelId, UserId, points::{PointsAward, award_points}}, runtime::auto_queue::channel_is_live, state::{def::AppState, state::get_twitch_access_token}}};
+
+/// Kolik bodů se rozdává a za co. Body se počítají na kanál, ne na sdílenou frontu.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PointsPolicy {
+    /// Každých `interval_minutes` za přítomnost v chatu (jen Twitch, Kick nemá seznam diváků)
+    #[serde(default = "default_presence")]
+    pub presence: i64,
+    /// Navíc, pokud divák od minulého ticku něco napsal
+    #[serde(default = "default_chat")]
+    pub chat: i64,
+    #[serde(default = "default_interval")]
+    pub interval_minutes: u32,
+    /// Cena `!buyprio`, 0 = vypnuto
+    #[serde(default = "default_prio_cost")]
+    pub prio_cost: i64,
+}
+
+fn default_presence() -> i64 {
+    10
+}
+
+fn default_chat() -> i64 {
+    5
+}
+
+fn default_interval() -> u32 {
+    5
+}
+
+fn default_prio_cost() -> i64 {
+    1000
+}
+
+impl Default for PointsPolicy {
+    fn default() -> Self {
+        PointsPolicy {
+            presence: default_presence(),
+            chat: default_chat(),
+            interval_minutes: default_interval(),
+            prio_cost: default_prio_cost(),
+        }
+    }
+}
+
+impl PointsPolicy {
+    pub fn summary(&self) -> String {
+        format!(
+            "+{} every {} min watching, +{} for chatting, prio costs {}",
+            self.presence,
+            self.interval_minutes,
+            self.chat,
+            if self.prio_cost > 0 { self.prio_cost.to_string() } else { "off".to_string() }
+        )
+    }
+}
+
+/// Kdo psal od posledního ticku, user_id -> display_name. Záznam existuje jen pro kanály s balíčkem `points`.
+pub type ChatActivity = DashMap<ChannelId, HashMap<String, String>>;
+
+/// Zapíše aktivitu z chatu, volá se pro každou zprávu
+pub fn record_chat_activity(state: &AppState, event: &ChatEvent) {
+    let Some(user) = &event.user else { return; };
+    let channel = ChannelId::new(event.platform, &event.channel);
+    if let Some(mut active) = state.runtime.chat_activity.get_mut(&channel) {
+        let user_id = UserId::new(user.identity.platform, &user.identity.platform_user_id);
+        active.insert(user_id.as_str().to_string(), user.name.display.clone());
+    }
+}
+
+/// Diváci v chatu kanálu, id broadcastera se zjistí jednou a pamatuje
+async fn fetch_present(state: &AppState, channel: &ChannelId, broadcaster_id: &mut Option<String>) -> BotResult<Vec<Chatter>> {
+    if broadcaster_id.is_none() {
+        let token = get_twitch_access_token(state).await?;
+        *broadcaster_id = Some(resolve_twitch_user_id(channel.channel(), &state.secrets, &token).await?.0);
+    }
+    let broadcaster = broadcaster_id.as_deref().unwrap_or_default();
+    fetch_lurkers(broadcaster, &state.secrets.user_access_token, &state.secrets.bot_id).await
+}
+
+async fn tick(pool: &PgPool, state: &Arc<AppState>, channel: &ChannelId, broadcaster_id: &mut Option<String>) -> BotResult<()> {
+    let policy = {
+        let cfg = state.config.read().await;
+        match cfg.get_channel_config(channel) {
+            Some(c) => c.points.clone(),
+            None => return Ok(()),
+        }
+    };
+
+    // Co se napsalo mimo stream, se zahodí
+    let chatted = state.runtime.chat_activity.get_mut(channel).map(|mut a| std::mem::take(&mut *a)).unwrap_or_default();
+    if !channel_is_live(state, channel).await? {
+        return Ok(());
+    }
+
+    let mut awards: HashMap<String, PointsAward> = HashMap::new();
+    if channel.platform() == Platform::Twitch && policy.presence > 0 {
+        // Bez seznamu diváků se aspoň rozdají body za chat
+        let lurkers = match fetch_present(state, channel, broadcaster_id).await {
+            Ok(lurkers) => lurkers,
+            Err(e) => {
+                warn!("Fetching chatters of {} failed: {:?}", channel.as_str(), e);
+                Vec::new()
+            }
+        };
+        for chatter in lurkers {
+            let user_id = UserId::new(Platform::Twitch, &chatter.user_id).as_str().to_string();
+            awards.insert(user_id.clone(), PointsAward { user_id, display_name: chatter.user_name, points: policy.presence });
+        }
+    }
+
+    for (user_id, display_name) in chatted {
+        awards
+            .entry(user_id.clone())
+            .or_insert(PointsAward { user_id, display_name, points: 0 })
+            .points += policy.chat;
+    }
+
+    let awards: Vec<PointsAward> = awards.into_values().filter(|a| a.points > 0).collect();
+    award_points(pool, channel, &awards, policy.interval_minutes as i64).await
+}
+
+/// Per-channel task, běží jen s balíčkem `points`
+pub fn spawn_points_loop(channel: ChannelId, state: Arc<AppState>, pool: PgPool) -> JoinHandle<()> {
+    state.runtime.chat_activity.insert(channel.clone(), HashMap::new());
+    tokio::spawn(async move {
+        let mut broadcaster_id = None;
+        loop {
+            let minutes = {
+                let cfg = state.config.read().await;
+                cfg.get_channel_config(&channel).map(|c| c.points.interval_minutes).unwrap_or_else(default_interval)
+            };
+            tokio::time::sleep(Duration::from_secs(minutes.max(1) as u64 * 60)).await;
+            if let Err(e) = tick(&pool, &state, &channel, &mut broadcaster_id).await {
+                warn!("Points for {} failed: {:?}", channel.as_str(), e);
+            }
+        }
+    })
+}
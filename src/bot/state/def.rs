@@ -4,7 +4,7 @@ use thiserror::Error;
 use tokio::{sync::{RwLock, broadcast::error::SendError}};
 use twitch_irc::{login::StaticLoginCredentials, transport::{tcp::{TCPTransport, TLS}, websocket::WSTransport}, validate};
 
-use crate::bot::{commands::{CommandRegistry, queue::logic::QueueKey}, db::ChannelId, dispatcher::dispatcher::DispatcherCache, handler::handler::UnifiedChatClient, web::sse::{SseBus, SseEvent}};
+use crate::{api::bungie::{BungieClient, BungieError}, bot::{commands::{CommandRegistry, queue::{export::ExportLinks, logic::QueueKey, party::PartyInvites, presence::{Presence, PresenceCache, PresenceCheck}, raffle::RafflePolicy, requirements::{JoinRequirements, RaidClearsCache}, rewards::RewardMapping, subs::SubRule, tags::GroupBalance, ready::{ReadyCheckPolicy, ReadyChecks}}}, db::ChannelId, dispatcher::dispatcher::DispatcherCache, runtime::{auto_queue::AutoQueue, points::{ChatActivity, PointsPolicy}}, handler::handler::UnifiedChatClient, web::sse::{SseBus, SseEvent}}};
 
 pub struct AppState {
     pub secrets: Arc<BotSecrets>,
@@ -14,6 +14,7 @@ pub struct AppState {
     pub registry: Arc<CommandRegistry>,
     pub sse_bus: SseBus,
     pub twitch_auth: Arc<RwLock<TwitchAppToken>>,
+    pub bungie: Arc<BungieClient>,
 }
 
 pub struct TwitchAppToken {
@@ -35,6 +36,12 @@ pub struct BotSecrets {
 
 pub struct BotRuntime {
     pub dispatchers: RwLock<DispatcherCache>,
+    pub ready_checks: ReadyChecks,
+    pub export_links: ExportLinks,
+    pub party_invites: PartyInvites,
+    pub chat_activity: ChatActivity,
+    pub raid_clears: RaidClearsCache,
+    pub presence: PresenceCache,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -42,21 +49,59 @@ pub struct BotConfig {
     pub channels: HashMap<ChannelId, ChannelConfig>, // Holds configuration for all channels
 }
 
+pub const MAIN_QUEUE: &str = "main";
+
+/// Nastavení jedné fronty (hlavní nebo pojmenované)
 #[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct ChannelConfig {
-    //Stav Queue
+pub struct QueueSettings {
     #[serde(default)]
     pub open: bool,
     #[serde(default)]
     pub size: usize,
     #[serde(default)]
     pub teamsize: usize,
-    pub queue_target: QueueKey,
     #[serde(default)]
     pub random_queue: bool,
+    #[serde(default)]
+    pub raffle: RafflePolicy,
+    #[serde(default)]
+    pub ready_check: ReadyCheckPolicy,
+    #[serde(default)]
+    pub balance: GroupBalance,
+    #[serde(default)]
+    pub presence: PresenceCheck,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ChannelConfig {
+    //Stav hlavní Queue, pole zůstávají v JSONu na nejvyšší úrovni
+    #[serde(flatten)]
+    pub main: QueueSettings,
+    //Další pojmenované fronty (`!join raid`, `!next dungeon`)
+    #[serde(default)]
+    pub queues: HashMap<String, QueueSettings>,
+    pub queue_target: QueueKey,
     //Které commandy jsou povolené
     #[serde(default)]
     pub packages: Vec<String>,
+    //Cooldown po odehraném runu
+    #[serde(default)]
+    pub cooldown: JoinCooldown,
+    //Automatické otevírání / zavírání fronty
+    #[serde(default)]
+    pub auto_queue: AutoQueue,
+    //Body za sledování (balíček points)
+    #[serde(default)]
+    pub points: PointsPolicy,
+    //Odměny za kanálové body -> akce ve frontě
+    #[serde(default)]
+    pub rewards: Vec<RewardMapping>,
+    //Prio runy za suby a darované suby
+    #[serde(default)]
+    pub sub_rules: Vec<SubRule>,
+    //Požadavky na raid cleary pro join
+    #[serde(default)]
+    pub requirements: JoinRequirements,
     //Statistiky
     #[serde(default)]
     pub runs: usize,
@@ -65,6 +110,16 @@ pub struct ChannelConfig {
     pub prefix: String,
 }
 
+/// Jak dlouho po runu se nejde znovu joinout. 0 = vypnuto.
+#[derive(Deserialize, Serialize, Clone, Debug, Default)]
+pub struct JoinCooldown {
+    /// Kolik runů musí divák vynechat
+    #[serde(default)]
+    pub runs: u32,
+    #[serde(default)]
+    pub minutes: u32,
+}
+
 fn default_prefix() -> String {
     "!".into()
 }
@@ -93,6 +148,8 @@ pub enum BotError {
     Chat(String),
     #[error("Env missing?  {0}")]
     VarError(#[from] VarError),
+    #[error("Bungie API: {0}")]
+    Bungie(#[from] BungieError),
     #[error("{0}")]
     Custom(String),
 
@@ -110,6 +167,24 @@ pub struct ObsQueueEntry {
     pub position: i32,
     pub display_name: String,
     pub bungie_name: String,
-    pub user_id: String
+    pub user_id: String,
+    /// Šance na výběr v příštím losování (jen v raffle módu)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub odds: Option<f64>,
+    /// Odhad čekání v minutách (jen v normální frontě)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_minutes: Option<i64>,
+    /// Stav ready-checku (jen u živé skupiny během checku)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ready: Option<bool>,
+    /// Stav ve hře z poslední kontroly (jen u živé skupiny)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence: Option<Presence>,
+    /// user_id zakladatele party, pokud hraje s partou
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub party: Option<String>,
+    /// Role / class tagy z `!join`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
@@ -1,18 +1,88 @@
 use std::{collections::HashMap, time::Instant};
-use crate::{api::twitch_api::create_twitch_app_token, bot::{chat_event::chat_event::Platform, commands::{commands::BotResult, queue::logic::QueueKey}, db::ChannelId, state::def::{AliasConfig, AppState, BotConfig, BotError, BotSecrets, ChannelConfig}, web::obs::ObsCommandInfo}};
+use sqlx::PgPool;
+
+use crate::{api::twitch_api::{create_twitch_app_token, refresh_twitch_user_token}, bot::{chat_event::chat_event::Platform, commands::{commands::BotResult, queue::{logic::QueueKey, presence::PresenceCheck, raffle::RafflePolicy, requirements::JoinRequirements, ready::ReadyCheckPolicy, tags::GroupBalance}}, db::{ChannelId, cooldown::LastRun, twitch_tokens::{TwitchUserToken, load_twitch_token, update_twitch_token}}, runtime::{auto_queue::AutoQueue, points::PointsPolicy}, state::def::{AliasConfig, AppState, BotConfig, BotError, BotSecrets, ChannelConfig, JoinCooldown, MAIN_QUEUE, QueueSettings}, web::obs::ObsCommandInfo}};
 
 
 impl ChannelConfig {
     pub fn new(channel_id: ChannelId) -> Self {
         ChannelConfig { 
-            open: false,
+            main: QueueSettings::default(),
+            queues: HashMap::new(),
             queue_target: QueueKey::Single(channel_id),
-            size: 1, 
-            teamsize: 1, 
             packages: vec!["moderation".to_string()], 
+            cooldown: JoinCooldown::default(),
+            auto_queue: AutoQueue::default(),
+            points: PointsPolicy::default(),
+            rewards: Vec::new(),
+            sub_rules: Vec::new(),
+            requirements: JoinRequirements::default(),
             runs: 0,   
             prefix: "!".to_string(), 
+        }
+    }
+
+    pub fn queue(&self, name: &str) -> Option<&QueueSettings> {
+        if name == MAIN_QUEUE {
+            Some(&self.main)
+        } else {
+            self.queues.get(name)
+        }
+    }
+
+    pub fn queue_mut(&mut self, name: &str) -> Option<&mut QueueSettings> {
+        if name == MAIN_QUEUE {
+            Some(&mut self.main)
+        } else {
+            self.queues.get_mut(name)
+        }
+    }
+
+    /// Hlavní fronta první, pak pojmenované podle abecedy
+    pub fn queue_names(&self) -> Vec<String> {
+        let mut named: Vec<String> = self.queues.keys().cloned().collect();
+        named.sort();
+        std::iter::once(MAIN_QUEUE.to_string()).chain(named).collect()
+    }
+}
+
+impl JoinCooldown {
+    pub fn is_enabled(&self) -> bool {
+        self.runs > 0 || self.minutes > 0
+    }
+
+    /// (runy, minuty), které divákovi ještě zbývají; None = může joinout
+    pub fn remaining(&self, last: &LastRun) -> Option<(i64, i64)> {
+        let runs = (self.runs as i64 - last.runs_since).max(0);
+        let seconds = (self.minutes as i64 * 60 - last.seconds_since).max(0);
+        if runs == 0 && seconds == 0 {
+            None
+        } else {
+            Some((runs, (seconds + 59) / 60))
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match (self.runs, self.minutes) {
+            (0, 0) => "off".to_string(),
+            (r, 0) => format!("sit out {r} run(s)"),
+            (0, m) => format!("wait {m} min"),
+            (r, m) => format!("sit out {r} run(s) and wait {m} min"),
+        }
+    }
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        QueueSettings {
+            open: false,
+            size: 1,
+            teamsize: 1,
             random_queue: false,
+            raffle: RafflePolicy::default(),
+            ready_check: ReadyCheckPolicy::default(),
+            balance: GroupBalance::default(),
+            presence: PresenceCheck::default(),
         }
     }
 }
@@ -36,7 +106,7 @@ impl BotSecrets {
 impl BotConfig {
     pub fn new() -> Self {
         let mut hash = HashMap::new();
-        hash.insert(ChannelId::new(Platform::Twitch, "krapmatt".to_string()), ChannelConfig {open: true, size: 1, teamsize: 2, packages: vec!["queue".to_string()], runs: 0, queue_target: QueueKey::Single(ChannelId::new(Platform::Twitch, "krapmatt".to_string())), prefix: "!".to_string(), random_queue: false });
+        hash.insert(ChannelId::new(Platform::Twitch, "krapmatt".to_string()), ChannelConfig {main: QueueSettings { open: true, size: 1, teamsize: 2, random_queue: false, raffle: RafflePolicy::default(), ready_check: ReadyCheckPolicy::default(), balance: GroupBalance::default(), presence: PresenceCheck::default() }, queues: HashMap::new(), packages: vec!["queue".to_string()], cooldown: JoinCooldown::default(), auto_queue: AutoQueue::default(), points: PointsPolicy::default(), rewards: Vec::new(), sub_rules: Vec::new(), requirements: JoinRequirements::default(), runs: 0, queue_target: QueueKey::Single(ChannelId::new(Platform::Twitch, "krapmatt".to_string())), prefix: "!".to_string() });
         BotConfig {
             channels: hash,
         }
@@ -101,6 +171,24 @@ pub async fn get_twitch_access_token(state: &AppState) -> BotResult<String> {
     Ok(auth.access_token.clone())
 }
 
+/// Token streamera z přihlášení do docku, obnoví se pár minut před vypršením.
+/// None, pokud se streamer ještě nepřihlásil.
+pub async fn get_broadcaster_token(pool: &PgPool, state: &AppState, channel: &ChannelId) -> BotResult<Option<TwitchUserToken>> {
+    let Some(mut token) = load_twitch_token(pool, channel).await? else {
+        return Ok(None);
+    };
+    if token.expires_at > chrono::Utc::now().timestamp() + 300 {
+        return Ok(Some(token));
+    }
+
+    let refreshed = refresh_twitch_user_token(&state.secrets, &token.refresh_token).await?;
+    update_twitch_token(pool, channel, &refreshed.access_token, &refreshed.refresh_token, refreshed.expires_in).await?;
+    token.access_token = refreshed.access_token;
+    token.refresh_token = refreshed.refresh_token;
+    token.expires_at = chrono::Utc::now().timestamp() + refreshed.expires_in;
+    Ok(Some(token))
+}
+
 impl AppState {
     pub fn all_commands_for_obs(&self) -> Vec<ObsCommandInfo> {
         self.registry
@@ -114,3 +202,29 @@ impl AppState {
             }).collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last(runs_since: i64, seconds_since: i64) -> LastRun {
+        LastRun { runs_since, seconds_since }
+    }
+
+    #[test]
+    fn cooldown_counts_runs_and_rounds_minutes_up() {
+        let cooldown = JoinCooldown { runs: 2, minutes: 10 };
+        assert_eq!(cooldown.remaining(&last(0, 0)), Some((2, 10)));
+        assert_eq!(cooldown.remaining(&last(1, 61)), Some((1, 9)));
+        assert_eq!(cooldown.remaining(&last(5, 599)), Some((0, 1)));
+        assert_eq!(cooldown.remaining(&last(2, 600)), None);
+    }
+
+    #[test]
+    fn cooldown_with_only_runs_ignores_time() {
+        let cooldown = JoinCooldown { runs: 1, minutes: 0 };
+        assert_eq!(cooldown.remaining(&last(0, 86_400)), Some((1, 0)));
+        assert_eq!(cooldown.remaining(&last(1, 0)), None);
+        assert_eq!(JoinCooldown::default().remaining(&last(0, 0)), None);
+    }
+}
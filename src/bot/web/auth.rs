@@ -8,11 +8,14 @@ use warp::{http::{header::SET_COOKIE, HeaderValue, StatusCode}, reply::Reply};
 
 use crate::bot::{
     chat_event::chat_event::Platform,
-    db::ChannelId,
+    db::{ChannelId, twitch_tokens::save_twitch_token},
     state::def::{AppState, BotSecrets},
     web::sessions::{platform_session_cookie, session_cookie_header},
 };
 
+/// Redemptions scopy pro odměny za kanálové body, token se ukládá při přihlášení streamera
+const TWITCH_SCOPES: &str = "user:read:email channel:read:redemptions channel:manage:redemptions";
+
 pub async fn twitch_login(secrets: Arc<BotSecrets>) -> Result<impl warp::Reply, warp::Rejection> {
     let client_id = &secrets.bot_id;
     let redirect_uri = "https://krapbott.up.railway.app/auth/callback";
@@ -20,9 +23,10 @@ pub async fn twitch_login(secrets: Arc<BotSecrets>) -> Result<impl warp::Reply,
     let url = format!(
         "https://id.twitch.tv/oauth2/authorize\
         ?client_id={}&redirect_uri={}\
-        &response_type=code&scope=user:read:email",
+        &response_type=code&scope={}",
         client_id,
-        urlencoding::encode(redirect_uri)
+        urlencoding::encode(redirect_uri),
+        urlencoding::encode(TWITCH_SCOPES)
     );
     let uri: Uri = url.parse().unwrap();
     Ok(warp::redirect::temporary(uri))
@@ -31,6 +35,12 @@ pub async fn twitch_login(secrets: Arc<BotSecrets>) -> Result<impl warp::Reply,
 #[derive(Deserialize)]
 pub struct TwitchTokenResponse {
     access_token: String,
+    #[serde(default)]
+    refresh_token: String,
+    #[serde(default)]
+    expires_in: i64,
+    #[serde(default)]
+    scope: Vec<String>,
 }
 
 #[derive(Deserialize)]
@@ -106,6 +116,12 @@ pub async fn twitch_callback(query: HashMap<String, String>, pool: Arc<sqlx::PgP
             "text/html; charset=utf-8",
         ).into_response());
     }
+    if !token.refresh_token.is_empty() {
+        if let Err(e) = save_twitch_token(&pool, &channel_id, &user.id, &token.access_token, &token.refresh_token, token.expires_in, &token.scope.join(" ")).await {
+            error!("Failed to store Twitch token for {}: {:?}", user.login, e);
+        }
+    }
+
     // Create session
     let session_id = uuid::Uuid::new_v4().to_string();
     sqlx::query!(
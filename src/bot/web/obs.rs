@@ -8,16 +8,16 @@ use warp::{filters::sse::Event, http::{header::SET_COOKIE, HeaderValue, StatusCo
 
 use crate::bot::{
     chat_event::chat_event::Platform,
-    commands::queue::logic::{remove_from_queue, reorder_queue, reset_queue_runs, resolve_queue_owner, run_next, set_queue_len, set_queue_open, set_queue_size},
-    db::{UserId, aliases::fetch_aliases_from_db, queue::fetch_queue_for_owner},
+    commands::queue::{export::get_export_link, raffle::attach_raffle_odds, presence::attach_presence, ready::{attach_ready_state, mark_ready}, logic::{attach_queue_eta, queue_settings, remove_from_queue, reorder_queue, reset_queue_runs, resolve_queue_owner, run_next, undo_last_change, set_queue_len, set_queue_open, set_queue_size}},
+    db::{UserId, aliases::fetch_aliases_from_db, history::{HISTORY_WINDOW_HOURS, fetch_recent_runs, fetch_viewer_runs}, stats::{STATS_DEFAULT_DAYS, fetch_channel_stats}, queue::{fetch_queue_for_owner, list_queue_bans, unban_from_queue}, transfer::{ImportMode, QueueExport, QueueExportRow, export_queue, import_queue, parse_queue_csv}},
     dispatcher::dispatcher::refresh_channel_dispatcher,
     handler::handler::ChatClient,
     replies::Replies,
-    state::def::{AppState, ObsQueueEntry},
-    web::sessions::{
+    state::def::{AppState, MAIN_QUEUE, ObsQueueEntry},
+    web::{sse::SseEvent, sessions::{
         channel_from_session, clear_session_cookie_header, get_cookie, platform_session_cookie,
         session_cookie_header, sessions_from_cookies,
-    },
+    }},
 };
 
 pub async fn obs_combined_page(cookies: Option<String>, pool: Arc<sqlx::PgPool>) -> Result<impl Reply, warp::Rejection> {
@@ -201,45 +201,71 @@ pub async fn obs_logout(cookies: Option<String>, pool: Arc<PgPool>) -> Result<Re
     Ok(response)
 }
 
+/// `?queue=raid` — bez parametru hlavní fronta
+#[derive(Deserialize)]
+pub struct QueueQuery {
+    pub queue: Option<String>,
+}
+
+fn queue_or_main(queue: Option<String>) -> String {
+    queue.filter(|q| !q.trim().is_empty()).unwrap_or_else(|| MAIN_QUEUE.to_string())
+}
+
 #[derive(Serialize)]
 pub struct ObsQueueResponse {
+    pub name: String,
+    pub queues: Vec<String>,
     pub open: bool,
     pub teamsize: usize,
     pub length: usize,
     pub runs: usize,
     pub queue: Vec<ObsQueueEntry>,
+    /// Sekundy do konce ready-checku, pokud zrovna běží
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ready_seconds_left: Option<u64>,
 }
 
-pub async fn obs_queue(cookies: Option<String>, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
+pub async fn obs_queue(cookies: Option<String>, query: QueueQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
     let channel = match channel_from_session(cookies, &pool).await {
         Ok(c) => c,
         Err(_) => return Err(warp::reject()),
     };
 
     let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+    let name = queue_or_main(query.queue);
 
-    let (teamsize, open, len, runs) = {
+    let (queues, runs) = {
         let cfg = state.config.read().await;
         (
-            cfg.get_channel_config(&owner).map(|c| c.teamsize).unwrap_or(1), 
-            cfg.get_channel_config(&owner).map(|c| c.open).unwrap_or(false), 
-            cfg.get_channel_config(&owner).map(|c| c.size).unwrap_or(1),
-            cfg.get_channel_config(&owner).map(|c| c.runs).unwrap_or(0)
+            cfg.get_channel_config(&owner).map(|c| c.queue_names()).unwrap_or_default(),
+            cfg.get_channel_config(&owner).map(|c| c.runs).unwrap_or(0),
         )
     };
+    let settings = queue_settings(&state, &owner, &name).await.map_err(|_| warp::reject())?;
+    let teamsize = settings.teamsize;
 
-    let queue = fetch_queue_for_owner(&pool, &owner, teamsize).await.map_err(|_| warp::reject())?;
+    let mut queue = fetch_queue_for_owner(&pool, &owner, &name).await.map_err(|_| warp::reject())?;
+    if settings.random_queue {
+        attach_raffle_odds(&pool, &owner, &name, &mut queue, &settings.raffle, teamsize).await
+    } else {
+        attach_queue_eta(&pool, &owner, &name, &mut queue, teamsize).await
+    }.map_err(|_| warp::reject())?;
+    let ready_seconds_left = attach_ready_state(&state, &owner, &name, &mut queue).await;
+    attach_presence(&state, &owner, &name, &mut queue);
     info!("{:?}", queue);
     Ok(warp::reply::json(&ObsQueueResponse {
-        open,
+        name,
+        queues,
+        open: settings.open,
         teamsize,
-        length: len,
+        length: settings.size,
         runs,
         queue,
+        ready_seconds_left,
     }).into_response())
 }
 
-pub async fn obs_queue_next(cookies: Option<String>, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
+pub async fn obs_queue_next(cookies: Option<String>, query: QueueQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
     let channel = match channel_from_session(cookies, &pool).await {
         Ok(c) => c,
         Err(_) => return Err(warp::reject()),
@@ -247,7 +273,7 @@ pub async fn obs_queue_next(cookies: Option<String>, pool: Arc<PgPool>, state: A
 
     let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
 
-    let reply = run_next(&pool, state.clone(), &owner).await.map_err(|_| warp::reject())?;
+    let reply = run_next(&pool, state.clone(), &owner, &queue_or_main(query.queue)).await.map_err(|_| warp::reject())?;
 
     // Send chat message
     state.chat_client.send_message(&channel, &reply).await.map_err(|_| warp::reject())?;
@@ -258,9 +284,51 @@ pub async fn obs_queue_next(cookies: Option<String>, pool: Arc<PgPool>, state: A
     })).into_response())
 }
 
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    pub limit: Option<i64>,
+    pub user: Option<String>,
+}
+
+pub async fn obs_history(cookies: Option<String>, query: HistoryQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
+    let channel = match channel_from_session(cookies, &pool).await {
+        Ok(c) => c,
+        Err(_) => return Err(warp::reject()),
+    };
+
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+
+    let runs = match query.user.as_deref().map(str::trim).filter(|u| !u.is_empty()) {
+        Some(user) => fetch_viewer_runs(&pool, &owner, user.trim_start_matches('@'), HISTORY_WINDOW_HOURS).await,
+        None => fetch_recent_runs(&pool, &owner, query.limit.unwrap_or(20).clamp(1, 100)).await,
+    }.map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&serde_json::json!({ "runs": runs })).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    pub days: Option<i32>,
+}
+
+pub async fn obs_stats(cookies: Option<String>, query: StatsQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+    let timezone = {
+        let cfg = state.config.read().await;
+        cfg.get_channel_config(&owner).map(|c| c.auto_queue.timezone.clone()).unwrap_or_else(|| "UTC".to_string())
+    };
+
+    let days = query.days.unwrap_or(STATS_DEFAULT_DAYS).clamp(1, 365);
+    let stats = fetch_channel_stats(&pool, &owner, days, &timezone, 10).await.map_err(|_| warp::reject())?;
+    Ok(warp::reply::json(&stats))
+}
+
 #[derive(Deserialize)]
 pub struct RemovePayload {
     pub user_id: String,
+    #[serde(default)]
+    pub queue: Option<String>,
 }
 
 pub async fn obs_queue_remove(cookies: Option<String>, body: RemovePayload, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
@@ -272,16 +340,39 @@ pub async fn obs_queue_remove(cookies: Option<String>, body: RemovePayload, pool
 
     let user_id = UserId::from_str(&body.user_id).map_err(|_| warp::reject())?;
 
-    remove_from_queue(&pool, &owner, &user_id, state)
+    remove_from_queue(&pool, &owner, &queue_or_main(body.queue), &user_id, state)
         .await
         .map_err(|_| warp::reject())?;
 
     Ok(warp::reply::json(&serde_json::json!({ "ok": true })))
 }
 
+#[derive(Deserialize)]
+pub struct ReadyPayload {
+    pub user_id: String,
+}
+
+/// Streamer potvrdí hráče v ready-checku kliknutím v docku
+pub async fn obs_queue_ready(cookies: Option<String>, body: ReadyPayload, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = match channel_from_session(cookies, &pool).await {
+        Ok(c) => c,
+        Err(_) => return Err(warp::reject()),
+    };
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+
+    let Some(reply) = mark_ready(&state, &owner, &body.user_id).await else {
+        return Ok(warp::reply::json(&serde_json::json!({ "ok": false })));
+    };
+    state.chat_client.send_message(&owner, &reply).await.map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&serde_json::json!({ "ok": true })))
+}
+
 #[derive(Deserialize)]
 pub struct ReorderPayload {
     pub order: Vec<String>,
+    #[serde(default)]
+    pub queue: Option<String>,
 }
 
 pub async fn obs_queue_reorder(cookies: Option<String>, body: ReorderPayload, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
@@ -296,6 +387,7 @@ pub async fn obs_queue_reorder(cookies: Option<String>, body: ReorderPayload, po
     };
 
     let owner = resolve_queue_owner(&state, &channel).await.unwrap_or(channel);
+    let queue = queue_or_main(body.queue);
 
     // 2. Kontrola a parsování ID uživatelů
     let mut users = Vec::new();
@@ -311,7 +403,7 @@ pub async fn obs_queue_reorder(cookies: Option<String>, body: ReorderPayload, po
     }
 
     // 3. Uložení do databáze
-    if let Err(e) = reorder_queue(&pool, &owner, users).await {
+    if let Err(e) = reorder_queue(&pool, &owner, &queue, users).await {
         tracing::error!("Reorder chyba: Selhalo uložení do databáze! {:?}", e);
         let json = warp::reply::json(&serde_json::json!({"error": "Database error"}));
         return Ok(warp::reply::with_status(json, warp::http::StatusCode::INTERNAL_SERVER_ERROR).into_response());
@@ -324,6 +416,8 @@ pub async fn obs_queue_reorder(cookies: Option<String>, body: ReorderPayload, po
 #[derive(serde::Deserialize)]
 pub struct ToggleQueuePayload {
     pub open: bool,
+    #[serde(default)]
+    pub queue: Option<String>,
 }
 
 #[derive(Debug)]
@@ -338,7 +432,7 @@ pub async fn obs_queue_toggle(cookies: Option<String>, body: ToggleQueuePayload,
 
     let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject::custom(ObsToggleError))?;
 
-    set_queue_open(&pool, state.clone(), &owner, body.open).await.map_err(|_| warp::reject::custom(ObsToggleError))?;
+    set_queue_open(&pool, state.clone(), &owner, &queue_or_main(body.queue), body.open).await.map_err(|_| warp::reject::custom(ObsToggleError))?;
 
     let msg = if body.open {
         &Replies::queue_opened()
@@ -357,6 +451,8 @@ pub async fn obs_queue_toggle(cookies: Option<String>, body: ToggleQueuePayload,
 #[derive(serde::Deserialize)]
 pub struct SizeQueuePayload {
     pub teamsize: usize,
+    #[serde(default)]
+    pub queue: Option<String>,
 }
 
 #[derive(Debug)]
@@ -371,7 +467,7 @@ pub async fn obs_queue_size(cookies: Option<String>, body: SizeQueuePayload, poo
 
     let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject::custom(ObsQueueSizeError))?;
 
-    set_queue_size(&pool, state.clone(), &owner, body.teamsize).await.map_err(|_| warp::reject::custom(ObsQueueSizeError))?;
+    set_queue_size(&pool, state.clone(), &owner, &queue_or_main(body.queue), body.teamsize).await.map_err(|_| warp::reject::custom(ObsQueueSizeError))?;
 
     state.chat_client.send_message(&owner, &Replies::queue_size(&body.teamsize.to_string())).await.map_err(|_| warp::reject::custom(ObsQueueSizeError))?;
 
@@ -384,6 +480,8 @@ pub async fn obs_queue_size(cookies: Option<String>, body: SizeQueuePayload, poo
 #[derive(serde::Deserialize)]
 pub struct LenQueuePayload {
     pub length: usize,
+    #[serde(default)]
+    pub queue: Option<String>,
 }
 
 #[derive(Debug)]
@@ -398,7 +496,7 @@ pub async fn obs_queue_len(cookies: Option<String>, body: LenQueuePayload, pool:
 
     let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject::custom(ObsQueueLenError))?;
 
-    set_queue_len(&pool, state.clone(), &owner, body.length).await.map_err(|_| warp::reject::custom(ObsQueueLenError))?;
+    set_queue_len(&pool, state.clone(), &owner, &queue_or_main(body.queue), body.length).await.map_err(|_| warp::reject::custom(ObsQueueLenError))?;
 
     state.chat_client.send_message(&owner, &Replies::queue_length(&body.length.to_string())).await.map_err(|_| warp::reject::custom(ObsQueueLenError))?;
 
@@ -640,30 +738,171 @@ pub async fn obs_queue_reset(
     })))
 }
 
+/// Tlačítko Undo v docku — vrátí poslední změnu vybrané fronty
+pub async fn obs_queue_undo(cookies: Option<String>, query: QueueQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+    let queue = queue_or_main(query.queue);
+
+    let reply = undo_last_change(&pool, &state, &owner, Some(&queue)).await.map_err(|_| warp::reject())?;
+    state.chat_client.send_message(&owner, &reply).await.map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&serde_json::json!({ "ok": true, "message": reply })))
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub queue: Option<String>,
+    /// `json` (výchozí) nebo `csv`
+    pub format: Option<String>,
+}
+
+fn export_reply(export: &QueueExport, format: Option<&str>) -> Response {
+    let file = format!("{}-{}", export.channel.replace(':', "-"), export.queue);
+    if format.is_some_and(|f| f.eq_ignore_ascii_case("csv")) {
+        let reply = warp::reply::with_header(export.to_csv(), "content-type", "text/csv; charset=utf-8");
+        warp::reply::with_header(reply, "content-disposition", format!("attachment; filename=\"{file}.csv\"")).into_response()
+    } else {
+        let reply = warp::reply::json(export);
+        warp::reply::with_header(reply, "content-disposition", format!("attachment; filename=\"{file}.json\"")).into_response()
+    }
+}
+
+pub async fn obs_queue_export(cookies: Option<String>, query: ExportQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+
+    let export = export_queue(&pool, &owner, &queue_or_main(query.queue)).await.map_err(|_| warp::reject())?;
+    Ok(export_reply(&export, query.format.as_deref()))
+}
+
+/// Krátký odkaz z `!queue_export`, funguje bez přihlášení dokud nevyprší
+pub async fn queue_export_link(token: String, query: ExportQuery, state: Arc<AppState>) -> Result<Response, warp::Rejection> {
+    let export = get_export_link(&state, &token).ok_or_else(warp::reject::not_found)?;
+    Ok(export_reply(&export, query.format.as_deref()))
+}
+
+#[derive(Deserialize)]
+pub struct ImportPayload {
+    #[serde(default)]
+    pub queue: Option<String>,
+    #[serde(default)]
+    pub mode: ImportMode,
+    /// Obsah souboru z exportu — JSON nebo CSV
+    pub data: String,
+}
+
+pub async fn obs_queue_import(cookies: Option<String>, body: ImportPayload, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+    let queue = queue_or_main(body.queue);
+    queue_settings(&state, &owner, &queue).await.map_err(|_| warp::reject())?;
+
+    let data = body.data.trim_start_matches('\u{feff}').trim();
+    let rows = if data.starts_with('{') {
+        serde_json::from_str::<QueueExport>(data).map(|e| e.entries).map_err(|e| e.to_string())
+    } else if data.starts_with('[') {
+        serde_json::from_str::<Vec<QueueExportRow>>(data).map_err(|e| e.to_string())
+    } else {
+        parse_queue_csv(data).map_err(|e| e.to_string())
+    };
+
+    let result = match rows {
+        Ok(rows) => import_queue(&pool, &owner, &queue, rows, body.mode).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(report) => {
+            let _ = state.sse_bus.send(SseEvent::QueueUpdated { channel: owner });
+            Ok(warp::reply::json(&serde_json::json!({ "ok": true, "imported": report.imported, "banned": report.banned })))
+        }
+        Err(e) => Ok(warp::reply::json(&serde_json::json!({ "ok": false, "error": e }))),
+    }
+}
+
+/// Aktivní bany / timeouty pro záložku Bans v docku
+pub async fn obs_bans(cookies: Option<String>, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+
+    let bans = list_queue_bans(&pool, &owner).await.map_err(|_| warp::reject())?;
+    Ok(warp::reply::json(&bans))
+}
+
+#[derive(Deserialize)]
+pub struct BanLiftPayload {
+    pub user_id: String,
+}
+
+pub async fn obs_ban_lift(cookies: Option<String>, body: BanLiftPayload, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let channel = channel_from_session(cookies, &pool).await.map_err(|_| warp::reject())?;
+    let owner = resolve_queue_owner(&state, &channel).await.map_err(|_| warp::reject())?;
+
+    let display_name = list_queue_bans(&pool, &owner).await.map_err(|_| warp::reject())?
+        .into_iter().find(|b| b.user_id == body.user_id).map(|b| b.display_name);
+    let lifted = unban_from_queue(&pool, &owner, &body.user_id).await.map_err(|_| warp::reject())?;
+
+    if let (true, Some(name)) = (lifted, display_name) {
+        state.chat_client.send_message(&owner, &Replies::mod_unbanned(&name)).await.map_err(|_| warp::reject())?;
+    }
+    let _ = state.sse_bus.send(SseEvent::BansUpdated { channel: owner });
+
+    Ok(warp::reply::json(&serde_json::json!({ "ok": lifted })))
+}
+
 pub async fn public_queue_page(streamer: String) -> Result<Response, warp::Rejection> {
     Ok(warp::reply::html(include_str!("public/queue.html")).into_response())
 }
 
-pub async fn public_queue_data(streamer: String, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+#[derive(Serialize)]
+pub struct PublicQueueResponse {
+    pub queues: Vec<String>,
+    pub queue: String,
+    pub groups: Vec<Vec<ObsQueueEntry>>,
+}
+
+/// Fronta pro overlaye a weby, pole skupin. `?queue=raid` vybere pojmenovanou frontu.
+pub async fn public_queue_data(streamer: String, query: QueueQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    let data = public_queue(&streamer, query, &pool, &state).await;
+    Ok(warp::reply::json(&data.groups))
+}
+
+/// Fronta i se seznamem front kanálu, pro veřejnou stránku
+pub async fn public_queues_data(streamer: String, query: QueueQuery, pool: Arc<PgPool>, state: Arc<AppState>) -> Result<impl Reply, warp::Rejection> {
+    Ok(warp::reply::json(&public_queue(&streamer, query, &pool, &state).await))
+}
+
+async fn public_queue(streamer: &str, query: QueueQuery, pool: &PgPool, state: &AppState) -> PublicQueueResponse {
+    let name = queue_or_main(query.queue);
     let channel_id = {
         let cfg = state.config.read().await;
-        cfg.channels.keys().find(|c| c.channel().eq_ignore_ascii_case(&streamer)).cloned()
+        cfg.channels.keys().find(|c| c.channel().eq_ignore_ascii_case(streamer)).cloned()
     };
 
     let Some(channel) = channel_id else {
-        // Pokud streamer neexistuje, vrátíme prázdné pole
-        return Ok(warp::reply::json(&Vec::<Vec<ObsQueueEntry>>::new()));
+        // Pokud streamer neexistuje, vrátíme prázdnou frontu
+        return PublicQueueResponse { queues: vec![], queue: name, groups: vec![] };
     };
 
     // 2. Zjistíme, čí to je fronta a jaká je velikost týmu
-    let owner = resolve_queue_owner(&state, &channel).await.unwrap_or(channel);
-    let teamsize = {
+    let owner = resolve_queue_owner(state, &channel).await.unwrap_or(channel);
+    let queues = {
         let cfg = state.config.read().await;
-        cfg.get_channel_config(&owner).map(|c| c.teamsize).unwrap_or(1)
+        cfg.get_channel_config(&owner).map(|c| c.queue_names()).unwrap_or_default()
     };
+    let Ok(settings) = queue_settings(state, &owner, &name).await else {
+        return PublicQueueResponse { queues, queue: name, groups: vec![] };
+    };
+    let teamsize = settings.teamsize.max(1);
 
     // 3. Vytáhneme frontu z databáze
-    let queue = fetch_queue_for_owner(&pool, &owner, teamsize).await.unwrap_or_default();
+    let mut queue = fetch_queue_for_owner(pool, &owner, &name).await.unwrap_or_default();
+    let _ = if settings.random_queue {
+        attach_raffle_odds(pool, &owner, &name, &mut queue, &settings.raffle, teamsize).await
+    } else {
+        attach_queue_eta(pool, &owner, &name, &mut queue, teamsize).await
+    };
 
     // 4. Rozdělíme data do "skupin" (chunks) podle teamsize, což čeká queue.html
     let mut grouped_data = Vec::new();
@@ -671,5 +910,5 @@ pub async fn public_queue_data(streamer: String, pool: Arc<PgPool>, state: Arc<A
         grouped_data.push(chunk.to_vec());
     }
 
-    Ok(warp::reply::json(&grouped_data))
+    PublicQueueResponse { queues, queue: name, groups: grouped_data }
 }
@@ -14,7 +14,14 @@ pub enum SseEvent {
         channel: ChannelId,
         open: bool,
     },
+    ReadyCheckUpdated {
+        channel: ChannelId,
+        queue: String,
+    },
     AliasesUpdated {
         channel: ChannelId
+    },
+    BansUpdated {
+        channel: ChannelId
     }
 }
@@ -4,10 +4,11 @@ use sqlx::PgPool;
 use tracing::info;
 use warp::Filter;
 use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use include_dir::{include_dir, Dir};
+use dashmap::DashMap;
 
-use crate::{api::{kick_oauth::KickAuthManager, twitch_api::create_twitch_app_token}, bot::{chat_event::chat_event::ChatEvent, commands::{CommandRegistry, commands::BotResult}, db::{ChannelId, config::{load_bot_config_from_db, save_channel_config}, initialize_database}, handler::handler::UnifiedChatClient, platforms::{kick::event_loop::run_kick_loop, twitch::{event_loop::run_twitch_loop, twitch::build_twitch_client}}, run_event_loop, state::def::{AliasConfig, AppState, BotRuntime, BotSecrets, ChannelConfig}, web::{auth::{kick_callback, kick_login, twitch_callback, twitch_login}, obs::{obs_alias_add, obs_alias_remove, obs_alias_remove_default, obs_alias_restore, obs_alias_restore_default, obs_alias_toggle_command, obs_aliases, obs_combined_page, obs_logout, obs_queue, obs_queue_events, obs_queue_len, obs_queue_next, obs_queue_remove, obs_queue_reorder, obs_queue_reset, obs_queue_size, obs_queue_toggle, obs_sessions, obs_switch_session}}}};
+use crate::{api::{bungie::BungieClient, kick_oauth::KickAuthManager, twitch_api::create_twitch_app_token}, bot::{chat_event::chat_event::ChatEvent, commands::{CommandRegistry, commands::BotResult}, db::{ChannelId, config::{load_bot_config_from_db, save_channel_config}, initialize_database}, handler::handler::UnifiedChatClient, platforms::{kick::event_loop::run_kick_loop, twitch::{event_loop::run_twitch_loop, eventsub::run_twitch_eventsub, twitch::build_twitch_client}}, run_event_loop, runtime::bungie_names::spawn_bungie_name_refresh, state::def::{AliasConfig, AppState, BotRuntime, BotSecrets, ChannelConfig}, web::{auth::{kick_callback, kick_login, twitch_callback, twitch_login}, obs::{obs_alias_add, obs_alias_remove, obs_alias_remove_default, obs_alias_restore, obs_alias_restore_default, obs_alias_toggle_command, obs_aliases, obs_ban_lift, obs_bans, obs_combined_page, obs_history, obs_logout, obs_stats, obs_queue, obs_queue_events, obs_queue_export, obs_queue_import, obs_queue_len, obs_queue_next, obs_queue_ready, obs_queue_remove, obs_queue_reorder, obs_queue_reset, obs_queue_size, obs_queue_toggle, obs_queue_undo, obs_sessions, QueueQuery, obs_switch_session}}}};
 use kick_rust::KickClient;
 
 #[tokio::main]
@@ -52,6 +53,12 @@ async fn main() -> BotResult<()> {
 
     let runtime = BotRuntime {
         dispatchers: RwLock::new(HashMap::new()),
+        ready_checks: Mutex::new(HashMap::new()),
+        export_links: DashMap::new(),
+        party_invites: Mutex::new(HashMap::new()),
+        chat_activity: DashMap::new(),
+        raid_clears: DashMap::new(),
+        presence: DashMap::new(),
     };
     
     let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<ChatEvent>();
@@ -77,12 +84,25 @@ async fn main() -> BotResult<()> {
         chat_client,
         registry: registry.clone(),
         sse_bus: sse_tx,
-        twitch_auth: Arc::new(RwLock::new(twitch_token))
+        twitch_auth: Arc::new(RwLock::new(twitch_token)),
+        bungie: Arc::new(BungieClient::new(secrets.x_api_key.clone())),
     });
 
     
     // Twitch input
     tokio::spawn(run_twitch_loop(twitch_rx, tx.clone(), state.clone()));
+    tokio::spawn(run_twitch_eventsub(pool.clone(), state.clone()));
+    // Destiny manifest do paměti dopředu, ať první !cr nečeká na stahování
+    {
+        let (pool, state) = (pool.clone(), state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = state.bungie.manifest(&pool).await {
+                tracing::warn!("Destiny manifest preload failed: {}", e);
+            }
+        });
+    }
+    // Přejmenování na Bungie, kontroluje uložená jména postupně
+    spawn_bungie_name_refresh(state.clone(), pool.clone());
     tokio::spawn(run_kick_loop(tx.clone(), state.clone()));
 
     // Core dispatcher
@@ -133,6 +153,7 @@ async fn main() -> BotResult<()> {
         .and(warp::path::end())
         .and(warp::get())
         .and(warp::header::optional("cookie"))
+        .and(warp::query::<QueueQuery>())
         .and(pool_filter.clone())
         .and(state_filter.clone())
         .and_then(obs_queue);
@@ -140,6 +161,7 @@ async fn main() -> BotResult<()> {
     let obs_next = warp::path!("api" / "obs" / "queue" / "next")
         .and(warp::post())
         .and(warp::header::optional("cookie"))
+        .and(warp::query::<QueueQuery>())
         .and(pool_filter.clone())
         .and(state_filter.clone())
         .and_then(obs_queue_next);
@@ -151,6 +173,14 @@ async fn main() -> BotResult<()> {
         .and(state_filter.clone())
         .and_then(obs_queue_remove);
 
+    let obs_ready = warp::path!("api" / "obs" / "queue" / "ready")
+        .and(warp::post())
+        .and(warp::header::optional("cookie"))
+        .and(warp::body::json())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_queue_ready);
+
     let obs_reorder = warp::path!("api" / "obs" / "queue" / "reorder")
         .and(warp::post())
         .and(warp::header::optional("cookie"))
@@ -267,6 +297,55 @@ async fn main() -> BotResult<()> {
         .and(pool_filter.clone())
         .and(state_filter.clone())
         .and_then(obs_queue_reset);
+    let obs_queue_undo = warp::path!("api" / "obs" / "queue" / "undo")
+        .and(warp::post())
+        .and(warp::header::optional("cookie"))
+        .and(warp::query::<QueueQuery>())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_queue_undo);
+    let obs_queue_export = warp::path!("api" / "obs" / "queue" / "export")
+        .and(warp::get())
+        .and(warp::header::optional("cookie"))
+        .and(warp::query::<crate::bot::web::obs::ExportQuery>())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_queue_export);
+    let obs_queue_import = warp::path!("api" / "obs" / "queue" / "import")
+        .and(warp::post())
+        .and(warp::header::optional("cookie"))
+        .and(warp::body::content_length_limit(1024 * 1024))
+        .and(warp::body::json())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_queue_import);
+    let obs_bans = warp::path!("api" / "obs" / "bans")
+        .and(warp::get())
+        .and(warp::header::optional("cookie"))
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_bans);
+    let obs_ban_lift = warp::path!("api" / "obs" / "bans" / "lift")
+        .and(warp::post())
+        .and(warp::header::optional("cookie"))
+        .and(warp::body::json())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_ban_lift);
+    let obs_history = warp::path!("api" / "obs" / "history")
+        .and(warp::get())
+        .and(warp::header::optional("cookie"))
+        .and(warp::query::<crate::bot::web::obs::HistoryQuery>())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_history);
+    let obs_stats = warp::path!("api" / "obs" / "stats")
+        .and(warp::get())
+        .and(warp::header::optional("cookie"))
+        .and(warp::query::<crate::bot::web::obs::StatsQuery>())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(obs_stats);
     let favicon = warp::path("favicon.ico")
         .and(warp::get())
         .map(|| warp::reply::with_status("", warp::http::StatusCode::NO_CONTENT));
@@ -276,9 +355,21 @@ async fn main() -> BotResult<()> {
         .and_then(crate::bot::web::obs::public_queue_page);
     let public_queue_api = warp::path!("api" / "public" / "queue" / String)
         .and(warp::get())
+        .and(warp::query::<QueueQuery>())
         .and(pool_filter.clone())
         .and(state_filter.clone())
         .and_then(crate::bot::web::obs::public_queue_data);
+    let public_queues_api = warp::path!("api" / "public" / "queues" / String)
+        .and(warp::get())
+        .and(warp::query::<QueueQuery>())
+        .and(pool_filter.clone())
+        .and(state_filter.clone())
+        .and_then(crate::bot::web::obs::public_queues_data);
+    let queue_export_link = warp::path!("x" / String)
+        .and(warp::get())
+        .and(warp::query::<crate::bot::web::obs::ExportQuery>())
+        .and(state_filter.clone())
+        .and_then(crate::bot::web::obs::queue_export_link);
     let public_queue_events_api = warp::path!("api" / "public" / "queue" / String / "events")
         .and(warp::get())
         .and(state_filter.clone())
@@ -293,19 +384,33 @@ async fn main() -> BotResult<()> {
     let options = warp::options()
         .map(|| warp::reply());
 
-    let routes = auth_twitch
+    // Skupiny se boxují zvlášť, jinak typ celého řetězce přeteče limit hloubky při buildu
+    let auth_routes = auth_twitch
     .or(auth_callback)
     .or(auth_kick)
     .or(auth_kick_callback)
-    .or(obs_combined)
+    .boxed();
+
+    let obs_queue_routes = obs_combined
     .or(obs_queue)
     .or(obs_next)
     .or(obs_remove)
+    .or(obs_ready)
     .or(obs_reorder)
     .or(obs_toggle)
     .or(obs_queue_size)
     .or(obs_queue_len)
     .or(obs_queue_reset)
+    .or(obs_queue_undo)
+    .or(obs_queue_export)
+    .or(obs_queue_import)
+    .or(obs_sse)
+    .boxed();
+
+    let obs_admin_routes = obs_bans
+    .or(obs_ban_lift)
+    .or(obs_history)
+    .or(obs_stats)
     .or(obs_aliases)
     .or(obs_aliases_add)
     .or(obs_aliases_remove)
@@ -316,12 +421,21 @@ async fn main() -> BotResult<()> {
     .or(obs_sessions)
     .or(obs_switch)
     .or(obs_logout)
-    .or(obs_sse)
-    .or(favicon)
+    .boxed();
+
+    let public_routes = favicon
     .or(public_queue_page)
     .or(public_queue_api)
+    .or(public_queues_api)
     .or(public_queue_events_api)
+    .or(queue_export_link)
     .or(options)
+    .boxed();
+
+    let routes = auth_routes
+    .or(obs_queue_routes)
+    .or(obs_admin_routes)
+    .or(public_routes)
     .with(cors)
     .boxed();
 